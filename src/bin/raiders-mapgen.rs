@@ -0,0 +1,125 @@
+use std::{fs, path::PathBuf};
+use clap::{App, Arg};
+use log::info;
+use raiders::{
+    config::{MovementConfig, TerrainConfig},
+    gen::{MapGenerator, Terrain},
+};
+
+/* Map generation tool */
+// Generates maps without starting the game. For every terrain type and
+// seed asked for, writes below the output directory:
+//   models/terrain-<terrain>-<seed>.obj    terrain mesh
+//   prefabs/map-<terrain>-<seed>.ron       scene prefab with the props
+//   heightmaps/<terrain>-<seed>.png        16-bit grayscale heightmap, or .raw
+//   heightmaps/<terrain>-<seed>.png.ron    heights the heightmap's black and white stand for
+//   minimaps/<terrain>-<seed>.png          hillshaded minimap
+//   stats/<terrain>-<seed>.json            height, slope and walkability summary
+//   scenes/<terrain>-<seed>.glb            whole scene as binary glTF
+fn main() -> amethyst::Result<()> {
+    amethyst::start_logger(Default::default());
+
+    let matches = App::new("raiders-mapgen")
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("Generates raiders maps and terrain statistics without the renderer")
+        .arg(Arg::with_name("terrain")
+            .long("terrain")
+            .value_name("TYPE")
+            .multiple(true)
+            .validator(|s| s.parse::<Terrain>().map(|_| ()))
+            .help("Terrain types to generate: mountains, foothills, coast, fault or a .png/.raw heightmap; \
+                   all generated types when not given"))
+        .arg(Arg::with_name("seed")
+            .long("seed")
+            .value_name("SEED")
+            .default_value("0")
+            .validator(|s| s.parse::<u64>().map(|_| ()).map_err(|e| e.to_string()))
+            .help("First seed to generate"))
+        .arg(Arg::with_name("count")
+            .long("count")
+            .value_name("N")
+            .default_value("1")
+            .validator(|s| s.parse::<u64>().map(|_| ()).map_err(|e| e.to_string()))
+            .help("Number of consecutive seeds to generate, starting at --seed"))
+        .arg(Arg::with_name("out")
+            .long("out")
+            .value_name("DIR")
+            .default_value("mapgen")
+            .help("Directory to write the generated files to"))
+        .arg(Arg::with_name("assets-dir")
+            .long("assets-dir")
+            .value_name("DIR")
+            .default_value("assets")
+            .help("Game assets directory, for the prop models included in the glTF scenes"))
+        .arg(Arg::with_name("max-slope")
+            .long("max-slope")
+            .value_name("DEGREES")
+            .validator(|s| s.parse::<f32>().map(|_| ()).map_err(|e| e.to_string()))
+            .help("Steepest walkable slope; defaults to the player's"))
+        .arg(Arg::with_name("max-error")
+            .long("max-error")
+            .value_name("METRES")
+            .validator(|s| s.parse::<f32>().map(|_| ()).map_err(|e| e.to_string()))
+            .help("Largest vertical error of the simplified terrain mesh, 0 to keep the full grid; \
+                   defaults to the game's"))
+        .arg(Arg::with_name("heightmap-format")
+            .long("heightmap-format")
+            .value_name("FORMAT")
+            .possible_values(&["png", "raw"])
+            .default_value("png")
+            .help("Write heightmaps as 16-bit PNG or little-endian RAW16"))
+        .get_matches();
+
+    let terrains: Vec<Terrain> = match matches.values_of("terrain") {
+        Some(values) => values.map(|s| s.parse().unwrap()).collect(),
+        None => vec![Terrain::Mountains, Terrain::Foothills, Terrain::Coast, Terrain::Fault],
+    };
+    let first: u64 = matches.value_of("seed").unwrap().parse().unwrap();
+    let count: u64 = matches.value_of("count").unwrap().parse().unwrap();
+    let max_slope: f32 = matches.value_of("max-slope")
+        .map(|s| s.parse().unwrap())
+        .unwrap_or(MovementConfig::default().max_slope);
+    let max_error: f32 = matches.value_of("max-error")
+        .map(|s| s.parse().unwrap())
+        .unwrap_or(TerrainConfig::default().max_error);
+    let format = matches.value_of("heightmap-format").unwrap();
+
+    let assets_dir = PathBuf::from(matches.value_of("assets-dir").unwrap());
+    let out = PathBuf::from(matches.value_of("out").unwrap());
+    for dir in &["models", "prefabs", "heightmaps", "minimaps", "stats", "scenes"] {
+        fs::create_dir_all(out.join(dir))?;
+    }
+
+    for seed in first..first + count {
+        for terrain in &terrains {
+            let mut gen = MapGenerator::new(terrain.clone(), seed, &out)
+                .map_err(amethyst::Error::from_string)?
+                .with_max_error(max_error);
+            gen.build_terrain();
+            gen.build_props();
+            gen.build_extraction();
+            gen.finish();
+
+            let name = format!("{}-{}", terrain, seed);
+            gen.height_grid()
+                .write(out.join("heightmaps").join(format!("{}.{}", name, format)))
+                .map_err(amethyst::Error::from_string)?;
+            gen.write_minimap(&out.join("minimaps").join(format!("{}.png", name)))
+                .map_err(amethyst::Error::from_string)?;
+
+            gen.export_glb(&assets_dir, &out.join("scenes").join(format!("{}.glb", name)))
+                .map_err(amethyst::Error::from_string)?;
+
+            let stats = gen.stats(max_slope);
+            let json = serde_json::to_string_pretty(&stats)?;
+            fs::write(out.join("stats").join(format!("{}.json", name)), json)?;
+
+            info!(
+                "{}: height {:.2}..{:.2}, mean slope {:.1}°, {:.1}% walkable",
+                name, stats.min_height, stats.max_height, stats.mean_slope, stats.walkable_percent,
+            );
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,120 @@
+use std::path::PathBuf;
+use clap::{App, Arg, ArgMatches};
+use log::LevelFilter;
+use crate::{gen::Terrain, sky::weather::WeatherKind};
+
+/* Launch options */
+// What the raiders binary was asked to do on the command line. Inserted as
+// a resource so the states can honour the map and menu choices
+#[derive(Clone, Debug, Default)]
+pub struct LaunchOptions {
+    // Map to generate; random when not given
+    pub seed: Option<u64>,
+    pub terrain: Option<Terrain>,
+    // Hour of the day the raid starts at; overrides config/mission.ron
+    pub start_hour: Option<f32>,
+    // Weather of the raid; picked for the map when not given
+    pub weather: Option<WeatherKind>,
+    // Go straight to the loading screen instead of waiting in the main menu
+    pub skip_menu: bool,
+    // Overrides for the directories next to the executable; main fills in
+    // the directories actually used before inserting the resource
+    pub config_dir: Option<PathBuf>,
+    pub assets_dir: Option<PathBuf>,
+    pub log_level: Option<LevelFilter>,
+    // Overrides config/display.ron
+    pub window_size: Option<(u32, u32)>,
+}
+
+impl LaunchOptions {
+    pub fn from_args() -> Self {
+        LaunchOptions::from_matches(&app().get_matches())
+    }
+
+    fn from_matches(matches: &ArgMatches<'_>) -> Self {
+        // Validators have already run, so parsing cannot fail here
+        LaunchOptions {
+            seed: matches.value_of("seed").map(|s| s.parse().unwrap()),
+            terrain: matches.value_of("terrain").map(|s| s.parse().unwrap()),
+            start_hour: matches.value_of("hour").map(|s| s.parse().unwrap()),
+            weather: matches.value_of("weather").map(|s| s.parse().unwrap()),
+            skip_menu: matches.is_present("skip-menu"),
+            config_dir: matches.value_of("config-dir").map(PathBuf::from),
+            assets_dir: matches.value_of("assets-dir").map(PathBuf::from),
+            log_level: matches.value_of("log-level").map(|s| s.parse().unwrap()),
+            window_size: match (matches.value_of("width"), matches.value_of("height")) {
+                (Some(w), Some(h)) => Some((w.parse().unwrap(), h.parse().unwrap())),
+                _ => None,
+            },
+        }
+    }
+}
+
+fn app() -> App<'static, 'static> {
+    App::new("raiders")
+        .version(env!("CARGO_PKG_VERSION"))
+        .arg(Arg::with_name("seed")
+            .long("seed")
+            .value_name("SEED")
+            .help("Seed for map generation")
+            .validator(|s| s.parse::<u64>().map(|_| ()).map_err(|e| e.to_string())))
+        .arg(Arg::with_name("terrain")
+            .long("terrain")
+            .value_name("TYPE")
+            .validator(|s| s.parse::<Terrain>().map(|_| ()))
+            .help("Terrain type to generate: mountains, foothills, coast, fault or a .png/.raw heightmap"))
+        .arg(Arg::with_name("hour")
+            .long("hour")
+            .value_name("HOUR")
+            .validator(hour)
+            .help("Hour of the day the raid starts at, from 0 to 24, overriding mission.ron"))
+        .arg(Arg::with_name("weather")
+            .long("weather")
+            .value_name("WEATHER")
+            .validator(|s| s.parse::<WeatherKind>().map(|_| ()))
+            .help("Weather of the raid: clear, fog, rain or wind; picked for the map when not given"))
+        .arg(Arg::with_name("skip-menu")
+            .long("skip-menu")
+            .help("Start generating the map immediately"))
+        .arg(Arg::with_name("config-dir")
+            .long("config-dir")
+            .value_name("DIR")
+            .help("Directory to load the .ron configuration from"))
+        .arg(Arg::with_name("assets-dir")
+            .long("assets-dir")
+            .value_name("DIR")
+            .help("Directory to load assets from and write generated maps to"))
+        .arg(Arg::with_name("log-level")
+            .long("log-level")
+            .value_name("LEVEL")
+            .possible_values(&["off", "error", "warn", "info", "debug", "trace"])
+            .help("Maximum level of log messages to print"))
+        .arg(Arg::with_name("width")
+            .long("width")
+            .value_name("PIXELS")
+            .requires("height")
+            .validator(dimension)
+            .help("Window width, overriding display.ron"))
+        .arg(Arg::with_name("height")
+            .long("height")
+            .value_name("PIXELS")
+            .requires("width")
+            .validator(dimension)
+            .help("Window height, overriding display.ron"))
+}
+
+fn dimension(s: String) -> Result<(), String> {
+    match s.parse::<u32>() {
+        Ok(0) => Err(String::from("must be greater than zero")),
+        Ok(_) => Ok(()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn hour(s: String) -> Result<(), String> {
+    match s.parse::<f32>() {
+        Ok(h) if h >= 0.0 && h <= 24.0 => Ok(()),
+        Ok(_) => Err(String::from("must be between 0 and 24")),
+        Err(e) => Err(e.to_string()),
+    }
+}
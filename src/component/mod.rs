@@ -0,0 +1,545 @@
+use amethyst::{
+    assets::PrefabData,
+    core::math::Vector3,
+    ecs::{Component, DenseVecStorage, Entity, NullStorage, WriteStorage},
+    Error,
+};
+use serde::{Deserialize, Serialize};
+use crate::{config::{DogTraits, MedicTraits}, gen::PropKind, intent::Hand};
+
+/* Unit collision capsule */
+// Kept in sync with the entity's Transform by ColliderSyncSystem.
+// `offset` is the height of the capsule centre above the transform origin
+#[derive(Clone, Copy, Debug)]
+pub struct Collider {
+    pub radius: f32,
+    pub half_height: f32,
+    pub offset: f32,
+}
+
+impl Component for Collider {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/* Ground-following character state */
+// The entity's Transform sits at eye level; the feet are `eye_height` below it
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CharacterController {
+    pub vertical_speed: f32,
+    pub eye_height: f32,
+    pub grounded: bool,
+    pub crouching: bool,
+}
+
+impl Component for CharacterController {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/* Tracked hand */
+// Child of the player entity, posed from PlayerIntent each frame
+#[derive(Clone, Copy, Debug)]
+pub struct HandPresence {
+    pub hand: Hand,
+    pub holding: Option<Entity>,
+    pub gripping: bool,
+}
+
+impl HandPresence {
+    pub fn new(hand: Hand) -> Self {
+        HandPresence {
+            hand: hand,
+            holding: None,
+            gripping: false,
+        }
+    }
+}
+
+impl Component for HandPresence {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/* World object that hands can pick up */
+// Needs a Collider so overlap queries can find it
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Grabbable {
+    pub held_by: Option<Hand>,
+}
+
+impl Component for Grabbable {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/* Two-handed bow */
+// `drawing` is the hand pulling the string, `draw` how far back it is in metres
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Bow {
+    pub drawing: Option<Hand>,
+    pub draw: f32,
+}
+
+impl Component for Bow {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/* Arrow or bolt in flight */
+// `drift` is the part of `velocity` the wind has added since the arrow was loosed
+#[derive(Clone, Copy, Debug)]
+pub struct Projectile {
+    pub velocity: Vector3<f32>,
+    pub drift: Vector3<f32>,
+    pub age: f32,
+}
+
+impl Component for Projectile {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/* Generated map prop */
+// Added to the entities of a generated map's prefab so systems can tell
+// what a building or tree is without going through Physics
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct MapProp {
+    pub kind: PropKind,
+}
+
+impl Component for MapProp {
+    type Storage = DenseVecStorage<Self>;
+}
+
+impl<'a> PrefabData<'a> for MapProp {
+    type SystemData = WriteStorage<'a, MapProp>;
+    type Result = ();
+
+    fn add_to_entity(
+        &self,
+        entity: Entity,
+        storage: &mut Self::SystemData,
+        _entities: &[Entity],
+        _children: &[Entity],
+        ) -> Result<(), Error> {
+        storage.insert(entity, *self)?;
+        Ok(())
+    }
+}
+
+/* Extraction zone */
+// Part of a generated map's prefab. The squad leaves the map once every
+// living member stands within `radius` metres of the zone's Transform
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct ExtractionZone {
+    pub radius: f32,
+}
+
+impl Component for ExtractionZone {
+    type Storage = DenseVecStorage<Self>;
+}
+
+impl<'a> PrefabData<'a> for ExtractionZone {
+    type SystemData = WriteStorage<'a, ExtractionZone>;
+    type Result = ();
+
+    fn add_to_entity(
+        &self,
+        entity: Entity,
+        storage: &mut Self::SystemData,
+        _entities: &[Entity],
+        _children: &[Entity],
+        ) -> Result<(), Error> {
+        storage.insert(entity, *self)?;
+        Ok(())
+    }
+}
+
+/* Squad member */
+// Units the player commands; the mission counts them as casualties and
+// waits for all of them to extract. The player is one too, with the
+// Player role, and never takes orders
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SquadRole {
+    Player,
+    Soldier,
+    Marksman,
+    Medic,
+    Dog,
+    Drone,
+}
+
+impl SquadRole {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SquadRole::Player => "You",
+            SquadRole::Soldier => "Soldier",
+            SquadRole::Marksman => "Marksman",
+            SquadRole::Medic => "Medic",
+            SquadRole::Dog => "Dog",
+            SquadRole::Drone => "Drone",
+        }
+    }
+
+    pub fn icon(&self) -> &'static str {
+        match self {
+            SquadRole::Player => "texture/control_playericon.png",
+            SquadRole::Soldier => "texture/control_squadmembericon.png",
+            SquadRole::Marksman => "texture/control_marksmanicon.png",
+            SquadRole::Medic => "texture/control_medicicon.png",
+            SquadRole::Dog => "texture/control_dogicon.png",
+            SquadRole::Drone => "texture/control_droneicon.png",
+        }
+    }
+}
+
+// Takedown and Bark are only carried out by dogs, Revive and Heal by medics
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Order {
+    Hold,
+    Follow,
+    MoveTo(Vector3<f32>),
+    Takedown(Entity),
+    Bark(Vector3<f32>),
+    Revive(Entity),
+    Heal(Entity),
+}
+
+impl Order {
+    pub fn description(&self) -> &'static str {
+        match self {
+            Order::Hold => "Holding",
+            Order::Follow => "Following",
+            Order::MoveTo(_) => "Moving",
+            Order::Takedown(_) => "Hunting",
+            Order::Bark(_) => "Distracting",
+            Order::Revive(_) => "Reviving",
+            Order::Heal(_) => "Healing",
+        }
+    }
+}
+
+// `speed` overrides the walking speed from MovementConfig
+#[derive(Clone, Copy, Debug)]
+pub struct SquadMember {
+    pub role: SquadRole,
+    pub order: Order,
+    pub speed: Option<f32>,
+}
+
+impl SquadMember {
+    pub fn new(role: SquadRole) -> Self {
+        SquadMember {
+            role: role,
+            order: Order::Hold,
+            speed: None,
+        }
+    }
+}
+
+impl Component for SquadMember {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/* Hit points */
+// A unit out of hit points is down. With a `bleed_time` it is only downed
+// at first: `bleeding` counts down the seconds left to revive it before
+// it dies. `regen` is healing still to come, as hit points per second and
+// the seconds it lasts
+#[derive(Clone, Copy, Debug)]
+pub struct Health {
+    pub current: f32,
+    pub max: f32,
+    pub bleed_time: f32,
+    pub bleeding: Option<f32>,
+    pub regen: Option<(f32, f32)>,
+}
+
+impl Health {
+    pub fn new(max: f32) -> Self {
+        Health {
+            current: max,
+            max: max,
+            bleed_time: 0.0,
+            bleeding: None,
+            regen: None,
+        }
+    }
+
+    // Out of action, downed or dead
+    pub fn is_down(&self) -> bool {
+        self.current <= 0.0
+    }
+
+    pub fn is_downed(&self) -> bool {
+        self.bleeding.is_some()
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.is_down() && !self.is_downed()
+    }
+
+    pub fn is_injured(&self) -> bool {
+        !self.is_down() && self.current < self.max
+    }
+
+    // Harm to a unit that is already downed finishes it off
+    pub fn damage(&mut self, amount: f32) {
+        if self.is_downed() {
+            self.bleeding = None;
+            return;
+        }
+        self.current = (self.current - amount).max(0.0);
+        if self.is_down() {
+            self.regen = None;
+            if self.bleed_time > 0.0 {
+                self.bleeding = Some(self.bleed_time);
+            }
+        }
+    }
+
+    // Gets a downed unit back up with `fraction` of its hit points
+    pub fn revive(&mut self, fraction: f32) {
+        if self.is_downed() {
+            self.bleeding = None;
+            self.current = (self.max * fraction).max(1.0).min(self.max);
+        }
+    }
+
+    pub fn heal_over_time(&mut self, rate: f32, duration: f32) {
+        if !self.is_down() {
+            self.regen = Some((rate, duration));
+        }
+    }
+}
+
+impl Component for Health {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/* Scout drone */
+// Squad member that flies above the terrain. `battery` counts down the
+// seconds of flight left; a drone with an empty battery has landed for good
+#[derive(Clone, Copy, Debug)]
+pub struct Drone {
+    pub battery: f32,
+    pub capacity: f32,
+}
+
+impl Drone {
+    pub fn new(capacity: f32) -> Self {
+        Drone {
+            battery: capacity,
+            capacity: capacity,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.battery <= 0.0
+    }
+
+    // Battery left in 0..1
+    pub fn charge(&self) -> f32 {
+        (self.battery / self.capacity.max(std::f32::EPSILON)).max(0.0).min(1.0)
+    }
+}
+
+impl Component for Drone {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/* Attack dog */
+// Squad member that smells out guards and can take them down or distract
+// them. `scent` is the closest guard it smells, `takedown` the seconds
+// spent on its current takedown and `cooldown` the seconds until it can bark again
+#[derive(Clone, Copy, Debug)]
+pub struct Dog {
+    pub traits: DogTraits,
+    pub scent: Option<Entity>,
+    pub takedown: f32,
+    pub cooldown: f32,
+}
+
+impl Dog {
+    pub fn new(traits: DogTraits) -> Self {
+        Dog {
+            traits: traits,
+            scent: None,
+            takedown: 0.0,
+            cooldown: 0.0,
+        }
+    }
+}
+
+impl Component for Dog {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/* Medic */
+// Squad member that gets downed comrades back up and treats the injured.
+// `progress` is the seconds spent on the current revive
+#[derive(Clone, Copy, Debug)]
+pub struct Medic {
+    pub traits: MedicTraits,
+    pub progress: f32,
+}
+
+impl Medic {
+    pub fn new(traits: MedicTraits) -> Self {
+        Medic {
+            traits: traits,
+            progress: 0.0,
+        }
+    }
+}
+
+impl Component for Medic {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/* Settlement guard */
+// Stands at its post looking along its Transform's -z axis. `awareness`
+// fills up in 0..1 while a squad member is in view and raises the alarm
+// when full. `known` guards have been seen by the squad and show up on
+// the minimap. A guard that hears a noise walks over to `investigate`
+// it, searches there for a while and then returns to its post
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Guard {
+    pub awareness: f32,
+    pub alerted: bool,
+    pub known: bool,
+    // Where the guard stands and faces when not investigating
+    pub post: [f32; 3],
+    pub post_yaw: f32,
+    pub investigate: Option<[f32; 3]>,
+    // Seconds spent searching at the noise
+    pub searched: f32,
+}
+
+impl Guard {
+    pub fn new(post: [f32; 3], post_yaw: f32) -> Self {
+        Guard {
+            post: post,
+            post_yaw: post_yaw,
+            ..Default::default()
+        }
+    }
+}
+
+impl Component for Guard {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/* Mission target */
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MissionTarget;
+
+impl Component for MissionTarget {
+    type Storage = NullStorage<Self>;
+}
+
+/* Sun */
+// Directional light that follows the time of day
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Sun;
+
+impl Component for Sun {
+    type Storage = NullStorage<Self>;
+}
+
+/* Loot waiting to be stolen */
+#[derive(Clone, Copy, Debug)]
+pub struct Loot {
+    pub value: u32,
+}
+
+impl Component for Loot {
+    type Storage = DenseVecStorage<Self>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit(bleed_time: f32) -> Health {
+        Health { bleed_time: bleed_time, ..Health::new(100.0) }
+    }
+
+    #[test]
+    fn damage_injures_then_downs() {
+        let mut health = unit(30.0);
+        health.damage(40.0);
+        assert_eq!(health.current, 60.0);
+        assert!(health.is_injured() && !health.is_down());
+
+        health.damage(100.0);
+        assert_eq!(health.current, 0.0);
+        assert!(health.is_down() && health.is_downed() && !health.is_dead());
+        assert_eq!(health.bleeding, Some(30.0));
+    }
+
+    #[test]
+    fn damage_kills_outright_without_a_bleed_time() {
+        let mut health = unit(0.0);
+        health.damage(150.0);
+        assert!(health.is_dead() && !health.is_downed());
+    }
+
+    #[test]
+    fn damage_finishes_off_a_downed_unit() {
+        let mut health = unit(30.0);
+        health.damage(100.0);
+        health.damage(1.0);
+        assert!(health.is_dead());
+        assert_eq!(health.bleeding, None);
+    }
+
+    #[test]
+    fn damage_stops_healing_once_down() {
+        let mut health = unit(30.0);
+        health.damage(50.0);
+        health.heal_over_time(5.0, 10.0);
+        health.damage(50.0);
+        assert_eq!(health.regen, None);
+    }
+
+    #[test]
+    fn revive_gets_a_downed_unit_back_up() {
+        let mut health = unit(30.0);
+        health.damage(100.0);
+        health.revive(0.25);
+        assert!(!health.is_down() && !health.is_downed());
+        assert_eq!(health.current, 25.0);
+
+        // Always with at least one hit point, and never more than the maximum
+        let mut health = unit(30.0);
+        health.damage(100.0);
+        health.revive(0.0);
+        assert_eq!(health.current, 1.0);
+        let mut health = unit(30.0);
+        health.damage(100.0);
+        health.revive(2.0);
+        assert_eq!(health.current, 100.0);
+    }
+
+    #[test]
+    fn revive_leaves_standing_and_dead_units_alone() {
+        let mut standing = unit(30.0);
+        standing.damage(40.0);
+        standing.revive(1.0);
+        assert_eq!(standing.current, 60.0);
+
+        let mut dead = unit(0.0);
+        dead.damage(100.0);
+        dead.revive(1.0);
+        assert!(dead.is_dead());
+    }
+
+    #[test]
+    fn heal_over_time_only_treats_units_still_standing() {
+        let mut health = unit(30.0);
+        health.damage(40.0);
+        health.heal_over_time(5.0, 4.0);
+        assert_eq!(health.regen, Some((5.0, 4.0)));
+
+        let mut downed = unit(30.0);
+        downed.damage(100.0);
+        downed.heal_over_time(5.0, 4.0);
+        assert_eq!(downed.regen, None);
+    }
+}
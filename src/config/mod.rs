@@ -0,0 +1,465 @@
+use serde::{Deserialize, Serialize};
+
+/* Player movement settings */
+// Loaded from config/movement.ron; speeds are in metres per second,
+// `max_slope` is in degrees. Footsteps carry `walk_noise` metres when
+// walking and `run_noise` when running; crouching is silent
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct MovementConfig {
+    pub walk_speed: f32,
+    pub run_speed: f32,
+    pub crouch_speed: f32,
+    pub gravity: f32,
+    pub step_height: f32,
+    pub max_slope: f32,
+    pub eye_height: f32,
+    pub crouch_eye_height: f32,
+    pub walk_noise: f32,
+    pub run_noise: f32,
+}
+
+impl Default for MovementConfig {
+    fn default() -> Self {
+        MovementConfig {
+            walk_speed: 3.0,
+            run_speed: 6.0,
+            crouch_speed: 1.5,
+            gravity: 9.81,
+            step_height: 0.4,
+            max_slope: 40.0,
+            eye_height: 1.7,
+            crouch_eye_height: 1.0,
+            walk_noise: 6.0,
+            run_noise: 15.0,
+        }
+    }
+}
+
+/* Commander camera settings */
+// Loaded from config/camera.ron; `edge_margin` is in pixels,
+// angles are in degrees and `transition_time` is in seconds
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct CameraConfig {
+    pub edge_margin: f32,
+    pub scroll_speed: f32,
+    pub zoom_speed: f32,
+    pub rotate_speed: f32,
+    pub min_distance: f32,
+    pub max_distance: f32,
+    pub pitch: f32,
+    pub transition_time: f32,
+}
+
+impl Default for CameraConfig {
+    fn default() -> Self {
+        CameraConfig {
+            edge_margin: 20.0,
+            scroll_speed: 1.0,
+            zoom_speed: 2.0,
+            rotate_speed: 90.0,
+            min_distance: 10.0,
+            max_distance: 80.0,
+            pitch: 55.0,
+            transition_time: 0.75,
+        }
+    }
+}
+
+/* Input backend selection */
+// Loaded from config/input.ron. `SimulatedXr` plays back the
+// given script (relative to the config directory) in place of a headset
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum InputBackend {
+    Desktop,
+    SimulatedXr(String),
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct InputConfig {
+    pub backend: InputBackend,
+}
+
+impl Default for InputConfig {
+    fn default() -> Self {
+        InputConfig {
+            backend: InputBackend::Desktop,
+        }
+    }
+}
+
+/* VR settings */
+// Loaded from config/vr.ron. `Mock` renders both eyes side-by-side
+// in the desktop window; `ipd` and the clip planes are in metres,
+// `fov` is the vertical field of view per eye in degrees. With
+// `world_ui` the menus are shown on a panel `ui_distance` metres in
+// front of the player, at `ui_scale` metres per UI pixel
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+pub enum VrMode {
+    Off,
+    Mock,
+    OpenXr,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct VrConfig {
+    pub mode: VrMode,
+    pub ipd: f32,
+    pub fov: f32,
+    pub znear: f32,
+    pub zfar: f32,
+    pub world_ui: bool,
+    pub ui_distance: f32,
+    pub ui_scale: f32,
+}
+
+impl Default for VrConfig {
+    fn default() -> Self {
+        VrConfig {
+            mode: VrMode::Off,
+            ipd: 0.064,
+            fov: 100.0,
+            znear: 0.05,
+            zfar: 2000.0,
+            world_ui: false,
+            ui_distance: 1.5,
+            ui_scale: 0.0015,
+        }
+    }
+}
+
+/* Comfort locomotion settings */
+// Loaded from config/locomotion.ron. Angles are in degrees,
+// `launch_speed` is the initial speed of the teleport arc in metres per
+// second and `fade_time` the length of each half of the fade in seconds
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+pub enum MoveMode {
+    Smooth,
+    Teleport,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+pub enum TurnMode {
+    Snap,
+    Smooth,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct LocomotionConfig {
+    pub movement: MoveMode,
+    pub turn: TurnMode,
+    pub snap_angle: f32,
+    pub turn_speed: f32,
+    pub launch_speed: f32,
+    pub max_slope: f32,
+    pub fade_time: f32,
+}
+
+impl Default for LocomotionConfig {
+    fn default() -> Self {
+        LocomotionConfig {
+            movement: MoveMode::Smooth,
+            turn: TurnMode::Snap,
+            snap_angle: 30.0,
+            turn_speed: 90.0,
+            launch_speed: 8.0,
+            max_slope: 30.0,
+            fade_time: 0.15,
+        }
+    }
+}
+
+/* Generated map cache settings */
+// Loaded from config/cache.ron; `max_size` is in megabytes. With `reuse`
+// off maps are always generated anew, but still cleaned up
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct CacheConfig {
+    pub reuse: bool,
+    pub max_size: u64,
+}
+
+impl CacheConfig {
+    pub fn max_bytes(&self) -> u64 {
+        self.max_size * 1024 * 1024
+    }
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig {
+            reuse: true,
+            max_size: 256,
+        }
+    }
+}
+
+/* Terrain mesh settings */
+// Loaded from config/terrain.ron; `max_error` is how far in metres the
+// simplified terrain may deviate vertically from the generated heightmap,
+// 0 keeps every vertex
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct TerrainConfig {
+    pub max_error: f32,
+}
+
+impl Default for TerrainConfig {
+    fn default() -> Self {
+        TerrainConfig {
+            max_error: 0.05,
+        }
+    }
+}
+
+/* Mission settings */
+// Loaded from config/mission.ron: guard vision, loot pickup, scoring, the alarm and bleeding out
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct MissionConfig {
+    pub view_distance: f32,
+    pub view_angle: f32,
+    pub detection_time: f32,
+    pub loot_radius: f32,
+    pub par_time: f32,
+    pub objective_points: i32,
+    pub time_points: f32,
+    pub detection_penalty: i32,
+    pub casualty_penalty: i32,
+    pub alarm_timeout: f32,
+    pub bleed_time: f32,
+}
+
+impl Default for MissionConfig {
+    fn default() -> Self {
+        MissionConfig {
+            view_distance: 25.0,
+            view_angle: 110.0,
+            detection_time: 1.5,
+            loot_radius: 1.5,
+            par_time: 600.0,
+            objective_points: 1000,
+            time_points: 2.0,
+            detection_penalty: 250,
+            casualty_penalty: 500,
+            alarm_timeout: 90.0,
+            bleed_time: 45.0,
+        }
+    }
+}
+
+/* Guard settings */
+// Loaded from config/guard.ron: how guards search out noises and shoot at the squad
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct GuardConfig {
+    pub speed: f32,
+    pub search_time: f32,
+    pub attack_range: f32,
+    pub attack_interval: f32,
+    pub attack_damage: f32,
+}
+
+impl Default for GuardConfig {
+    fn default() -> Self {
+        GuardConfig {
+            speed: 2.0,
+            search_time: 8.0,
+            attack_range: 30.0,
+            attack_interval: 1.5,
+            attack_damage: 20.0,
+        }
+    }
+}
+
+/* Time of day settings */
+// Loaded from config/time.ron; `time_scale` is how many times faster than real time the day runs
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct TimeConfig {
+    pub start_hour: f32,
+    pub time_scale: f32,
+}
+
+impl Default for TimeConfig {
+    fn default() -> Self {
+        TimeConfig {
+            start_hour: 19.0,
+            time_scale: 30.0,
+        }
+    }
+}
+
+/* Drone settings */
+// Loaded from config/drone.ron. Drones fly `altitude` metres above the
+// ground at `speed` metres per second, climbing or sinking at most
+// `climb_rate` metres per second to follow it. A full battery lasts
+// `battery` seconds of flight, and a flying drone sees `reveal_radius`
+// metres around it
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct DroneConfig {
+    pub altitude: f32,
+    pub speed: f32,
+    pub climb_rate: f32,
+    pub battery: f32,
+    pub reveal_radius: f32,
+}
+
+impl Default for DroneConfig {
+    fn default() -> Self {
+        DroneConfig {
+            altitude: 12.0,
+            speed: 6.0,
+            climb_rate: 4.0,
+            battery: 180.0,
+            reveal_radius: 20.0,
+        }
+    }
+}
+
+/* Fog of war settings */
+// Loaded from config/fog.ron. The fog grid has a cell every `spacing`
+// metres, rounded to a whole number of navigation grid cells so the two
+// grids line up, and is updated every `update_interval` seconds. Squad members
+// see `view_radius` metres around them from eyes `eye_height` metres
+// above the ground
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct FogConfig {
+    pub spacing: f32,
+    pub update_interval: f32,
+    pub view_radius: f32,
+    pub eye_height: f32,
+}
+
+impl Default for FogConfig {
+    fn default() -> Self {
+        FogConfig {
+            spacing: 2.0,
+            update_interval: 0.2,
+            view_radius: 30.0,
+            eye_height: 1.6,
+        }
+    }
+}
+
+/* Weather settings */
+// Loaded from config/weather.ron. Fog hides everything beyond
+// `fog_distance` metres. Guards and the squad see the `fog_sight`
+// fraction of their usual range in fog and `rain_sight` in rain, and
+// footsteps carry `rain_hearing` as far in rain and `wind_hearing` in
+// wind. Wind blows at `wind_speed` metres per second, other weather
+// than fog brings a `breeze_speed` breeze
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct WeatherConfig {
+    pub fog_distance: f32,
+    pub fog_sight: f32,
+    pub rain_sight: f32,
+    pub rain_hearing: f32,
+    pub wind_hearing: f32,
+    pub wind_speed: f32,
+    pub breeze_speed: f32,
+}
+
+impl Default for WeatherConfig {
+    fn default() -> Self {
+        WeatherConfig {
+            fog_distance: 60.0,
+            fog_sight: 0.4,
+            rain_sight: 0.7,
+            rain_hearing: 0.5,
+            wind_hearing: 0.7,
+            wind_speed: 8.0,
+            breeze_speed: 2.0,
+        }
+    }
+}
+
+/* Unit definitions */
+// Loaded from assets/units/<unit>.ron when the squad is spawned. A unit
+// walks at `speed` metres per second with a capsule of `radius` and
+// `half_height`, centred `radius + half_height` above the ground. Traits
+// of a unit kind are only given for units of that kind
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct UnitDefinition {
+    pub prefab: String,
+    pub health: f32,
+    pub speed: f32,
+    pub radius: f32,
+    pub half_height: f32,
+    pub dog: Option<DogTraits>,
+    pub medic: Option<MedicTraits>,
+}
+
+impl Default for UnitDefinition {
+    fn default() -> Self {
+        UnitDefinition {
+            prefab: String::from("prefabs/soldier.ron"),
+            health: 100.0,
+            speed: 3.0,
+            radius: 0.3,
+            half_height: 0.6,
+            dog: None,
+            medic: None,
+        }
+    }
+}
+
+// A dog smells guards within `scent_radius` metres through walls, takes
+// a guard down silently after `takedown_time` seconds within
+// `takedown_range` metres of it, and barks loud enough for guards
+// `bark_radius` metres away to hear, at most every `bark_cooldown` seconds
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct DogTraits {
+    pub scent_radius: f32,
+    pub takedown_range: f32,
+    pub takedown_time: f32,
+    pub bark_radius: f32,
+    pub bark_cooldown: f32,
+}
+
+impl Default for DogTraits {
+    fn default() -> Self {
+        DogTraits {
+            scent_radius: 30.0,
+            takedown_range: 1.2,
+            takedown_time: 1.0,
+            bark_radius: 25.0,
+            bark_cooldown: 10.0,
+        }
+    }
+}
+
+// A medic works on a squad member within `reach` metres of it: a downed
+// one gets back up with `revive_health` of its hit points after
+// `revive_time` seconds, an injured one regains `heal_rate` hit points
+// per second for `heal_time` seconds
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct MedicTraits {
+    pub reach: f32,
+    pub revive_time: f32,
+    pub revive_health: f32,
+    pub heal_rate: f32,
+    pub heal_time: f32,
+}
+
+impl Default for MedicTraits {
+    fn default() -> Self {
+        MedicTraits {
+            reach: 1.5,
+            revive_time: 4.0,
+            revive_health: 0.3,
+            heal_rate: 5.0,
+            heal_time: 10.0,
+        }
+    }
+}
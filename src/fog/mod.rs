@@ -0,0 +1,188 @@
+use crate::gen::navmesh::NavMesh;
+
+// Height above the ground of what a unit looks at: a standing unit's head
+const TARGET_HEIGHT: f32 = 1.6;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Visibility {
+    // Never seen by the squad
+    Hidden,
+    // Seen before, but out of sight now
+    Explored,
+    Visible,
+}
+
+/* Fog of war */
+// What the squad sees of the map, on a coarser copy of the NavMesh grid:
+// cells sit on navigation grid points indexed by row (z) then column (x).
+// Every update clears the visible cells and each friendly unit reveals the
+// cells around it that the terrain does not hide from its eyes; revealed
+// cells stay explored for good
+#[derive(Clone, Debug)]
+pub struct FogOfWar {
+    size: usize,
+    min: (f32, f32),
+    spacing: (f32, f32),
+    heights: Vec<f32>,
+    explored: Vec<bool>,
+    visible: Vec<bool>,
+}
+
+impl FogOfWar {
+    // Takes every `stride`th point of the navigation grid along both axes,
+    // so fog cells sit exactly on navigation cells and share their heights
+    pub fn from_nav(nav: &NavMesh, stride: usize) -> Self {
+        let stride = stride.max(1);
+        let size = (nav.size() - 1) / stride + 1;
+        let origin = nav.position(0);
+        let spacing = (nav.spacing().0 * stride as f32, nav.spacing().1 * stride as f32);
+        let mut heights = Vec::with_capacity(size * size);
+        for row in 0..size {
+            for col in 0..size {
+                heights.push(nav.position(row * stride * nav.size() + col * stride)[1]);
+            }
+        }
+
+        FogOfWar {
+            size: size,
+            min: (origin[0], origin[2]),
+            spacing: spacing,
+            heights: heights,
+            explored: vec![false; size * size],
+            visible: vec![false; size * size],
+        }
+    }
+
+    pub fn size(&self) -> usize { self.size }
+
+    // Point on the ground at the centre of `cell`
+    pub fn position(&self, cell: usize) -> [f32; 3] {
+        let (row, col) = (cell / self.size, cell % self.size);
+        [
+            self.min.0 + col as f32 * self.spacing.0,
+            self.heights[cell],
+            self.min.1 + row as f32 * self.spacing.1,
+        ]
+    }
+
+    // Cell closest to (x, z), if that lies on the map
+    pub fn cell_at(&self, x: f32, z: f32) -> Option<usize> {
+        let col = ((x - self.min.0) / self.spacing.0).round();
+        let row = ((z - self.min.1) / self.spacing.1).round();
+        if col < 0.0 || row < 0.0 || col as usize >= self.size || row as usize >= self.size {
+            return None;
+        }
+        Some(row as usize * self.size + col as usize)
+    }
+
+    pub fn visibility(&self, cell: usize) -> Visibility {
+        if self.visible.get(cell).cloned().unwrap_or(false) {
+            Visibility::Visible
+        } else if self.explored.get(cell).cloned().unwrap_or(false) {
+            Visibility::Explored
+        } else {
+            Visibility::Hidden
+        }
+    }
+
+    // Off the map counts as hidden
+    pub fn visibility_at(&self, x: f32, z: f32) -> Visibility {
+        self.cell_at(x, z).map_or(Visibility::Hidden, |cell| self.visibility(cell))
+    }
+
+    pub fn is_visible(&self, x: f32, z: f32) -> bool {
+        self.visibility_at(x, z) == Visibility::Visible
+    }
+
+    pub fn is_explored(&self, x: f32, z: f32) -> bool {
+        self.visibility_at(x, z) != Visibility::Hidden
+    }
+
+    // Ground height at (x, z), interpolated between the grid points and clamped to the map's edges
+    pub fn ground_height(&self, x: f32, z: f32) -> f32 {
+        let last = (self.size - 1) as f32;
+        let u = ((x - self.min.0) / self.spacing.0).max(0.0).min(last);
+        let v = ((z - self.min.1) / self.spacing.1).max(0.0).min(last);
+        let (col, row) = (u.floor() as usize, v.floor() as usize);
+        let (next_col, next_row) = ((col + 1).min(self.size - 1), (row + 1).min(self.size - 1));
+        let at = |row: usize, col: usize| self.heights[row * self.size + col];
+        let (fx, fz) = (u - col as f32, v - row as f32);
+
+        let top = at(row, col) * (1.0 - fx) + at(row, next_col) * fx;
+        let bottom = at(next_row, col) * (1.0 - fx) + at(next_row, next_col) * fx;
+        top * (1.0 - fz) + bottom * fz
+    }
+
+    // Starts an update: nothing is in sight until revealed again
+    pub fn clear_visible(&mut self) {
+        for visible in self.visible.iter_mut() {
+            *visible = false;
+        }
+    }
+
+    pub fn reveal_cell(&mut self, cell: usize) {
+        if cell < self.visible.len() {
+            self.visible[cell] = true;
+            self.explored[cell] = true;
+        }
+    }
+
+    // Reveals the cells within `radius` metres of `eye` that the terrain does not hide from it
+    pub fn reveal(&mut self, eye: [f32; 3], radius: f32) {
+        let [x, _, z] = eye;
+        let cols = ((x - radius - self.min.0) / self.spacing.0).floor().max(0.0) as usize
+            ..((x + radius - self.min.0) / self.spacing.0).ceil().max(0.0) as usize + 1;
+        let rows = ((z - radius - self.min.1) / self.spacing.1).floor().max(0.0) as usize
+            ..((z + radius - self.min.1) / self.spacing.1).ceil().max(0.0) as usize + 1;
+
+        for row in rows.start..rows.end.min(self.size) {
+            for col in cols.start..cols.end.min(self.size) {
+                let cell = row * self.size + col;
+                let [cx, _, cz] = self.position(cell);
+                if (cx - x).powi(2) + (cz - z).powi(2) <= radius * radius && self.line_of_sight(eye, cell) {
+                    self.reveal_cell(cell);
+                }
+            }
+        }
+    }
+
+    // Whether a unit standing in `cell` can be seen from `eye`, with no ground between them rising above the line of sight
+    fn line_of_sight(&self, eye: [f32; 3], cell: usize) -> bool {
+        let [x, y, z] = self.position(cell);
+        let target = [x, y + TARGET_HEIGHT, z];
+        let distance = ((target[0] - eye[0]).powi(2) + (target[2] - eye[2]).powi(2)).sqrt();
+        // One sample per grid step is enough to catch every ridge the grid holds
+        let steps = (distance / self.spacing.0.min(self.spacing.1)).ceil() as usize;
+
+        (1..steps).all(|i| {
+            let t = i as f32 / steps as f32;
+            let px = eye[0] + (target[0] - eye[0]) * t;
+            let pz = eye[2] + (target[2] - eye[2]) * t;
+            let py = eye[1] + (target[1] - eye[1]) * t;
+            self.ground_height(px, pz) <= py
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fog_cells_sit_on_navigation_cells() {
+        let height = |x: f32, z: f32| Some(x * 0.1 + z * 0.05);
+        let nav = NavMesh::sample(13, (-6.0, -9.0), (6.0, 3.0), height, &[], 45.0);
+        let fog = FogOfWar::from_nav(&nav, 3);
+        assert_eq!(fog.size(), 5);
+
+        for cell in 0..fog.size() * fog.size() {
+            let [x, y, z] = fog.position(cell);
+            let on_nav = nav.position(nav.cell_at(x, z).unwrap());
+            assert!((on_nav[0] - x).abs() < 1e-4 && (on_nav[2] - z).abs() < 1e-4);
+            assert!((on_nav[1] - y).abs() < 1e-4);
+            assert_eq!(fog.cell_at(x, z), Some(cell));
+        }
+        let [x, _, z] = fog.position(fog.size() * fog.size() - 1);
+        assert_eq!((x, z), (6.0, 3.0));
+    }
+}
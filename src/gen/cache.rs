@@ -0,0 +1,233 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use log::{info, warn};
+use ncollide3d::procedural::{IndexBuffer, TriMesh};
+use serde::{Deserialize, Serialize};
+use wavefront_obj::obj::{self, Primitive};
+use super::{MapGenerator, Prop, Terrain, GENERATOR_VERSION};
+
+// Directory below the assets directory that holds the cache
+const CACHE_DIR: &str = "cache";
+const META_FILE: &str = "meta.ron";
+const MODEL_FILE: &str = "terrain.obj";
+const PREFAB_FILE: &str = "map.ron";
+const MINIMAP_FILE: &str = "minimap.png";
+
+/* Generated map cache */
+// Every generated map gets its own directory under assets/cache, named
+// after its terrain type, seed and the generator version. Requesting the
+// same map again reuses the files instead of generating it anew. Custom
+// terrain is cached too, so it gets cleaned up, but never reused since
+// its heightmap may have been edited
+pub struct MapCache {
+    assets_dir: PathBuf,
+    max_bytes: u64,
+}
+
+// What is needed to play a map without generating it again
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct CacheMeta {
+    terrain: Terrain,
+    seed: u64,
+    version: u32,
+    // The terrain model depends on how far it was simplified
+    max_error: f32,
+    props: Vec<Prop>,
+    // Seconds since the Unix epoch
+    last_used: u64,
+}
+
+pub struct CachedMap {
+    pub mesh: TriMesh<f32>,
+    pub props: Vec<Prop>,
+    // Relative to the assets directory
+    pub prefab_path: String,
+    pub minimap_path: String,
+}
+
+impl MapCache {
+    pub fn new(assets_dir: &Path, max_bytes: u64) -> Self {
+        MapCache {
+            assets_dir: assets_dir.to_path_buf(),
+            max_bytes: max_bytes,
+        }
+    }
+
+    fn key(terrain: &Terrain, seed: u64) -> String {
+        format!("{}-{}-v{}", terrain, seed, GENERATOR_VERSION)
+    }
+
+    // Paths of the terrain model and map prefab, relative to the assets directory
+    pub fn paths(terrain: &Terrain, seed: u64) -> (String, String) {
+        let dir = format!("{}/{}", CACHE_DIR, MapCache::key(terrain, seed));
+        (format!("{}/{}", dir, MODEL_FILE), format!("{}/{}", dir, PREFAB_FILE))
+    }
+
+    // Path of the minimap image, relative to the assets directory
+    pub fn minimap_path(terrain: &Terrain, seed: u64) -> String {
+        format!("{}/{}/{}", CACHE_DIR, MapCache::key(terrain, seed), MINIMAP_FILE)
+    }
+
+    // Generator that writes into the map's cache directory
+    pub fn generator(&self, terrain: Terrain, seed: u64, max_error: f32) -> Result<MapGenerator, String> {
+        let dir = self.assets_dir.join(CACHE_DIR).join(MapCache::key(&terrain, seed));
+        fs::create_dir_all(&dir)
+            .map_err(|e| format!("[ERROR][raiders::gen] Cannot create cache directory: {}", e))?;
+
+        let (model, prefab) = MapCache::paths(&terrain, seed);
+        Ok(MapGenerator::new(terrain, seed, &self.assets_dir)?
+            .with_paths(model, prefab)
+            .with_max_error(max_error))
+    }
+
+    // Renders the minimap of a finished map and records the map so it can be reused
+    pub fn store(&self, gen: &MapGenerator) -> Result<(), String> {
+        gen.write_minimap(&self.assets_dir.join(MapCache::minimap_path(gen.map_type(), gen.seed())))?;
+
+        let meta = CacheMeta {
+            terrain: gen.map_type().clone(),
+            seed: gen.seed(),
+            version: GENERATOR_VERSION,
+            max_error: gen.max_error(),
+            props: gen.props().to_vec(),
+            last_used: now(),
+        };
+        self.write_meta(&meta)
+    }
+
+    pub fn load(&self, terrain: &Terrain, seed: u64, max_error: f32) -> Option<CachedMap> {
+        if let Terrain::Custom(_) = terrain {
+            return None;
+        }
+
+        let dir = self.assets_dir.join(CACHE_DIR).join(MapCache::key(terrain, seed));
+        let mut meta: CacheMeta = ron::de::from_str(&fs::read_to_string(dir.join(META_FILE)).ok()?).ok()?;
+        if meta.max_error != max_error || !dir.join(PREFAB_FILE).is_file() || !dir.join(MINIMAP_FILE).is_file() {
+            return None;
+        }
+        let mesh = match load_mesh(&dir.join(MODEL_FILE)) {
+            Ok(mesh) => mesh,
+            Err(e) => {
+                warn!("Ignoring cached map {}: {}", MapCache::key(terrain, seed), e);
+                return None;
+            },
+        };
+
+        meta.last_used = now();
+        if let Err(e) = self.write_meta(&meta) {
+            warn!("{}", e);
+        }
+
+        Some(CachedMap {
+            mesh: mesh,
+            props: meta.props,
+            prefab_path: MapCache::paths(terrain, seed).1,
+            minimap_path: MapCache::minimap_path(terrain, seed),
+        })
+    }
+
+    fn write_meta(&self, meta: &CacheMeta) -> Result<(), String> {
+        let path = self.assets_dir.join(CACHE_DIR)
+            .join(MapCache::key(&meta.terrain, meta.seed))
+            .join(META_FILE);
+        let text = ron::ser::to_string(meta)
+            .map_err(|e| format!("[ERROR][raiders::gen] Cannot serialise cache entry: {}", e))?;
+        fs::write(path, text)
+            .map_err(|e| format!("[ERROR][raiders::gen] Cannot write cache entry: {}", e))
+    }
+
+    // Removes entries from other generator versions and unfinished ones,
+    // then the least recently used until the cache fits in its size limit
+    pub fn cleanup(&self) {
+        let root = self.assets_dir.join(CACHE_DIR);
+        let dirs = match fs::read_dir(&root) {
+            Ok(dirs) => dirs,
+            Err(_) => return,
+        };
+
+        let mut entries = Vec::new();
+        for dir in dirs.filter_map(Result::ok).map(|entry| entry.path()).filter(|path| path.is_dir()) {
+            let meta: Option<CacheMeta> = fs::read_to_string(dir.join(META_FILE)).ok()
+                .and_then(|text| ron::de::from_str(&text).ok());
+            match meta {
+                Some(meta) if meta.version == GENERATOR_VERSION => {
+                    entries.push((meta.last_used, dir_size(&dir), dir));
+                },
+                _ => remove(&dir),
+            }
+        }
+
+        entries.sort_by_key(|(last_used, _, _)| *last_used);
+        let mut total: u64 = entries.iter().map(|(_, size, _)| size).sum();
+        for (_, size, dir) in entries {
+            if total <= self.max_bytes {
+                break;
+            }
+            remove(&dir);
+            total -= size;
+        }
+
+        // Files from before the cache, which were never cleaned up
+        for (dir, prefix, extension) in &[("prefabs", "map-", "ron"), ("models", "terrain-", "obj")] {
+            let files = match fs::read_dir(self.assets_dir.join(dir)) {
+                Ok(files) => files,
+                Err(_) => continue,
+            };
+            for path in files.filter_map(Result::ok).map(|entry| entry.path()) {
+                let stale = path.file_name().map_or(false, |name| name.to_string_lossy().starts_with(prefix))
+                    && path.extension().map_or(false, |e| e == *extension);
+                if stale {
+                    let _ = fs::remove_file(&path);
+                }
+            }
+        }
+    }
+}
+
+// Reads a terrain OBJ written by MapGenerator::build_terrain back into a mesh
+fn load_mesh(path: &Path) -> Result<TriMesh<f32>, String> {
+    let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let set = obj::parse(text).map_err(|e| format!("{:?}", e))?;
+    let object = set.objects.first().ok_or("no terrain object")?;
+
+    let coords = object.vertices.iter()
+        .map(|v| [v.x as f32, v.y as f32, v.z as f32].into())
+        .collect();
+    let uvs = object.tex_vertices.iter()
+        .map(|t| [t.u as f32, t.v as f32].into())
+        .collect();
+    let indices = object.geometry.iter()
+        .flat_map(|geometry| geometry.shapes.iter())
+        .filter_map(|shape| match &shape.primitive {
+            Primitive::Triangle(a, b, c) => Some([a.0 as u32, b.0 as u32, c.0 as u32].into()),
+            _ => None,
+        })
+        .collect();
+
+    let mut mesh = TriMesh::new(coords, None, Some(uvs), Some(IndexBuffer::Unified(indices)));
+    mesh.recompute_normals();
+    Ok(mesh)
+}
+
+fn dir_size(dir: &Path) -> u64 {
+    fs::read_dir(dir)
+        .map(|files| files.filter_map(Result::ok)
+            .filter_map(|entry| entry.metadata().ok())
+            .map(|metadata| metadata.len())
+            .sum())
+        .unwrap_or(0)
+}
+
+fn remove(dir: &Path) {
+    info!("Removing cached map {}", dir.display());
+    if let Err(e) = fs::remove_dir_all(dir) {
+        warn!("Cannot remove {}: {}", dir.display(), e);
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
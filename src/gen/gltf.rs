@@ -0,0 +1,251 @@
+use std::{collections::HashMap, fs, path::Path};
+use serde_json::{json, Value};
+use wavefront_obj::obj::{self, Primitive};
+use super::{Material, MapGenerator, PropKind, TERRAIN_COLOUR};
+
+/* glTF export */
+// Writes the generated scene as a binary glTF 2.0 file: one node for the
+// terrain and one per prop, all under a root node, with the same flat
+// colours the prefab uses. Prop models are read from `assets_dir`
+pub fn export_glb(gen: &MapGenerator, assets_dir: &Path, path: &Path) -> Result<(), String> {
+    let mut scene = Scene::default();
+
+    let terrain_colour = colour(&Material::Srgba(TERRAIN_COLOUR));
+    let terrain_material = scene.material("Terrain", terrain_colour);
+    let mesh = gen.export_mesh();
+    let normals = mesh.normals.as_ref()
+        .ok_or("[ERROR][raiders::gen] Normals vector not found")?;
+    let uvs = mesh.uvs.as_ref()
+        .ok_or("[ERROR][raiders::gen] UV vector not found")?;
+    let terrain_mesh = scene.mesh(
+        "Terrain",
+        mesh.coords.iter().map(|p| [p.x, p.y, p.z]).collect(),
+        normals.iter().map(|n| [n.x, n.y, n.z]).collect(),
+        uvs.iter().map(|uv| [uv.x, uv.y]).collect(),
+        mesh.indices.clone().unwrap_unified().iter().flat_map(|t| vec![t.x, t.y, t.z]).collect(),
+        terrain_material,
+    );
+    let mut children = vec![scene.node(json!({ "name": "Terrain", "mesh": terrain_mesh }))];
+
+    // Each prop kind's model is stored once and shared by all its nodes
+    let mut meshes: HashMap<&'static str, usize> = HashMap::new();
+    for (i, prop) in gen.props.iter().enumerate() {
+        let mesh = match meshes.get(prop.kind.model()) {
+            Some(mesh) => *mesh,
+            None => {
+                let mesh = prop_mesh(&mut scene, prop.kind, assets_dir)?;
+                meshes.insert(prop.kind.model(), mesh);
+                mesh
+            },
+        };
+
+        let [x, y, z] = prop.position;
+        let (sin, cos) = (prop.yaw * 0.5).sin_cos();
+        children.push(scene.node(json!({
+            "name": format!("{:?} {}", prop.kind, i),
+            "mesh": mesh,
+            "translation": [x, y + prop.kind.ground_offset(), z],
+            "rotation": [0.0, sin, 0.0, cos],
+        })));
+    }
+
+    let root = scene.node(json!({
+        "name": format!("map-{}-{}", gen.map_type, gen.seed),
+        "children": children,
+    }));
+
+    fs::write(path, scene.into_glb(root))
+        .map_err(|e| format!("[ERROR][raiders::gen] Cannot write glTF scene: {}", e))
+}
+
+// Flattens a prop's OBJ model into one glTF mesh with a vertex per triangle corner
+fn prop_mesh(scene: &mut Scene, kind: PropKind, assets_dir: &Path) -> Result<usize, String> {
+    let text = fs::read_to_string(assets_dir.join(kind.model()))
+        .map_err(|e| format!("[ERROR][raiders::gen] Cannot read {}: {}", kind.model(), e))?;
+    let set = obj::parse(text)
+        .map_err(|e| format!("[ERROR][raiders::gen] Invalid model {}: {:?}", kind.model(), e))?;
+
+    let (mut positions, mut normals, mut uvs) = (Vec::new(), Vec::new(), Vec::new());
+    for object in &set.objects {
+        for geometry in &object.geometry {
+            for shape in &geometry.shapes {
+                let corners = match &shape.primitive {
+                    Primitive::Triangle(a, b, c) => [*a, *b, *c],
+                    _ => continue,
+                };
+
+                for &(v, t, n) in &corners {
+                    let p = object.vertices[v];
+                    positions.push([p.x as f32, p.y as f32, p.z as f32]);
+                    normals.push(match n {
+                        Some(n) => {
+                            let n = object.normals[n];
+                            [n.x as f32, n.y as f32, n.z as f32]
+                        },
+                        None => [0.0, 1.0, 0.0],
+                    });
+                    uvs.push(match t {
+                        // OBJ's v axis points up, glTF's points down
+                        Some(t) => {
+                            let t = object.tex_vertices[t];
+                            [t.u as f32, 1.0 - t.v as f32]
+                        },
+                        None => [0.0, 0.0],
+                    });
+                }
+            }
+        }
+    }
+
+    let name = format!("{:?}", kind);
+    let material = scene.material(&name, colour(&kind.material()));
+    let indices = (0..positions.len() as u32).collect();
+    Ok(scene.mesh(&name, positions, normals, uvs, indices, material))
+}
+
+fn colour(material: &Material) -> [f32; 4] {
+    match material {
+        Material::Srgba((r, g, b, a)) => [*r, *g, *b, *a],
+        // Textured materials are not exported yet
+        Material::Mtl(_) | Material::Image(_) => [1.0, 1.0, 1.0, 1.0],
+    }
+}
+
+/* glTF document under construction */
+#[derive(Default)]
+struct Scene {
+    buffer: Vec<u8>,
+    buffer_views: Vec<Value>,
+    accessors: Vec<Value>,
+    materials: Vec<Value>,
+    meshes: Vec<Value>,
+    nodes: Vec<Value>,
+}
+
+const FLOAT: u32 = 5126;
+const UNSIGNED_INT: u32 = 5125;
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+
+impl Scene {
+    fn material(&mut self, name: &str, colour: [f32; 4]) -> usize {
+        self.materials.push(json!({
+            "name": name,
+            "pbrMetallicRoughness": {
+                "baseColorFactor": colour,
+                "metallicFactor": 0.0,
+                "roughnessFactor": 1.0,
+            },
+        }));
+        self.materials.len() - 1
+    }
+
+    fn mesh(
+        &mut self,
+        name: &str,
+        positions: Vec<[f32; 3]>,
+        normals: Vec<[f32; 3]>,
+        uvs: Vec<[f32; 2]>,
+        indices: Vec<u32>,
+        material: usize,
+        ) -> usize {
+        // POSITION accessors must state their bounds
+        let mut min = [std::f32::MAX; 3];
+        let mut max = [std::f32::MIN; 3];
+        for p in &positions {
+            for i in 0..3 {
+                min[i] = min[i].min(p[i]);
+                max[i] = max[i].max(p[i]);
+            }
+        }
+
+        let count = positions.len();
+        let position = self.accessor(floats(positions.iter().flatten()), ARRAY_BUFFER, json!({
+            "componentType": FLOAT, "count": count, "type": "VEC3", "min": min, "max": max,
+        }));
+        let normal = self.accessor(floats(normals.iter().flatten()), ARRAY_BUFFER, json!({
+            "componentType": FLOAT, "count": count, "type": "VEC3",
+        }));
+        let uv = self.accessor(floats(uvs.iter().flatten()), ARRAY_BUFFER, json!({
+            "componentType": FLOAT, "count": count, "type": "VEC2",
+        }));
+        let index = self.accessor(
+            indices.iter().flat_map(|i| i.to_le_bytes().to_vec()).collect(),
+            ELEMENT_ARRAY_BUFFER,
+            json!({ "componentType": UNSIGNED_INT, "count": indices.len(), "type": "SCALAR" }),
+        );
+
+        self.meshes.push(json!({
+            "name": name,
+            "primitives": [{
+                "attributes": { "POSITION": position, "NORMAL": normal, "TEXCOORD_0": uv },
+                "indices": index,
+                "material": material,
+            }],
+        }));
+        self.meshes.len() - 1
+    }
+
+    fn node(&mut self, node: Value) -> usize {
+        self.nodes.push(node);
+        self.nodes.len() - 1
+    }
+
+    // Appends `data` to the binary buffer as its own view and returns the accessor index
+    fn accessor(&mut self, data: Vec<u8>, target: u32, mut accessor: Value) -> usize {
+        pad(&mut self.buffer, 0);
+        self.buffer_views.push(json!({
+            "buffer": 0,
+            "byteOffset": self.buffer.len(),
+            "byteLength": data.len(),
+            "target": target,
+        }));
+        self.buffer.extend(data);
+
+        accessor["bufferView"] = json!(self.buffer_views.len() - 1);
+        self.accessors.push(accessor);
+        self.accessors.len() - 1
+    }
+
+    fn into_glb(mut self, root: usize) -> Vec<u8> {
+        pad(&mut self.buffer, 0);
+        let document = json!({
+            "asset": { "version": "2.0", "generator": concat!("raiders ", env!("CARGO_PKG_VERSION")) },
+            "scene": 0,
+            "scenes": [{ "nodes": [root] }],
+            "nodes": self.nodes,
+            "meshes": self.meshes,
+            "materials": self.materials,
+            "accessors": self.accessors,
+            "bufferViews": self.buffer_views,
+            "buffers": [{ "byteLength": self.buffer.len() }],
+        });
+        let mut json = document.to_string().into_bytes();
+        pad(&mut json, b' ');
+
+        // Header, then the JSON and BIN chunks, each with a length and type
+        let length = 12 + 8 + json.len() + 8 + self.buffer.len();
+        let mut glb = Vec::with_capacity(length);
+        glb.extend_from_slice(b"glTF");
+        glb.extend_from_slice(&2u32.to_le_bytes());
+        glb.extend_from_slice(&(length as u32).to_le_bytes());
+        glb.extend_from_slice(&(json.len() as u32).to_le_bytes());
+        glb.extend_from_slice(b"JSON");
+        glb.extend(json);
+        glb.extend_from_slice(&(self.buffer.len() as u32).to_le_bytes());
+        glb.extend_from_slice(b"BIN\0");
+        glb.extend(self.buffer);
+        glb
+    }
+}
+
+fn floats<'a, I: Iterator<Item = &'a f32>>(values: I) -> Vec<u8> {
+    values.flat_map(|f| f.to_le_bytes().to_vec()).collect()
+}
+
+// glTF requires chunks and buffer views to start on 4-byte boundaries
+fn pad(data: &mut Vec<u8>, byte: u8) {
+    while data.len() % 4 != 0 {
+        data.push(byte);
+    }
+}
@@ -0,0 +1,227 @@
+use std::{fs, io::{self, BufWriter}, path::{Path, PathBuf}};
+use ncollide3d::procedural::TriMesh;
+use serde::{Deserialize, Serialize};
+
+// Height in metres of a white pixel in an imported heightmap without a
+// height range next to it; black is 0
+pub const IMPORT_HEIGHT_RANGE: f32 = 30.0;
+
+// Whether `path` names a heightmap image this module can read and write
+pub fn is_heightmap_file(path: &Path) -> bool {
+    match extension(path).as_ref().map(String::as_str) {
+        Some("png") | Some("raw") | Some("r16") => true,
+        _ => false,
+    }
+}
+
+fn extension(path: &Path) -> Option<String> {
+    path.extension().map(|e| e.to_string_lossy().to_lowercase())
+}
+
+/* Height range */
+// Heights in metres that black and white stand for in a heightmap image.
+// Exports write it next to the image as `<image>.ron`, so importing an
+// exported heightmap gives back the heights it was exported with
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub struct HeightRange {
+    pub min: f32,
+    pub max: f32,
+}
+
+impl Default for HeightRange {
+    fn default() -> Self {
+        HeightRange {
+            min: 0.0,
+            max: IMPORT_HEIGHT_RANGE,
+        }
+    }
+}
+
+impl HeightRange {
+    // Range stored next to the heightmap at `path`, or the default for images made elsewhere
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = HeightRange::path(path.as_ref());
+        match fs::read_to_string(&path) {
+            Ok(text) => ron::de::from_str(&text)
+                .map_err(|e| format!("[ERROR][raiders::gen] Invalid height range {}: {}", path.display(), e)),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(HeightRange::default()),
+            Err(e) => Err(format!("[ERROR][raiders::gen] Cannot read height range {}: {}", path.display(), e)),
+        }
+    }
+
+    // Writes the range next to the heightmap at `path`
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|e| format!("[ERROR][raiders::gen] Cannot serialise height range: {}", e))?;
+        fs::write(HeightRange::path(path.as_ref()), text)
+            .map_err(|e| format!("[ERROR][raiders::gen] Cannot write height range: {}", e))
+    }
+
+    fn path(heightmap: &Path) -> PathBuf {
+        let mut name = heightmap.as_os_str().to_owned();
+        name.push(".ron");
+        PathBuf::from(name)
+    }
+}
+
+/* Height grid */
+// Regular grid of terrain heights, sampled from a generated mesh or read
+// from an image. Rows run along z and columns along x, both from the
+// lowest coordinate up
+#[derive(Clone, Debug)]
+pub struct HeightGrid {
+    pub width: u32,
+    pub height: u32,
+    pub heights: Vec<f32>,
+}
+
+impl HeightGrid {
+    // Bins every vertex of `mesh` into a `cells` x `cells` grid
+    pub fn from_mesh(mesh: &TriMesh<f32>, cells: u32) -> Self {
+        let coords = &mesh.coords;
+        let (mut min_x, mut max_x) = (std::f32::MAX, std::f32::MIN);
+        let (mut min_z, mut max_z) = (std::f32::MAX, std::f32::MIN);
+        for p in coords {
+            min_x = min_x.min(p.x);
+            max_x = max_x.max(p.x);
+            min_z = min_z.min(p.z);
+            max_z = max_z.max(p.z);
+        }
+
+        let last = (cells - 1) as f32;
+        let mut heights = vec![0.0; (cells * cells) as usize];
+        for p in coords {
+            let col = ((p.x - min_x) / (max_x - min_x).max(std::f32::EPSILON) * last).round() as u32;
+            let row = ((p.z - min_z) / (max_z - min_z).max(std::f32::EPSILON) * last).round() as u32;
+            heights[(row * cells + col) as usize] = p.y;
+        }
+
+        HeightGrid {
+            width: cells,
+            height: cells,
+            heights: heights,
+        }
+    }
+
+    // Reads an 8 or 16-bit grayscale PNG, or a square little-endian RAW16
+    // file as exported by most terrain tools, spanning its height range
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let (width, height, samples) = match extension(path).as_ref().map(String::as_str) {
+            Some("png") => HeightGrid::load_png(path)?,
+            Some("raw") | Some("r16") => HeightGrid::load_raw(path)?,
+            _ => return Err(format!("[ERROR][raiders::gen] Unsupported heightmap format: {}", path.display())),
+        };
+        let range = HeightRange::load(path)?;
+
+        Ok(HeightGrid {
+            width: width,
+            height: height,
+            heights: samples.iter().map(|s| range.min + s * (range.max - range.min)).collect(),
+        })
+    }
+
+    // Size and samples in 0..1 of a PNG heightmap
+    fn load_png(path: &Path) -> Result<(u32, u32, Vec<f32>), String> {
+        let file = fs::File::open(path)
+            .map_err(|e| format!("[ERROR][raiders::gen] Cannot open heightmap: {}", e))?;
+        let mut decoder = png::Decoder::new(file);
+        decoder.set_transformations(png::Transformations::EXPAND);
+        let (info, mut reader) = decoder.read_info()
+            .map_err(|e| format!("[ERROR][raiders::gen] Invalid heightmap: {}", e))?;
+        let mut data = vec![0; info.buffer_size()];
+        reader.next_frame(&mut data)
+            .map_err(|e| format!("[ERROR][raiders::gen] Invalid heightmap: {}", e))?;
+
+        let samples: Vec<f32> = match reader.output_color_type() {
+            (png::ColorType::Grayscale, png::BitDepth::Sixteen) => data.chunks(2)
+                .map(|b| u16::from_be_bytes([b[0], b[1]]) as f32 / std::u16::MAX as f32)
+                .collect(),
+            (png::ColorType::Grayscale, png::BitDepth::Eight) => data.iter()
+                .map(|&b| b as f32 / std::u8::MAX as f32)
+                .collect(),
+            (color, depth) => return Err(format!(
+                "[ERROR][raiders::gen] Heightmap must be 8 or 16-bit grayscale, found {:?} {:?}",
+                color, depth,
+            )),
+        };
+
+        Ok((info.width, info.height, samples))
+    }
+
+    fn load_raw(path: &Path) -> Result<(u32, u32, Vec<f32>), String> {
+        let data = fs::read(path)
+            .map_err(|e| format!("[ERROR][raiders::gen] Cannot read heightmap: {}", e))?;
+        let side = ((data.len() / 2) as f64).sqrt() as u32;
+        if side < 2 || (side * side * 2) as usize != data.len() {
+            return Err(format!("[ERROR][raiders::gen] {} is not a square RAW16 heightmap", path.display()));
+        }
+
+        let samples: Vec<f32> = data.chunks(2)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]) as f32 / std::u16::MAX as f32)
+            .collect();
+
+        Ok((side, side, samples))
+    }
+
+    pub fn min(&self) -> f32 {
+        self.heights.iter().cloned().fold(std::f32::MAX, f32::min)
+    }
+
+    pub fn max(&self) -> f32 {
+        self.heights.iter().cloned().fold(std::f32::MIN, f32::max)
+    }
+
+    pub fn range(&self) -> HeightRange {
+        HeightRange {
+            min: self.min(),
+            max: self.max(),
+        }
+    }
+
+    // Heights rescaled so the lowest point is 0 and the highest is 65535.
+    // Importing the result with `range` gives the heights back
+    pub fn to_u16(&self) -> Vec<u16> {
+        let (min, max) = (self.min(), self.max());
+        let range = (max - min).max(std::f32::EPSILON);
+
+        self.heights.iter()
+            .map(|h| ((h - min) / range * std::u16::MAX as f32).round() as u16)
+            .collect()
+    }
+
+    // Writes a PNG or RAW16 file depending on the extension of `path`,
+    // along with the height range it spans
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let path = path.as_ref();
+        match extension(path).as_ref().map(String::as_str) {
+            Some("png") => self.write_png(path)?,
+            Some("raw") | Some("r16") => self.write_raw(path)?,
+            _ => return Err(format!("[ERROR][raiders::gen] Unsupported heightmap format: {}", path.display())),
+        }
+        self.range().write(path)
+    }
+
+    // Little-endian 16-bit samples without a header
+    pub fn write_raw<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let data: Vec<u8> = self.to_u16().iter().flat_map(|h| h.to_le_bytes().to_vec()).collect();
+        fs::write(path.as_ref(), data)
+            .map_err(|e| format!("[ERROR][raiders::gen] Cannot write heightmap: {}", e))
+    }
+
+    // 16-bit grayscale PNG, one pixel per cell
+    pub fn write_png<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let file = fs::File::create(path.as_ref())
+            .map_err(|e| format!("[ERROR][raiders::gen] Cannot create heightmap: {}", e))?;
+
+        let mut encoder = png::Encoder::new(BufWriter::new(file), self.width, self.height);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Sixteen);
+
+        // PNG stores 16-bit samples big-endian
+        let data: Vec<u8> = self.to_u16().iter().flat_map(|h| h.to_be_bytes().to_vec()).collect();
+        encoder.write_header()
+            .and_then(|mut writer| writer.write_image_data(&data))
+            .map_err(|e| format!("[ERROR][raiders::gen] Cannot write heightmap: {}", e))
+    }
+}
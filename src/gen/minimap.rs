@@ -0,0 +1,107 @@
+use std::{fs, io::BufWriter, path::Path};
+use super::heightmap::HeightGrid;
+
+// Side of the minimap image in pixels
+pub const MINIMAP_SIZE: u32 = 256;
+// Ground in the lowest part of the height range is drawn as water
+const WATER_FRACTION: f32 = 0.05;
+// Share of a colour kept on slopes facing away from the light
+const AMBIENT: f32 = 0.35;
+const WATER_SHALLOW: [f32; 3] = [0.25, 0.5, 0.7];
+const WATER_DEEP: [f32; 3] = [0.1, 0.25, 0.5];
+// Colour ramp from low to high ground, as (height fraction, colour)
+const RAMP: [(f32, [f32; 3]); 5] = [
+    (0.0, [0.76, 0.70, 0.50]),
+    (0.15, [0.38, 0.58, 0.27]),
+    (0.55, [0.24, 0.40, 0.20]),
+    (0.8, [0.50, 0.45, 0.40]),
+    (1.0, [0.95, 0.95, 0.95]),
+];
+
+/* Minimap image */
+// Top-down picture of a height grid covering `extent` metres along x and
+// z: a colour ramp by height, shaded as if lit from the north-west, with
+// the lowest ground drawn as water. Columns run along x and rows along
+// z, so the top edge is the map's lowest z. Returns `size` x `size` RGB pixels
+pub fn render(grid: &HeightGrid, extent: (f32, f32), size: u32) -> Vec<u8> {
+    let (min, max) = (grid.min(), grid.max());
+    let range = (max - min).max(std::f32::EPSILON);
+    let last = (size - 1).max(1) as f32;
+    // Metres between neighbouring pixels
+    let step = (extent.0 / last, extent.1 / last);
+    // Light from the north-west (-x, -z), 45 degrees above the horizon
+    let light = [-0.5, std::f32::consts::FRAC_1_SQRT_2, -0.5];
+
+    let mut pixels = Vec::with_capacity((size * size * 3) as usize);
+    for row in 0..size {
+        for col in 0..size {
+            let (u, v) = (col as f32 / last, row as f32 / last);
+            let height = sample(grid, u, v);
+            let t = (height - min) / range;
+
+            // Surface normal from the heights of the neighbouring pixels
+            let du = 1.0 / last;
+            let dx = (sample(grid, u + du, v) - sample(grid, u - du, v)) / (2.0 * step.0);
+            let dz = (sample(grid, u, v + du) - sample(grid, u, v - du)) / (2.0 * step.1);
+            let length = (dx * dx + dz * dz + 1.0).sqrt();
+            let normal = [-dx / length, 1.0 / length, -dz / length];
+            let lit = (normal[0] * light[0] + normal[1] * light[1] + normal[2] * light[2]).max(0.0);
+
+            let colour = if t < WATER_FRACTION {
+                // Water is flat, so only its depth shows
+                lerp(WATER_DEEP, WATER_SHALLOW, t / WATER_FRACTION)
+            } else {
+                let shade = AMBIENT + (1.0 - AMBIENT) * lit;
+                let [r, g, b] = ramp(t);
+                [r * shade, g * shade, b * shade]
+            };
+            pixels.extend(colour.iter().map(|c| (c.max(0.0).min(1.0) * 255.0).round() as u8));
+        }
+    }
+
+    pixels
+}
+
+// 8-bit RGB PNG of `render`
+pub fn write_png<P: AsRef<Path>>(grid: &HeightGrid, extent: (f32, f32), size: u32, path: P) -> Result<(), String> {
+    let file = fs::File::create(path.as_ref())
+        .map_err(|e| format!("[ERROR][raiders::gen] Cannot create minimap: {}", e))?;
+
+    let mut encoder = png::Encoder::new(BufWriter::new(file), size, size);
+    encoder.set_color(png::ColorType::RGB);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let data = render(grid, extent, size);
+    encoder.write_header()
+        .and_then(|mut writer| writer.write_image_data(&data))
+        .map_err(|e| format!("[ERROR][raiders::gen] Cannot write minimap: {}", e))
+}
+
+// Bilinearly interpolated height at (u, v) in 0..1 across the grid, clamped to its edges
+fn sample(grid: &HeightGrid, u: f32, v: f32) -> f32 {
+    let x = u.max(0.0).min(1.0) * (grid.width - 1) as f32;
+    let y = v.max(0.0).min(1.0) * (grid.height - 1) as f32;
+    let (col, row) = (x.floor() as u32, y.floor() as u32);
+    let (next_col, next_row) = ((col + 1).min(grid.width - 1), (row + 1).min(grid.height - 1));
+    let at = |row: u32, col: u32| grid.heights[(row * grid.width + col) as usize];
+    let (fx, fy) = (x - col as f32, y - row as f32);
+
+    let top = at(row, col) * (1.0 - fx) + at(row, next_col) * fx;
+    let bottom = at(next_row, col) * (1.0 - fx) + at(next_row, next_col) * fx;
+    top * (1.0 - fy) + bottom * fy
+}
+
+fn ramp(t: f32) -> [f32; 3] {
+    for pair in RAMP.windows(2) {
+        let ((from, low), (to, high)) = (pair[0], pair[1]);
+        if t <= to {
+            return lerp(low, high, (t - from) / (to - from));
+        }
+    }
+    RAMP[RAMP.len() - 1].1
+}
+
+fn lerp(a: [f32; 3], b: [f32; 3], t: f32) -> [f32; 3] {
+    let t = t.max(0.0).min(1.0);
+    [a[0] + (b[0] - a[0]) * t, a[1] + (b[1] - a[1]) * t, a[2] + (b[2] - a[2]) * t]
+}
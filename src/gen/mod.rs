@@ -1,8 +1,18 @@
-use std::fs;
-use std::io::Write;
+pub mod cache;
+pub mod gltf;
+pub mod heightmap;
+pub mod minimap;
+pub mod navmesh;
+pub mod prefab;
+pub mod simplify;
+
+use std::{fmt, path::{Path, PathBuf}, str::FromStr};
 use ncollide3d::procedural::TriMesh;
-use log::info;
-use rand::prelude::*;
+use log::{info, warn};
+use amethyst::core::Transform;
+use crate::component::{ExtractionZone, MapProp};
+use serde::{Deserialize, Serialize};
+use rand::{prelude::*, rngs::StdRng};
 use rand_distr::{Standard, LogNormal, Uniform, UnitCircle, Exp1, Float};
 use obj_exporter::{Geometry, ObjSet, Object, Primitive as ObjPrimitive, Shape, TVertex, Vertex};
 use terr::{
@@ -10,12 +20,15 @@ use terr::{
     unbounded::Perlin,
 };
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Terrain {
     Mountains,
     Foothills,
     Coast,
     Fault,
+    // Heightmap image made or edited outside the game, see `heightmap::HeightGrid::load`
+    Custom(PathBuf),
 }
 
 impl Distribution<Terrain> for Standard {
@@ -29,21 +42,94 @@ impl Distribution<Terrain> for Standard {
     }
 }
 
+impl Terrain {
+    // Used in generated file names, so custom terrain goes by its file name
+    pub fn name(&self) -> String {
+        match self {
+            Terrain::Mountains => String::from("mountains"),
+            Terrain::Foothills => String::from("foothills"),
+            Terrain::Coast => String::from("coast"),
+            Terrain::Fault => String::from("fault"),
+            Terrain::Custom(path) => format!(
+                "custom-{}",
+                path.file_stem().map_or(String::new(), |stem| stem.to_string_lossy().into_owned()),
+            ),
+        }
+    }
+
+    // Whether a map can be generated from the terrain, which for custom
+    // terrain means the heightmap can be read, is square and is at least 2x2
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            Terrain::Custom(source) => load_custom(source).map(|_| ()),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn load_custom(source: &Path) -> Result<heightmap::HeightGrid, String> {
+    let grid = heightmap::HeightGrid::load(source)?;
+    if grid.width != grid.height || grid.width < 2 {
+        return Err(format!("[ERROR][raiders::gen] {} is not a square heightmap of at least 2x2 samples", source.display()));
+    }
+    Ok(grid)
+}
+
+impl fmt::Display for Terrain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.name())
+    }
+}
+
+impl FromStr for Terrain {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "mountains" => Ok(Terrain::Mountains),
+            "foothills" => Ok(Terrain::Foothills),
+            "coast" => Ok(Terrain::Coast),
+            "fault" => Ok(Terrain::Fault),
+            _ if heightmap::is_heightmap_file(Path::new(s)) => Ok(Terrain::Custom(PathBuf::from(s))),
+            _ => Err(format!(
+                "unknown terrain type '{}', expected mountains, foothills, coast, fault or a .png/.raw heightmap",
+                s,
+            )),
+        }
+    }
+}
+
+// Bump whenever a change makes the same terrain type and seed produce a
+// different map, so cached maps from older versions are not reused
+pub const GENERATOR_VERSION: u32 = 4;
+
+// Everything generated for a map is derived from the terrain type and the
+// seed, so the same pair always produces the same map
 pub struct MapGenerator {
     map_type: Terrain,
+    seed: u64,
+    rng: StdRng,
+    // Samples along each side of the heightmap the mesh was built from
+    cells: u32,
     mesh: TriMesh<f32>,
-    handle: fs::File,
+    // Simplified version of `mesh` for rendering and export, if any
+    simplified: Option<TriMesh<f32>>,
+    max_error: f32,
+    props: Vec<Prop>,
+    prefab: prefab::MapPrefab,
+    // Output files, relative to `assets_dir`
+    model_path: String,
     path: String,
+    assets_dir: PathBuf,
 }
 
 impl MapGenerator {
-    pub fn new(map_type: Terrain) -> Self {
-    	let num: usize = rand::thread_rng().gen();
-    	let path = format!("prefabs/map-{}.ron", num);
-    	let mut file = fs::File::create(format!("assets/{}", &path)).unwrap();
-    	file.write_all(b"#![enable(implicit_some)] Prefab ( entities: [").unwrap();
-    	
-        match map_type {
+    // Generated files are written below `assets_dir`. Fails only when a
+    // custom heightmap cannot be read
+    pub fn new(map_type: Terrain, seed: u64, assets_dir: &Path) -> Result<Self, String> {
+    	let mut rng = StdRng::seed_from_u64(seed);
+
+        let (cells, heightmap) = match &map_type {
             Terrain::Mountains => {
                 let cells = 2_u32.pow(5) + 1;
                 let mut heightmap = Heightmap::new_flat((cells, cells), (100.0, 100.0));
@@ -52,27 +138,10 @@ impl MapGenerator {
                 let w = [-0.8, 0.2, 0.4];
                 // let w = [-0.9, 1.2];
                 // let w = [0.7, -1.2];
-                let voronoi = Voronoi::random(&heightmap, 24, &mut rand::thread_rng());
+                let voronoi = Voronoi::random(&heightmap, 24, &mut rng);
                 voronoi.apply_to(&mut heightmap, &w, |x,y| (x*x + y*y).sqrt());
 
-                let mut quad = heightmap.to_trimesh();
-                for p in &mut quad.coords {
-                    // Quad is created with z=height, but y is up in amethyst.
-                    // We must rotate all three coords to keep the right side up.
-                    let temp = p.z;
-                    p.z = p.x;
-                    p.x = p.y;
-                    p.y = temp;
-                }
-                quad.recompute_normals();
-                info!("Terrain generation finished");
-
-                MapGenerator {
-                    map_type: map_type,
-                    mesh: quad,
-                    handle: file,
-                    path: path,
-                }
+                (cells, heightmap)
             },
             
             Terrain::Foothills => {
@@ -81,7 +150,6 @@ impl MapGenerator {
 
                 // Randomise the height of the four corners:
                 let distr = LogNormal::new(0.5, 1.0).unwrap();
-                let mut rng = rand::thread_rng();
                 for (x, y) in [(0, 0), (0, cells-1), (cells-1, 0), (cells-1, cells-1)].iter() {
                     let h = distr.sample(&mut rng) as f32;
                     heightmap.set(*x, *y, h);
@@ -93,32 +161,13 @@ impl MapGenerator {
                 diamond_square(&mut heightmap, 0, &mut rng, distr).unwrap();
 
                 let w = [-1.0, 0.5, 1.0];
-                let voronoi = Voronoi::random(&heightmap, 24, &mut rand::thread_rng());
+                let voronoi = Voronoi::random(&heightmap, 24, &mut rng);
                 voronoi.apply_to(&mut heightmap, &w, |x,y| 0.01 * (x*x + y*y));
 
-                let mut quad = heightmap.to_trimesh();
-                for p in &mut quad.coords {
-                    // Quad is created with z=height, but y is up in amethyst.
-                    // We must rotate all three coords to keep the right side up.
-                    let temp = p.z;
-                    p.z = p.x;
-                    p.x = p.y;
-                    p.y = temp;
-                }
-                quad.recompute_normals();
-                info!("Terrain generation finished");
-                
-                MapGenerator {
-                    map_type: map_type,
-                    mesh: quad,
-                    handle: file,
-                    path: path,
-                }
+                (cells, heightmap)
             },
             
             Terrain::Coast => {
-                let mut rng = thread_rng();
-
                 let cells = 2_u32.pow(8);
                 let mut heightmap = Heightmap::new_flat((cells, cells), (100.0, 100.0));
                 let mut ampl = 20.0;
@@ -135,24 +184,7 @@ impl MapGenerator {
                     larc *= 2.0;
                 }
 
-                let mut quad = heightmap.to_trimesh();
-                for p in &mut quad.coords {
-                    // Quad is created with z=height, but y is up in amethyst.
-                    // We must rotate all three coords to keep the right side up.
-                    let temp = p.z;
-                    p.z = p.x;
-                    p.x = p.y;
-                    p.y = temp;
-                }
-                quad.recompute_normals();
-                info!("Terrain generation finished");
-                
-                MapGenerator {
-                    map_type: map_type,
-                    mesh: quad,
-                    handle: file,
-                    path: path,
-                }
+                (cells, heightmap)
             },
             
             Terrain::Fault => {
@@ -161,7 +193,6 @@ impl MapGenerator {
 
                 // Randomise the height of the four corners:
                 let distr = LogNormal::new(0.5, 1.0).unwrap();
-                let mut rng = rand::thread_rng();
                 for (x, y) in [(0, 0), (0, cells-1), (cells-1, 0), (cells-1, cells-1)].iter() {
                     let h = distr.sample(&mut rng) as f32;
                     heightmap.set(*x, *y, h);
@@ -187,41 +218,160 @@ impl MapGenerator {
                     });
                 }
 
-                let mut quad = heightmap.to_trimesh();
-                for p in &mut quad.coords {
-                    // Quad is created with z=height, but y is up in kiss3d's camera.
-                    // We must rotate all three coords to keep the right side up.
-                    let temp = p.z;
-                    p.z = p.x;
-                    p.x = p.y;
-                    p.y = temp;
-                }
-                quad.recompute_normals();
-                info!("Terrain generation finished");
-                
-                MapGenerator {
-                    map_type: map_type,
-                    mesh: quad,
-                    handle: file,
-                    path: path,
+                (cells, heightmap)
+            },
+
+            Terrain::Custom(source) => {
+                let grid = load_custom(source)?;
+
+                // Rows of the grid run along the mesh's z axis, which is the heightmap's x axis
+                let cells = grid.width;
+                let mut heightmap = Heightmap::new_flat((cells, cells), (100.0, 100.0));
+                for row in 0..cells {
+                    for col in 0..cells {
+                        heightmap.set(row, col, grid.heights[(row * cells + col) as usize]);
+                    }
                 }
+
+                (cells, heightmap)
             },
+        };
+
+        let mut quad = heightmap.to_trimesh();
+        for p in &mut quad.coords {
+            // Quad is created with z=height, but y is up in amethyst.
+            // We must rotate all three coords to keep the right side up.
+            let temp = p.z;
+            p.z = p.x;
+            p.x = p.y;
+            p.y = temp;
         }
+        quad.recompute_normals();
+        info!("Terrain generation finished");
+
+    	let model_path = format!("models/terrain-{}-{}.obj", map_type, seed);
+    	let path = format!("prefabs/map-{}-{}.ron", map_type, seed);
+
+        Ok(MapGenerator {
+            map_type: map_type,
+            seed: seed,
+            rng: rng,
+            cells: cells,
+            mesh: quad,
+            simplified: None,
+            max_error: 0.0,
+            props: Vec::new(),
+            prefab: prefab::MapPrefab::default(),
+            model_path: model_path,
+            path: path,
+            assets_dir: assets_dir.to_path_buf(),
+        })
     }
     
-    pub fn map_type(&self) -> Terrain { self.map_type }
+    // Writes the terrain model and map prefab to these paths below the
+    // assets directory instead of models/ and prefabs/
+    pub fn with_paths(mut self, model_path: String, prefab_path: String) -> Self {
+        self.model_path = model_path;
+        self.path = prefab_path;
+        self
+    }
+
+    // Exported and rendered terrain leaves out vertices that are within
+    // `max_error` metres of the surface without them; 0 keeps the full grid.
+    // Props are still placed using the full grid
+    pub fn with_max_error(mut self, max_error: f32) -> Self {
+        self.max_error = max_error.max(0.0);
+        self.simplified = if max_error > 0.0 {
+            let simplified = simplify::simplify(&self.mesh, self.cells, max_error);
+            info!(
+                "Simplified terrain from {} to {} vertices",
+                self.mesh.coords.len(), simplified.coords.len(),
+            );
+            Some(simplified)
+        } else {
+            None
+        };
+        self
+    }
+
+    pub fn map_type(&self) -> &Terrain { &self.map_type }
+    pub fn seed(&self) -> u64 { self.seed }
+    pub fn max_error(&self) -> f32 { self.max_error }
     pub fn map_path(&self) -> &str { self.path.as_str() }
+    pub fn mesh(&self) -> &TriMesh<f32> { &self.mesh }
+    // The terrain as exported, rendered and collided with
+    pub fn export_mesh(&self) -> &TriMesh<f32> { self.simplified.as_ref().unwrap_or(&self.mesh) }
+    pub fn props(&self) -> &[Prop] { self.props.as_slice() }
+
+    // Binary glTF of the terrain and props, reading prop models from `assets_dir`
+    pub fn export_glb(&self, assets_dir: &Path, path: &Path) -> Result<(), String> {
+        gltf::export_glb(self, assets_dir, path)
+    }
+
+    pub fn height_grid(&self) -> heightmap::HeightGrid {
+        heightmap::HeightGrid::from_mesh(&self.mesh, self.cells)
+    }
+
+    // Hillshaded minimap of the full terrain grid as a PNG
+    pub fn write_minimap(&self, path: &Path) -> Result<(), String> {
+        let (mut min_x, mut max_x) = (std::f32::MAX, std::f32::MIN);
+        let (mut min_z, mut max_z) = (std::f32::MAX, std::f32::MIN);
+        for p in &self.mesh.coords {
+            min_x = min_x.min(p.x);
+            max_x = max_x.max(p.x);
+            min_z = min_z.min(p.z);
+            max_z = max_z.max(p.z);
+        }
+        minimap::write_png(&self.height_grid(), (max_x - min_x, max_z - min_z), minimap::MINIMAP_SIZE, path)
+    }
+
+    // Summary of the terrain shape; ground steeper than `max_slope`
+    // degrees does not count as walkable
+    pub fn stats(&self, max_slope: f32) -> MapStats {
+        let coords = &self.mesh.coords;
+        let (mut min_height, mut max_height) = (std::f32::MAX, std::f32::MIN);
+        for p in coords {
+            min_height = min_height.min(p.y);
+            max_height = max_height.max(p.y);
+        }
+
+        // Slopes are weighted by triangle area so the grid density does not matter
+        let (mut area, mut slope, mut walkable) = (0.0, 0.0, 0.0);
+        for t in self.mesh.indices.clone().unwrap_unified() {
+            let (a, b, c) = (coords[t.x as usize], coords[t.y as usize], coords[t.z as usize]);
+            let cross = (b - a).cross(&(c - a));
+            let tri_area = cross.norm() * 0.5;
+            if tri_area <= 0.0 { continue; }
+
+            let angle = (cross.y.abs() / cross.norm()).min(1.0).acos().to_degrees();
+            area += tri_area;
+            slope += angle * tri_area;
+            if angle <= max_slope {
+                walkable += tri_area;
+            }
+        }
+        let area = area.max(std::f32::EPSILON);
+
+        MapStats {
+            terrain: self.map_type.clone(),
+            seed: self.seed,
+            min_height: min_height,
+            max_height: max_height,
+            mean_slope: slope / area,
+            walkable_percent: walkable / area * 100.0,
+        }
+    }
 
     // .obj generation
     pub fn build_terrain(&mut self) {
-    	let num: usize = rand::thread_rng().gen();
-    	let rpath = format!("models/terrain-{}.obj", num);
+    	let rpath = self.model_path.clone();
+        let mesh = self.export_mesh();
         
         let mesh_object = ObjSet {
             material_library: None,
             objects: vec![Object {
                 name: String::from("Terrain"),
-                vertices: self.mesh.coords
+                vertices: mesh.coords
                     .clone()
                     .into_iter()
                     .map(|point| {
@@ -230,7 +380,7 @@ impl MapGenerator {
                         Vertex { x: slice[0] as f64, y: slice[1] as f64, z: slice[2] as f64 }
                     })
                     .collect(),
-                tex_vertices: self.mesh.uvs
+                tex_vertices: mesh.uvs
                     .clone()
                     .expect("[ERROR][raiders::gen] UV vector not found")
                     .into_iter()
@@ -240,7 +390,7 @@ impl MapGenerator {
                         TVertex { u: slice[0] as f64, v: slice[1] as f64, w: 0.0 }
                     })
                     .collect(),
-                normals: self.mesh.normals
+                normals: mesh.normals
                     .clone()
                     .expect("[ERROR][raiders::gen] Normals vector not found")
                     .into_iter()
@@ -252,7 +402,7 @@ impl MapGenerator {
                     .collect(),
                 geometry: vec![Geometry {
                     material_name: None,
-                    shapes: self.mesh.indices
+                    shapes: mesh.indices
                         .clone()
                         .unwrap_unified()
                         .into_iter()
@@ -274,29 +424,274 @@ impl MapGenerator {
             }],
         };
 
-        obj_exporter::export_to_file(&mesh_object, &format!("assets/{}", rpath)).unwrap();
-        self.add_mesh(&rpath, (0.0, 0.0, 0.0), Material::Srgba((0.0, 1.0, 0.0, 1.0)));
+        obj_exporter::export_to_file(&mesh_object, self.assets_dir.join(&rpath)).unwrap();
+        self.add_mesh(&rpath, (0.0, 0.0, 0.0), 0.0, Material::Srgba(TERRAIN_COLOUR), None);
+    }
+
+    // Settlement and tree placement
+    pub fn build_props(&mut self) {
+        let rng = &mut self.rng;
+        let normals = self.mesh.normals
+            .clone()
+            .expect("[ERROR][raiders::gen] Normals vector not found");
+        let coords = &self.mesh.coords;
+
+        // Centre of the map, used to keep the settlement away from the edges
+        let (mut cx, mut cz) = (0.0, 0.0);
+        for p in coords {
+            cx += p.x;
+            cz += p.z;
+        }
+        cx /= coords.len() as f32;
+        cz /= coords.len() as f32;
+
+        // The settlement sits on the flattest of a handful of candidate spots near the centre
+        let centre = (0..SETTLEMENT_CANDIDATES)
+            .map(|_| rng.gen_range(0, coords.len()))
+            .filter(|&i| {
+                let p = coords[i];
+                (p.x - cx).powi(2) + (p.z - cz).powi(2) < SETTLEMENT_SPREAD.powi(2)
+            })
+            .max_by(|&a, &b| normals[a].y.partial_cmp(&normals[b].y).unwrap())
+            .unwrap_or(0);
+        let centre = coords[centre];
+
+        let mut buildings = 0;
+        for _ in 0..SETTLEMENT_CANDIDATES {
+            if buildings == SETTLEMENT_SIZE { break; }
+
+            let i = rng.gen_range(0, coords.len());
+            let p = coords[i];
+            let distance = ((p.x - centre.x).powi(2) + (p.z - centre.z).powi(2)).sqrt();
+            if distance > SETTLEMENT_RADIUS || normals[i].y < BUILDING_MIN_NORMAL_Y { continue; }
+            if self.props.iter().any(|prop| prop.distance_xz(p.x, p.z) < BUILDING_SPACING) { continue; }
+
+            let kind = if buildings % 4 == 0 { PropKind::TallBuilding } else { PropKind::Shack };
+            self.props.push(Prop {
+                kind: kind,
+                position: [p.x, p.y, p.z],
+                yaw: rng.gen_range(0.0, std::f32::consts::PI * 2.0),
+            });
+            buildings += 1;
+        }
+
+        for _ in 0..TREE_COUNT {
+            let i = rng.gen_range(0, coords.len());
+            let p = coords[i];
+            let distance = ((p.x - centre.x).powi(2) + (p.z - centre.z).powi(2)).sqrt();
+            if distance < SETTLEMENT_RADIUS || normals[i].y < TREE_MIN_NORMAL_Y { continue; }
+
+            self.props.push(Prop {
+                kind: PropKind::Tree,
+                position: [p.x, p.y, p.z],
+                yaw: rng.gen_range(0.0, std::f32::consts::PI * 2.0),
+            });
+        }
+        info!("Placed {} buildings and {} trees", buildings, self.props.len() - buildings);
+
+        for prop in self.props.clone() {
+            let [x, y, z] = prop.position;
+            let path = String::from(prop.kind.model());
+            self.add_mesh(&path, (x, y + prop.kind.ground_offset(), z), prop.yaw, prop.kind.material(), Some(prop.kind));
+        }
     }
     
+    // Extraction zones on the map edges that can be walked to from the
+    // settlement, picked at random and kept apart from each other
+    pub fn build_extraction(&mut self) {
+        let nav = navmesh::NavMesh::build(&self.mesh, self.cells, &self.props, WALKABLE_SLOPE);
+
+        let buildings: Vec<&Prop> = self.props.iter().filter(|prop| prop.kind != PropKind::Tree).collect();
+        let (mut cx, mut cz) = (0.0, 0.0);
+        if buildings.is_empty() {
+            for p in &self.mesh.coords {
+                cx += p.x / self.mesh.coords.len() as f32;
+                cz += p.z / self.mesh.coords.len() as f32;
+            }
+        } else {
+            for building in &buildings {
+                cx += building.position[0] / buildings.len() as f32;
+                cz += building.position[2] / buildings.len() as f32;
+            }
+        }
+
+        let start = match nav.nearest_walkable(cx, cz) {
+            Some(start) => start,
+            None => {
+                warn!("No walkable ground for extraction zones");
+                return;
+            },
+        };
+        let reachable = nav.reachable(start);
+        let mut candidates: Vec<usize> = nav.edge_cells().into_iter().filter(|&cell| reachable[cell]).collect();
+        candidates.shuffle(&mut self.rng);
+
+        let mut zones: Vec<[f32; 3]> = Vec::new();
+        for cell in candidates {
+            if zones.len() == EXTRACTION_ZONES { break; }
+
+            let [x, y, z] = nav.position(cell);
+            if zones.iter().any(|zone| (zone[0] - x).powi(2) + (zone[2] - z).powi(2) < EXTRACTION_SPACING.powi(2)) {
+                continue;
+            }
+            zones.push([x, y, z]);
+
+            let mut transform = Transform::default();
+            transform.set_translation_xyz(x, y, z);
+            self.prefab.add(None, prefab::EntityData {
+                transform: Some(transform),
+                extraction: Some(ExtractionZone { radius: EXTRACTION_RADIUS }),
+                ..Default::default()
+            });
+        }
+        if zones.is_empty() {
+            warn!("No map edge can be reached from the settlement");
+        }
+        info!("Placed {} extraction zones", zones.len());
+    }
+
+    // Writes the map prefab; nothing is written before this
     pub fn finish(&mut self) {
-    	self.handle.write_all(b" ], )").unwrap();
+        self.prefab.write(self.assets_dir.join(&self.path)).unwrap();
     }
-    
-    fn add_mesh(&mut self, path: &String, (x, y, z): (f32, f32, f32), mtl: Material) {
-    	let transform = format!("transform: ( translation: ({}, {}, {}), ),", x, y, z);
-    	let mtl_string = match mtl {
-    		Material::Srgba((r, g, b, a)) => format!("Generate(Srgba({}, {}, {}, {}))", r, g, b, a),
-    		Material::Mtl(_mpath) => String::from("bottom text"),
-    		Material::Image(_mpath) => String::from("bottom text"),
-    	};
-    	let mesh = format!("( data: ( graphics: ( mesh: Asset(File(\"{}\", (\"OBJ\", ()))), material: ( albedo: {}, ), ), {} ), ),", path, mtl_string, transform);
-    	
-    	self.handle.write_all(mesh.as_bytes()).unwrap();
+
+    pub fn prefab(&self) -> &prefab::MapPrefab { &self.prefab }
+
+    fn add_mesh(&mut self, path: &str, (x, y, z): (f32, f32, f32), yaw: f32, mtl: Material, prop: Option<PropKind>) {
+        let colour = match mtl {
+            Material::Srgba(colour) => colour,
+            // Textured materials are not supported yet
+            Material::Mtl(_) | Material::Image(_) => (1.0, 1.0, 1.0, 1.0),
+        };
+
+        let mut transform = Transform::default();
+        transform.set_translation_xyz(x, y, z);
+        transform.set_rotation_y_axis(yaw);
+
+        self.prefab.add(None, prefab::EntityData {
+            graphics: Some(prefab::Graphics::obj(path, colour)),
+            transform: Some(transform),
+            prop: prop.map(|kind| MapProp { kind: kind }),
+            ..Default::default()
+        });
     }
 }
 
+/* Map statistics */
+// Heights are in metres, `mean_slope` is in degrees
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct MapStats {
+    pub terrain: Terrain,
+    pub seed: u64,
+    pub min_height: f32,
+    pub max_height: f32,
+    pub mean_slope: f32,
+    pub walkable_percent: f32,
+}
+
+const TERRAIN_COLOUR: (f32, f32, f32, f32) = (0.0, 1.0, 0.0, 1.0);
+
 enum Material {
 	Srgba((f32, f32, f32, f32)),
 	Mtl(String),
 	Image(String),
-}
\ No newline at end of file
+}
+
+/* Props */
+const SETTLEMENT_CANDIDATES: usize = 64;
+const SETTLEMENT_SPREAD: f32 = 25.0;
+const SETTLEMENT_RADIUS: f32 = 15.0;
+const SETTLEMENT_SIZE: usize = 8;
+const BUILDING_SPACING: f32 = 5.0;
+const BUILDING_MIN_NORMAL_Y: f32 = 0.9;
+const TREE_COUNT: usize = 120;
+const TREE_MIN_NORMAL_Y: f32 = 0.75;
+
+/* Extraction */
+const EXTRACTION_ZONES: usize = 2;
+const EXTRACTION_RADIUS: f32 = 6.0;
+const EXTRACTION_SPACING: f32 = 40.0;
+// Steepest ground in degrees the squad is expected to cross, the player's default max_slope
+const WALKABLE_SLOPE: f32 = 40.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum PropKind {
+    Tree,
+    Shack,
+    TallBuilding,
+}
+
+impl PropKind {
+    pub fn model(&self) -> &'static str {
+        match self {
+            PropKind::Tree => "models/tree.obj",
+            PropKind::Shack => "models/shack.obj",
+            PropKind::TallBuilding => "models/tall_building.obj",
+        }
+    }
+
+    // Distance from the model origin down to its lowest vertex
+    pub fn ground_offset(&self) -> f32 {
+        match self {
+            PropKind::Tree => 2.72,
+            PropKind::Shack => 1.64,
+            PropKind::TallBuilding => 2.91,
+        }
+    }
+
+    // Radius around the prop's position that units cannot walk through
+    pub fn footprint(&self) -> f32 {
+        match self {
+            PropKind::Tree => 0.5,
+            PropKind::Shack => 2.6,
+            PropKind::TallBuilding => 1.6,
+        }
+    }
+
+    fn material(&self) -> Material {
+        match self {
+            PropKind::Tree => Material::Srgba((0.2, 0.45, 0.15, 1.0)),
+            PropKind::Shack => Material::Srgba((0.55, 0.4, 0.25, 1.0)),
+            PropKind::TallBuilding => Material::Srgba((0.6, 0.6, 0.6, 1.0)),
+        }
+    }
+}
+
+// A placed prop; `position` is the point on the terrain surface it stands on
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct Prop {
+    pub kind: PropKind,
+    pub position: [f32; 3],
+    pub yaw: f32,
+}
+
+impl Prop {
+    pub fn distance_xz(&self, x: f32, z: f32) -> f32 {
+        ((self.position[0] - x).powi(2) + (self.position[2] - z).powi(2)).sqrt()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::heightmap::HeightGrid;
+
+    fn custom(name: &str, side: u32) -> Terrain {
+        let path = std::env::temp_dir().join(format!("raiders-{}-{}.png", name, std::process::id()));
+        let grid = HeightGrid { width: side, height: side, heights: vec![0.5; (side * side) as usize] };
+        grid.write_png(&path).unwrap();
+        Terrain::Custom(path)
+    }
+
+    #[test]
+    fn rejects_a_single_pixel_heightmap() {
+        let terrain = custom("single", 1);
+        let error = terrain.validate().unwrap_err();
+        assert!(error.starts_with("[ERROR][raiders::gen]") && error.contains("2x2"), "{}", error);
+    }
+
+    #[test]
+    fn accepts_a_two_by_two_heightmap() {
+        assert_eq!(custom("smallest", 2).validate(), Ok(()));
+    }
+}
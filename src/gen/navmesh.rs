@@ -0,0 +1,282 @@
+use std::{cmp::Ordering, collections::{BinaryHeap, VecDeque}};
+use ncollide3d::procedural::TriMesh;
+use super::{heightmap::HeightGrid, Prop};
+
+/* Navigation mesh */
+// Walkable ground at the heightmap's resolution. A cell is walkable when
+// the ground towards its neighbours is no steeper than `max_slope` and no
+// prop stands on it; units move between walkable cells in eight
+// directions. Cells are indexed by row (z) then column (x), like HeightGrid
+#[derive(Clone, Debug)]
+pub struct NavMesh {
+    size: usize,
+    min: (f32, f32),
+    spacing: (f32, f32),
+    heights: Vec<f32>,
+    walkable: Vec<bool>,
+}
+
+impl NavMesh {
+    // `max_slope` is in degrees
+    pub fn build(mesh: &TriMesh<f32>, cells: u32, props: &[Prop], max_slope: f32) -> Self {
+        let grid = HeightGrid::from_mesh(mesh, cells);
+        let (mut min_x, mut max_x) = (std::f32::MAX, std::f32::MIN);
+        let (mut min_z, mut max_z) = (std::f32::MAX, std::f32::MIN);
+        for p in &mesh.coords {
+            min_x = min_x.min(p.x);
+            max_x = max_x.max(p.x);
+            min_z = min_z.min(p.z);
+            max_z = max_z.max(p.z);
+        }
+
+        NavMesh::from_heights(cells as usize, (min_x, min_z), (max_x, max_z), grid.heights, props, max_slope)
+    }
+
+    // Samples `height` on a `size` x `size` grid spanning `min` to `max` in
+    // (x, z). Used at runtime, where the terrain is only known through the
+    // collision world; points without ground are not walkable
+    pub fn sample<F>(size: usize, min: (f32, f32), max: (f32, f32), height: F, props: &[Prop], max_slope: f32) -> Self
+        where F: Fn(f32, f32) -> Option<f32>
+    {
+        let last = (size - 1).max(1) as f32;
+        let mut heights = Vec::with_capacity(size * size);
+        let mut missing = Vec::new();
+        for row in 0..size {
+            for col in 0..size {
+                let x = min.0 + (max.0 - min.0) * col as f32 / last;
+                let z = min.1 + (max.1 - min.1) * row as f32 / last;
+                match height(x, z) {
+                    Some(h) => heights.push(h),
+                    None => {
+                        missing.push(heights.len());
+                        heights.push(0.0);
+                    },
+                }
+            }
+        }
+
+        let mut nav = NavMesh::from_heights(size, min, max, heights, props, max_slope);
+        for cell in missing {
+            nav.walkable[cell] = false;
+        }
+        nav
+    }
+
+    fn from_heights(size: usize, min: (f32, f32), max: (f32, f32), heights: Vec<f32>, props: &[Prop], max_slope: f32) -> Self {
+        let (min_x, min_z) = min;
+        let last = (size - 1).max(1) as f32;
+        let spacing = (((max.0 - min_x) / last).max(std::f32::EPSILON), ((max.1 - min_z) / last).max(std::f32::EPSILON));
+        let max_rise = max_slope.to_radians().tan();
+
+        let mut nav = NavMesh {
+            size: size,
+            min: min,
+            spacing: spacing,
+            heights: heights,
+            walkable: vec![false; size * size],
+        };
+
+        for row in 0..size {
+            for col in 0..size {
+                let h = nav.heights[row * size + col];
+                let steep = [(0, 1), (1, 0), (0, -1), (-1, 0)].iter().any(|&(dr, dc)| {
+                    match nav.index(row as isize + dr, col as isize + dc) {
+                        Some(n) => {
+                            let run = if dr != 0 { spacing.1 } else { spacing.0 };
+                            (nav.heights[n] - h).abs() / run > max_rise
+                        },
+                        None => false,
+                    }
+                });
+                nav.walkable[row * size + col] = !steep;
+            }
+        }
+
+        for prop in props {
+            let [x, _, z] = prop.position;
+            let radius = prop.kind.footprint();
+            let cols = ((x - radius - min_x) / spacing.0).floor().max(0.0) as usize
+                ..((x + radius - min_x) / spacing.0).ceil().max(0.0) as usize + 1;
+            let rows = ((z - radius - min_z) / spacing.1).floor().max(0.0) as usize
+                ..((z + radius - min_z) / spacing.1).ceil().max(0.0) as usize + 1;
+            for row in rows.start..rows.end.min(size) {
+                for col in cols.start..cols.end.min(size) {
+                    let [cx, _, cz] = nav.position(row * size + col);
+                    if (cx - x).powi(2) + (cz - z).powi(2) < radius * radius {
+                        nav.walkable[row * size + col] = false;
+                    }
+                }
+            }
+        }
+
+        nav
+    }
+
+    pub fn size(&self) -> usize { self.size }
+
+    // Metres between neighbouring cells along x and z
+    pub fn spacing(&self) -> (f32, f32) { self.spacing }
+
+    pub fn is_walkable(&self, cell: usize) -> bool {
+        self.walkable.get(cell).cloned().unwrap_or(false)
+    }
+
+    // Point on the ground at the centre of `cell`
+    pub fn position(&self, cell: usize) -> [f32; 3] {
+        let (row, col) = (cell / self.size, cell % self.size);
+        [
+            self.min.0 + col as f32 * self.spacing.0,
+            self.heights[cell],
+            self.min.1 + row as f32 * self.spacing.1,
+        ]
+    }
+
+    // Cell closest to (x, z), if that lies on the map
+    pub fn cell_at(&self, x: f32, z: f32) -> Option<usize> {
+        let col = ((x - self.min.0) / self.spacing.0).round();
+        let row = ((z - self.min.1) / self.spacing.1).round();
+        if col < 0.0 || row < 0.0 {
+            return None;
+        }
+        self.index(row as isize, col as isize)
+    }
+
+    // Walkable cell closest to (x, z), searching the whole map
+    pub fn nearest_walkable(&self, x: f32, z: f32) -> Option<usize> {
+        (0..self.walkable.len())
+            .filter(|&cell| self.walkable[cell])
+            .map(|cell| {
+                let [cx, _, cz] = self.position(cell);
+                (cell, (cx - x).powi(2) + (cz - z).powi(2))
+            })
+            .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
+            .map(|(cell, _)| cell)
+    }
+
+    // Cells on the outermost rows and columns
+    pub fn edge_cells(&self) -> Vec<usize> {
+        let last = self.size - 1;
+        (0..self.size * self.size)
+            .filter(|cell| {
+                let (row, col) = (cell / self.size, cell % self.size);
+                row == 0 || col == 0 || row == last || col == last
+            })
+            .collect()
+    }
+
+    // For every cell, whether units can walk there from `from`
+    pub fn reachable(&self, from: usize) -> Vec<bool> {
+        let mut reached = vec![false; self.walkable.len()];
+        if !self.is_walkable(from) {
+            return reached;
+        }
+
+        let mut queue = VecDeque::new();
+        reached[from] = true;
+        queue.push_back(from);
+        while let Some(cell) = queue.pop_front() {
+            for next in self.neighbours(cell) {
+                if !reached[next] {
+                    reached[next] = true;
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        reached
+    }
+
+    // Shortest walk from `from` to `to` as the cells along it, both ends
+    // included, or None if `to` cannot be reached. A* search over the
+    // same steps as `neighbours`
+    pub fn find_path(&self, from: usize, to: usize) -> Option<Vec<usize>> {
+        if !self.is_walkable(from) || !self.is_walkable(to) {
+            return None;
+        }
+
+        let distance = |a: usize, b: usize| {
+            let ([ax, _, az], [bx, _, bz]) = (self.position(a), self.position(b));
+            ((ax - bx).powi(2) + (az - bz).powi(2)).sqrt()
+        };
+        let mut cost = vec![std::f32::MAX; self.walkable.len()];
+        let mut came_from = vec![None; self.walkable.len()];
+        let mut open = BinaryHeap::new();
+        cost[from] = 0.0;
+        open.push(Step { cell: from, estimate: distance(from, to) });
+
+        while let Some(Step { cell, .. }) = open.pop() {
+            if cell == to {
+                let mut path = vec![to];
+                let mut cell = to;
+                while let Some(previous) = came_from[cell] {
+                    path.push(previous);
+                    cell = previous;
+                }
+                path.reverse();
+                return Some(path);
+            }
+
+            for next in self.neighbours(cell) {
+                let next_cost = cost[cell] + distance(cell, next);
+                if next_cost < cost[next] {
+                    cost[next] = next_cost;
+                    came_from[next] = Some(cell);
+                    open.push(Step { cell: next, estimate: next_cost + distance(next, to) });
+                }
+            }
+        }
+
+        None
+    }
+
+    // Walkable cells a unit can step to from `cell`. Diagonal steps need
+    // both cells beside them to be walkable, so units never cut corners
+    pub fn neighbours(&self, cell: usize) -> Vec<usize> {
+        let (row, col) = ((cell / self.size) as isize, (cell % self.size) as isize);
+        let open = |dr: isize, dc: isize| self.index(row + dr, col + dc).filter(|&n| self.walkable[n]);
+
+        let mut result = Vec::with_capacity(8);
+        for &(dr, dc) in &[(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1)] {
+            if let Some(n) = open(dr, dc) {
+                if dr == 0 || dc == 0 || (open(dr, 0).is_some() && open(0, dc).is_some()) {
+                    result.push(n);
+                }
+            }
+        }
+        result
+    }
+
+    fn index(&self, row: isize, col: isize) -> Option<usize> {
+        if row < 0 || col < 0 || row as usize >= self.size || col as usize >= self.size {
+            None
+        } else {
+            Some(row as usize * self.size + col as usize)
+        }
+    }
+}
+
+// Open cell in the A* search, ordered so the heap pops the lowest estimate first
+struct Step {
+    cell: usize,
+    estimate: f32,
+}
+
+impl PartialEq for Step {
+    fn eq(&self, other: &Self) -> bool {
+        self.estimate == other.estimate
+    }
+}
+
+impl Eq for Step {}
+
+impl PartialOrd for Step {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Step {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.estimate.partial_cmp(&self.estimate).unwrap_or(Ordering::Equal)
+    }
+}
@@ -0,0 +1,203 @@
+use std::{fs, path::Path};
+use amethyst::{
+    core::Transform,
+    renderer::{camera::CameraPrefab, light::Light},
+};
+use serde::{Deserialize, Serialize};
+use crate::component::{ExtractionZone, MapProp};
+
+/* Map prefab */
+// Typed form of a generated map's prefab file. Serialises to the same RON
+// as a `Prefab<ScenePrefabData>`, plus the game's own components, which
+// `state::MapPrefabData` understands. Entities refer to their parent by
+// index, like in any amethyst prefab
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename = "Prefab")]
+pub struct MapPrefab {
+    pub entities: Vec<PrefabEntity>,
+}
+
+impl MapPrefab {
+    // Adds an entity and returns its index for use as a parent
+    pub fn add(&mut self, parent: Option<usize>, data: EntityData) -> usize {
+        self.entities.push(PrefabEntity {
+            parent: parent,
+            data: Some(data),
+        });
+        self.entities.len() - 1
+    }
+
+    pub fn to_ron(&self) -> Result<String, String> {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|e| format!("[ERROR][raiders::gen] Cannot serialise map prefab: {}", e))
+    }
+
+    pub fn from_ron(text: &str) -> Result<Self, String> {
+        ron::de::from_str(text)
+            .map_err(|e| format!("[ERROR][raiders::gen] Invalid map prefab: {}", e))
+    }
+
+    // The whole file is serialised before anything is written, so a failure never leaves half a prefab behind
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let text = self.to_ron()?;
+        fs::write(path.as_ref(), text)
+            .map_err(|e| format!("[ERROR][raiders::gen] Cannot write map prefab: {}", e))
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct PrefabEntity {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<EntityData>,
+}
+
+// Mirrors the fields of amethyst's BasicScenePrefab, followed by the
+// game's components. Empty fields are left out of the file
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct EntityData {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub graphics: Option<Graphics>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transform: Option<Transform>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub light: Option<Light>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub camera: Option<CameraPrefab>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prop: Option<MapProp>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extraction: Option<ExtractionZone>,
+}
+
+/* Graphics */
+// amethyst's GraphicsPrefab cannot be built outside of a loader, so the
+// parts the generator uses are mirrored here with the same RON layout
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Graphics {
+    pub mesh: MeshPrefab,
+    pub material: MaterialPrefab,
+}
+
+impl Graphics {
+    // OBJ model below the assets directory with a flat colour
+    pub fn obj(path: &str, colour: (f32, f32, f32, f32)) -> Self {
+        Graphics {
+            mesh: MeshPrefab::Asset(AssetPrefab::File(String::from(path), (String::from("OBJ"), ()))),
+            material: MaterialPrefab {
+                albedo: Some(TexturePrefab::Generate(TextureGenerator::Srgba(colour.0, colour.1, colour.2, colour.3))),
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum MeshPrefab {
+    Asset(AssetPrefab),
+}
+
+// A file below the assets directory and the name of its format
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum AssetPrefab {
+    File(String, (String, ())),
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct MaterialPrefab {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub albedo: Option<TexturePrefab>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum TexturePrefab {
+    Generate(TextureGenerator),
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum TextureGenerator {
+    Srgba(f32, f32, f32, f32),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use amethyst::{
+        assets::Prefab,
+        renderer::light::PointLight,
+    };
+    use crate::{gen::PropKind, state::MapPrefabData};
+
+    // One of everything the map prefab can hold
+    fn sample() -> MapPrefab {
+        let mut prefab = MapPrefab::default();
+
+        let mut transform = Transform::default();
+        transform.set_translation_xyz(12.0, 3.5, -40.0);
+        transform.set_rotation_y_axis(1.2);
+        let shack = prefab.add(None, EntityData {
+            graphics: Some(Graphics::obj("models/shack.obj", (0.6, 0.4, 0.2, 1.0))),
+            transform: Some(transform),
+            prop: Some(MapProp { kind: PropKind::Shack }),
+            ..Default::default()
+        });
+        prefab.add(Some(shack), EntityData {
+            light: Some(Light::Point(PointLight { intensity: 3.0, ..PointLight::default() })),
+            ..Default::default()
+        });
+        prefab.add(None, EntityData {
+            camera: Some(ron::de::from_str("Perspective(aspect: 1.3, fovy: 1.0471975512, znear: 0.1, zfar: 2000.0)").unwrap()),
+            ..Default::default()
+        });
+        prefab.add(None, EntityData {
+            transform: Some(Transform::default()),
+            extraction: Some(ExtractionZone { radius: 6.0 }),
+            ..Default::default()
+        });
+        prefab
+    }
+
+    #[test]
+    fn round_trips_through_ron() {
+        let prefab = sample();
+        let text = prefab.to_ron().unwrap();
+        let read = MapPrefab::from_ron(&text).unwrap();
+
+        assert_eq!(read.entities.len(), prefab.entities.len());
+        let parents: Vec<Option<usize>> = read.entities.iter().map(|entity| entity.parent).collect();
+        assert_eq!(parents, vec![None, Some(0), None, None]);
+
+        let shack = read.entities[0].data.as_ref().unwrap();
+        assert_eq!(shack.prop.map(|prop| prop.kind), Some(PropKind::Shack));
+        assert_eq!(shack.transform.as_ref().unwrap().translation().x, 12.0);
+        assert!(shack.light.is_none() && shack.camera.is_none() && shack.extraction.is_none());
+        assert!(read.entities[1].data.as_ref().unwrap().light.is_some());
+        assert!(read.entities[2].data.as_ref().unwrap().camera.is_some());
+        assert_eq!(read.entities[3].data.as_ref().unwrap().extraction.map(|zone| zone.radius), Some(6.0));
+
+        // Nothing is lost or changed on the way, so writing it again gives the same file
+        assert_eq!(read.to_ron().unwrap(), text);
+    }
+
+    #[test]
+    fn loads_as_a_map_prefab() {
+        let text = sample().to_ron().unwrap();
+        let prefab: Prefab<MapPrefabData> = ron::de::from_str(&text).unwrap();
+        assert_eq!(prefab.len(), 4);
+    }
+
+    #[test]
+    fn empty_fields_are_left_out() {
+        let mut prefab = MapPrefab::default();
+        prefab.add(None, EntityData {
+            extraction: Some(ExtractionZone { radius: 6.0 }),
+            ..Default::default()
+        });
+        let text = prefab.to_ron().unwrap();
+        for field in &["parent", "graphics", "transform", "light", "camera", "prop"] {
+            assert!(!text.contains(field), "{} written to {}", field, text);
+        }
+    }
+}
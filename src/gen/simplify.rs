@@ -0,0 +1,216 @@
+use ncollide3d::procedural::{IndexBuffer, TriMesh};
+
+/* Terrain simplification */
+// Right-triangulated irregular network (RTIN): the grid is split into
+// right triangles that are only subdivided where the terrain differs from
+// the flat triangle by more than `max_error` metres vertically. Vertices
+// stay on the heightmap grid, so UVs carry over, and normals are
+// recomputed. Grids whose side is not 2^n + 1 are resampled to the next
+// size that is
+pub fn simplify(mesh: &TriMesh<f32>, cells: u32, max_error: f32) -> TriMesh<f32> {
+    let grid = Grid::from_mesh(mesh, cells as usize);
+    let grid = if (grid.size - 1).is_power_of_two() { grid } else { grid.resample((grid.size - 1).next_power_of_two() + 1) };
+    let errors = grid.errors();
+
+    let mut builder = Builder {
+        grid: &grid,
+        errors: &errors,
+        max_error: max_error,
+        vertices: vec![None; grid.size * grid.size],
+        coords: Vec::new(),
+        uvs: Vec::new(),
+        indices: Vec::new(),
+    };
+    let last = grid.size - 1;
+    builder.triangle((0, 0), (last, last), (last, 0));
+    builder.triangle((last, last), (0, 0), (0, last));
+
+    let mut simplified = TriMesh::new(
+        builder.coords.into_iter().map(|p| p.into()).collect(),
+        None,
+        Some(builder.uvs.into_iter().map(|uv| uv.into()).collect()),
+        Some(IndexBuffer::Unified(builder.indices.into_iter().map(|t| t.into()).collect())),
+    );
+    simplified.recompute_normals();
+    simplified
+}
+
+// Heights and UVs on a square grid, indexed by row (z) then column (x)
+struct Grid {
+    size: usize,
+    min: (f32, f32),
+    extent: (f32, f32),
+    heights: Vec<f32>,
+    uvs: Vec<[f32; 2]>,
+}
+
+impl Grid {
+    fn from_mesh(mesh: &TriMesh<f32>, size: usize) -> Self {
+        let (mut min_x, mut max_x) = (std::f32::MAX, std::f32::MIN);
+        let (mut min_z, mut max_z) = (std::f32::MAX, std::f32::MIN);
+        for p in &mesh.coords {
+            min_x = min_x.min(p.x);
+            max_x = max_x.max(p.x);
+            min_z = min_z.min(p.z);
+            max_z = max_z.max(p.z);
+        }
+        let extent = ((max_x - min_x).max(std::f32::EPSILON), (max_z - min_z).max(std::f32::EPSILON));
+
+        let last = (size - 1) as f32;
+        let mut heights = vec![0.0; size * size];
+        let mut uvs = vec![[0.0; 2]; size * size];
+        for (i, p) in mesh.coords.iter().enumerate() {
+            let col = ((p.x - min_x) / extent.0 * last).round() as usize;
+            let row = ((p.z - min_z) / extent.1 * last).round() as usize;
+            heights[row * size + col] = p.y;
+            if let Some(uv) = mesh.uvs.as_ref().and_then(|uvs| uvs.get(i)) {
+                uvs[row * size + col] = [uv.x, uv.y];
+            }
+        }
+
+        Grid {
+            size: size,
+            min: (min_x, min_z),
+            extent: extent,
+            heights: heights,
+            uvs: uvs,
+        }
+    }
+
+    // Bilinear resampling onto a `size` x `size` grid covering the same area
+    fn resample(&self, size: usize) -> Self {
+        let scale = (self.size - 1) as f32 / (size - 1) as f32;
+        let mut heights = Vec::with_capacity(size * size);
+        let mut uvs = Vec::with_capacity(size * size);
+
+        for row in 0..size {
+            for col in 0..size {
+                let (y, x) = (row as f32 * scale, col as f32 * scale);
+                let (r0, c0) = ((y as usize).min(self.size - 2), (x as usize).min(self.size - 2));
+                let (ty, tx) = (y - r0 as f32, x - c0 as f32);
+                let corners = [
+                    (r0 * self.size + c0, (1.0 - tx) * (1.0 - ty)),
+                    (r0 * self.size + c0 + 1, tx * (1.0 - ty)),
+                    ((r0 + 1) * self.size + c0, (1.0 - tx) * ty),
+                    ((r0 + 1) * self.size + c0 + 1, tx * ty),
+                ];
+
+                heights.push(corners.iter().map(|&(i, w)| self.heights[i] * w).sum());
+                uvs.push([
+                    corners.iter().map(|&(i, w)| self.uvs[i][0] * w).sum(),
+                    corners.iter().map(|&(i, w)| self.uvs[i][1] * w).sum(),
+                ]);
+            }
+        }
+
+        Grid {
+            size: size,
+            min: self.min,
+            extent: self.extent,
+            heights: heights,
+            uvs: uvs,
+        }
+    }
+
+    // For every vertex, the largest vertical error made by leaving out the
+    // triangles it splits, including all of their descendants
+    fn errors(&self) -> Vec<f32> {
+        let size = self.size;
+        let tile = size - 1;
+        let count = tile * tile * 2 - 2;
+        let parents = count - tile * tile;
+        let mut errors = vec![0.0f32; size * size];
+
+        for i in (0..count).rev() {
+            let (a, b, c) = triangle_coords(i + 2, tile);
+            let (mx, my) = ((a.0 + b.0) / 2, (a.1 + b.1) / 2);
+            let middle = my * size + mx;
+
+            let interpolated = (self.heights[a.1 * size + a.0] + self.heights[b.1 * size + b.0]) * 0.5;
+            errors[middle] = errors[middle].max((interpolated - self.heights[middle]).abs());
+
+            if i < parents {
+                let left = ((a.1 + c.1) / 2) * size + (a.0 + c.0) / 2;
+                let right = ((b.1 + c.1) / 2) * size + (b.0 + c.0) / 2;
+                errors[middle] = errors[middle].max(errors[left]).max(errors[right]);
+            }
+        }
+
+        errors
+    }
+}
+
+// Corners of the RTIN triangle with the given id; the hypotenuse is a-b
+fn triangle_coords(id: usize, tile: usize) -> ((usize, usize), (usize, usize), (usize, usize)) {
+    let (mut a, mut b, mut c) = if id & 1 == 1 {
+        ((0, 0), (tile, tile), (tile, 0))
+    } else {
+        ((tile, tile), (0, 0), (0, tile))
+    };
+
+    let mut id = id >> 1;
+    while id > 1 {
+        let m = ((a.0 + b.0) / 2, (a.1 + b.1) / 2);
+        if id & 1 == 1 {
+            b = a;
+            a = c;
+        } else {
+            a = b;
+            b = c;
+        }
+        c = m;
+        id >>= 1;
+    }
+
+    (a, b, c)
+}
+
+struct Builder<'a> {
+    grid: &'a Grid,
+    errors: &'a [f32],
+    max_error: f32,
+    vertices: Vec<Option<u32>>,
+    coords: Vec<[f32; 3]>,
+    uvs: Vec<[f32; 2]>,
+    indices: Vec<[u32; 3]>,
+}
+
+impl<'a> Builder<'a> {
+    fn triangle(&mut self, a: (usize, usize), b: (usize, usize), c: (usize, usize)) {
+        let m = ((a.0 + b.0) / 2, (a.1 + b.1) / 2);
+        let long = (a.0 as isize - c.0 as isize).abs() + (a.1 as isize - c.1 as isize).abs() > 1;
+
+        if long && self.errors[m.1 * self.grid.size + m.0] > self.max_error {
+            self.triangle(c, a, m);
+            self.triangle(b, c, m);
+            return;
+        }
+
+        let (ia, ib, ic) = (self.vertex(a), self.vertex(b), self.vertex(c));
+        // Keep every triangle facing up, whichever way round the grid is
+        let (pa, pb, pc) = (self.coords[ia as usize], self.coords[ib as usize], self.coords[ic as usize]);
+        let up = (pb[2] - pa[2]) * (pc[0] - pa[0]) - (pb[0] - pa[0]) * (pc[2] - pa[2]);
+        if up >= 0.0 {
+            self.indices.push([ia, ib, ic]);
+        } else {
+            self.indices.push([ia, ic, ib]);
+        }
+    }
+
+    fn vertex(&mut self, (col, row): (usize, usize)) -> u32 {
+        let i = row * self.grid.size + col;
+        if let Some(index) = self.vertices[i] {
+            return index;
+        }
+
+        let last = (self.grid.size - 1) as f32;
+        let x = self.grid.min.0 + col as f32 / last * self.grid.extent.0;
+        let z = self.grid.min.1 + row as f32 / last * self.grid.extent.1;
+        self.coords.push([x, self.grid.heights[i], z]);
+        self.uvs.push(self.grid.uvs[i]);
+
+        let index = (self.coords.len() - 1) as u32;
+        self.vertices[i] = Some(index);
+        index
+    }
+}
@@ -0,0 +1,317 @@
+use std::{fs, path::Path};
+use amethyst::core::math::{UnitQuaternion, Vector2, Vector3};
+use serde::{Deserialize, Serialize};
+
+/* Tracked pose */
+// Position and orientation relative to the player's play space,
+// i.e. the player entity's transform
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Pose {
+    pub position: Vector3<f32>,
+    pub orientation: UnitQuaternion<f32>,
+}
+
+impl Default for Pose {
+    fn default() -> Self {
+        Pose {
+            position: Vector3::zeros(),
+            orientation: UnitQuaternion::identity(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Hand {
+    Left,
+    Right,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct HandState {
+    pub pose: Pose,
+    pub tracked: bool,
+    // Analog values in 0..1
+    pub trigger: f32,
+    pub grip: f32,
+}
+
+/* Player intent resource */
+// Everything gameplay systems need to know about what the player wants
+// to do this frame, independent of the device it came from. Written by
+// exactly one backend system per frame
+#[derive(Clone, Debug, Default)]
+pub struct PlayerIntent {
+    // x is strafe right, y is forward; length is at most 1
+    pub locomotion: Vector2<f32>,
+    // Yaw and pitch to turn by this frame, in radians
+    pub look: Vector2<f32>,
+    pub head: Pose,
+    pub head_tracked: bool,
+    pub left: HandState,
+    pub right: HandState,
+    pub run: bool,
+    pub crouch: bool,
+    pub toggle_camera: bool,
+    // Switch the view to the squad's drone and back
+    pub toggle_drone: bool,
+    // Send the squad's dogs after the guard they track, or have them bark
+    pub dog_takedown: bool,
+    pub dog_bark: bool,
+    // Send the squad's medics to revive the closest downed squad member, or to treat the most injured
+    pub medic_revive: bool,
+    pub medic_heal: bool,
+    // Held while aiming a teleport, which happens on release
+    pub teleport: bool,
+    // Turn left (positive) or right (negative) in -1..1
+    pub turn: f32,
+    // Commander camera controls: rotation in -1..1, zoom in wheel steps
+    pub rotate: f32,
+    pub zoom: f32,
+    // Screen-space cursor, if there is one
+    pub pointer: Option<(f32, f32)>,
+}
+
+impl PlayerIntent {
+    pub fn hand(&self, hand: Hand) -> &HandState {
+        match hand {
+            Hand::Left => &self.left,
+            Hand::Right => &self.right,
+        }
+    }
+
+    pub fn hand_mut(&mut self, hand: Hand) -> &mut HandState {
+        match hand {
+            Hand::Left => &mut self.left,
+            Hand::Right => &mut self.right,
+        }
+    }
+}
+
+/* XR backends */
+// A source of head and hand tracking. The OpenXR runtime will implement
+// this; SimulatedXr plays back a script so the pipeline runs without a headset
+pub trait XrBackend: Send + Sync + 'static {
+    fn poll(&mut self, time: f32, intent: &mut PlayerIntent);
+}
+
+// One moment of a simulated XR session. Positions are in metres,
+// rotations are (pitch, yaw, roll) in degrees
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct XrKeyframe {
+    pub time: f32,
+    pub head: ([f32; 3], [f32; 3]),
+    pub left: ([f32; 3], [f32; 3]),
+    pub right: ([f32; 3], [f32; 3]),
+    pub locomotion: [f32; 2],
+    pub turn: f32,
+    pub teleport: bool,
+    pub left_trigger: f32,
+    pub left_grip: f32,
+    pub right_trigger: f32,
+    pub right_grip: f32,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct XrScript {
+    pub looping: bool,
+    pub frames: Vec<XrKeyframe>,
+}
+
+pub struct SimulatedXr {
+    script: XrScript,
+}
+
+impl SimulatedXr {
+    pub fn new(script: XrScript) -> Self {
+        SimulatedXr { script: script }
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let text = fs::read_to_string(path.as_ref())
+            .map_err(|e| format!("[ERROR][raiders::intent] Cannot read XR script: {}", e))?;
+        let script = ron::de::from_str(&text)
+            .map_err(|e| format!("[ERROR][raiders::intent] Invalid XR script: {}", e))?;
+
+        Ok(SimulatedXr::new(script))
+    }
+
+    // Interpolated state of the script at `time` seconds
+    pub fn sample(&self, time: f32) -> Option<XrKeyframe> {
+        let frames = &self.script.frames;
+        let last = frames.last()?;
+        let time = if self.script.looping && last.time > 0.0 { time % last.time } else { time };
+
+        let next = frames.iter().position(|frame| frame.time > time);
+        match next {
+            None => Some(last.clone()),
+            Some(0) => Some(frames[0].clone()),
+            Some(i) => {
+                let (a, b) = (&frames[i - 1], &frames[i]);
+                let t = (time - a.time) / (b.time - a.time);
+
+                Some(XrKeyframe {
+                    time: time,
+                    head: lerp_pose(a.head, b.head, t),
+                    left: lerp_pose(a.left, b.left, t),
+                    right: lerp_pose(a.right, b.right, t),
+                    locomotion: [lerp(a.locomotion[0], b.locomotion[0], t), lerp(a.locomotion[1], b.locomotion[1], t)],
+                    turn: lerp(a.turn, b.turn, t),
+                    teleport: a.teleport,
+                    left_trigger: lerp(a.left_trigger, b.left_trigger, t),
+                    left_grip: lerp(a.left_grip, b.left_grip, t),
+                    right_trigger: lerp(a.right_trigger, b.right_trigger, t),
+                    right_grip: lerp(a.right_grip, b.right_grip, t),
+                })
+            },
+        }
+    }
+}
+
+impl XrBackend for SimulatedXr {
+    fn poll(&mut self, time: f32, intent: &mut PlayerIntent) {
+        let frame = match self.sample(time) {
+            Some(frame) => frame,
+            None => return,
+        };
+
+        intent.head = pose(frame.head);
+        intent.head_tracked = true;
+        intent.left = HandState {
+            pose: pose(frame.left),
+            tracked: true,
+            trigger: frame.left_trigger,
+            grip: frame.left_grip,
+        };
+        intent.right = HandState {
+            pose: pose(frame.right),
+            tracked: true,
+            trigger: frame.right_trigger,
+            grip: frame.right_grip,
+        };
+        intent.locomotion = Vector2::new(frame.locomotion[0], frame.locomotion[1]);
+        intent.turn = frame.turn;
+        intent.teleport = frame.teleport;
+    }
+}
+
+pub fn pose((position, [pitch, yaw, roll]): ([f32; 3], [f32; 3])) -> Pose {
+    Pose {
+        position: Vector3::new(position[0], position[1], position[2]),
+        orientation: UnitQuaternion::from_euler_angles(pitch.to_radians(), yaw.to_radians(), roll.to_radians()),
+    }
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+fn lerp_pose(a: ([f32; 3], [f32; 3]), b: ([f32; 3], [f32; 3]), t: f32) -> ([f32; 3], [f32; 3]) {
+    let mut out = a;
+    for i in 0..3 {
+        out.0[i] = lerp(a.0[i], b.0[i], t);
+        out.1[i] = lerp(a.1[i], b.1[i], t);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 1e-5;
+
+    fn frame(time: f32, head: [f32; 3], right_trigger: f32, teleport: bool) -> XrKeyframe {
+        XrKeyframe {
+            time: time,
+            head: (head, [0.0, 0.0, 0.0]),
+            right_trigger: right_trigger,
+            teleport: teleport,
+            ..XrKeyframe::default()
+        }
+    }
+
+    fn backend(looping: bool) -> SimulatedXr {
+        SimulatedXr::new(XrScript {
+            looping: looping,
+            frames: vec![
+                frame(0.0, [0.0, 1.6, 0.0], 0.0, true),
+                frame(2.0, [2.0, 1.6, -4.0], 1.0, false),
+            ],
+        })
+    }
+
+    #[test]
+    fn empty_script_leaves_intent_alone() {
+        let mut xr = SimulatedXr::new(XrScript::default());
+        assert!(xr.sample(1.0).is_none());
+
+        let mut intent = PlayerIntent::default();
+        xr.poll(1.0, &mut intent);
+        assert!(!intent.head_tracked);
+        assert!(!intent.right.tracked);
+    }
+
+    #[test]
+    fn sample_interpolates_between_keyframes() {
+        let frame = backend(false).sample(1.0).unwrap();
+        assert!((frame.head.0[0] - 1.0).abs() < EPSILON);
+        assert!((frame.head.0[2] + 2.0).abs() < EPSILON);
+        assert!((frame.right_trigger - 0.5).abs() < EPSILON);
+        // Buttons hold their value until the next keyframe
+        assert!(frame.teleport);
+    }
+
+    #[test]
+    fn sample_clamps_outside_the_script() {
+        let xr = backend(false);
+        assert_eq!(xr.sample(-1.0).unwrap().head.0, [0.0, 1.6, 0.0]);
+        assert_eq!(xr.sample(5.0).unwrap().head.0, [2.0, 1.6, -4.0]);
+    }
+
+    #[test]
+    fn looping_script_wraps_around() {
+        let frame = backend(true).sample(3.0).unwrap();
+        assert!((frame.head.0[0] - 1.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn poll_writes_poses_and_buttons_into_the_intent() {
+        let mut xr = SimulatedXr::new(XrScript {
+            looping: false,
+            frames: vec![XrKeyframe {
+                head: ([0.0, 1.7, 0.0], [0.0, 90.0, 0.0]),
+                left: ([-0.2, 1.2, -0.3], [0.0, 0.0, 0.0]),
+                right: ([0.2, 1.2, -0.3], [0.0, 0.0, 0.0]),
+                locomotion: [0.0, 1.0],
+                turn: -0.5,
+                teleport: true,
+                left_grip: 1.0,
+                right_trigger: 0.8,
+                ..XrKeyframe::default()
+            }],
+        });
+        let mut intent = PlayerIntent::default();
+        xr.poll(0.0, &mut intent);
+
+        assert!(intent.head_tracked && intent.left.tracked && intent.right.tracked);
+        assert_eq!(intent.head.position, Vector3::new(0.0, 1.7, 0.0));
+        // A quarter turn left points the head's -z towards -x
+        let forward = intent.head.orientation * -Vector3::z();
+        assert!((forward - -Vector3::x()).norm() < EPSILON);
+        assert_eq!(intent.hand(Hand::Left).pose.position, Vector3::new(-0.2, 1.2, -0.3));
+        assert_eq!(intent.hand(Hand::Left).grip, 1.0);
+        assert_eq!(intent.hand(Hand::Right).trigger, 0.8);
+        assert_eq!(intent.locomotion, Vector2::new(0.0, 1.0));
+        assert_eq!(intent.turn, -0.5);
+        assert!(intent.teleport);
+    }
+
+    #[test]
+    fn scripts_load_from_ron() {
+        let script: XrScript = ron::de::from_str("(looping: true, frames: [(time: 0.0, teleport: true), (time: 1.0)])").unwrap();
+        let frame = SimulatedXr::new(script).sample(0.5).unwrap();
+        assert!(frame.teleport);
+        assert_eq!(frame.head, ([0.0; 3], [0.0; 3]));
+    }
+}
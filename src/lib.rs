@@ -1,6 +1,14 @@
 extern crate amethyst;
 
 pub mod state;
-//pub mod component;
+pub mod cli;
+pub mod component;
+pub mod config;
+pub mod fog;
 pub mod system;
 pub mod gen;
+pub mod intent;
+pub mod mission;
+pub mod physics;
+pub mod sky;
+pub mod vr;
@@ -2,55 +2,179 @@
 static GLOBAL: jemallocator::Jemalloc = jemallocator::Jemalloc;
 
 use raiders::{
+    cli::LaunchOptions,
+    config::{
+        CacheConfig, CameraConfig, DroneConfig, FogConfig, GuardConfig, InputBackend, InputConfig, LocomotionConfig, MissionConfig,
+        MovementConfig, TerrainConfig, TimeConfig, VrConfig, VrMode, WeatherConfig,
+    },
+    gen::cache::MapCache,
+    intent::SimulatedXr,
     state::*,
     system::{
+        camera::CameraSystem,
         control::ControllerSystem,
+        fog::FogSystem,
+        footstep::FootstepSystem,
+        guard::{GuardAttackSystem, GuardSystem},
+        hands::{BowSystem, HandSystem, ProjectileSystem},
+        health::HealthSystem,
+        hud::HudSystem,
+        locomotion::{TeleportSystem, TurnSystem},
+        minimap::MinimapSystem,
+        sky::{DayNightSystem, WeatherSystem},
+        mission::{DetectionSystem, ExtractionSystem, LootSystem, MissionSystem},
+        intent::{DesktopIntentSystem, XrIntentSystem},
+        physics::ColliderSyncSystem,
+        squad::{DogSystem, DroneSystem, MedicSystem, OrderSystem},
+        vr::VrRigSystem,
+        world_ui::{LaserPointerSystem, WorldUiSystem},
     },
+    sky::render::RenderSky,
+    vr::render::RenderStereo,
 };
 use amethyst::{
+    config::Config,
     core::transform::TransformBundle,
     assets::PrefabLoaderSystemDesc,
     input::{InputBundle, StringBindings},
     prelude::*,
     renderer::{
-        plugins::{RenderFlat3D, RenderToWindow},
+        plugins::{RenderDebugLines, RenderPbr3D, RenderToWindow},
         types::DefaultBackend,
         RenderingBundle,
     },
     ui::{UiBundle, RenderUi},
     utils::application_root_dir,
+    window::DisplayConfig,
+    LoggerConfig,
 };
 
+// Shown until a raid's sky takes over
+const CLEAR_COLOR: [f32; 4] = [0.34, 0.36, 0.52, 1.0];
+
 fn main() -> amethyst::Result<()> {
-    amethyst::start_logger(Default::default());
+    let mut options = LaunchOptions::from_args();
+    // A bad heightmap would otherwise only turn up on the loading screen
+    if let Some(terrain) = &options.terrain {
+        terrain.validate().map_err(amethyst::Error::from_string)?;
+    }
+
+    let mut logger_config = LoggerConfig::default();
+    if let Some(level) = options.log_level {
+        logger_config.level_filter = level;
+    }
+    amethyst::start_logger(logger_config);
 
     let app_root = application_root_dir()?;
 
-    let assets_dir = app_root.join("assets");
-    let config_dir = app_root.join("config");
-    let display_config_path = config_dir.join("display.ron");
+    let assets_dir = options.assets_dir.clone().unwrap_or_else(|| app_root.join("assets"));
+    let config_dir = options.config_dir.clone().unwrap_or_else(|| app_root.join("config"));
+    options.assets_dir = Some(assets_dir.clone());
+    options.config_dir = Some(config_dir.clone());
+
+    let mut display_config = DisplayConfig::load(config_dir.join("display.ron"))?;
+    if let Some(size) = options.window_size {
+        display_config.dimensions = Some(size);
+    }
     let binding_path = config_dir.join("bindings.ron");
+    let movement_config = MovementConfig::load(config_dir.join("movement.ron"))?;
+    let camera_config = CameraConfig::load(config_dir.join("camera.ron"))?;
+    let input_config = InputConfig::load(config_dir.join("input.ron"))?;
+    let vr_config = VrConfig::load(config_dir.join("vr.ron"))?;
+    let locomotion_config = LocomotionConfig::load(config_dir.join("locomotion.ron"))?;
+    let cache_config = CacheConfig::load(config_dir.join("cache.ron"))?;
+    let terrain_config = TerrainConfig::load(config_dir.join("terrain.ron"))?;
+    let mission_config = MissionConfig::load(config_dir.join("mission.ron"))?;
+    let guard_config = GuardConfig::load(config_dir.join("guard.ron"))?;
+    let time_config = TimeConfig::load(config_dir.join("time.ron"))?;
+    let drone_config = DroneConfig::load(config_dir.join("drone.ron"))?;
+    let fog_config = FogConfig::load(config_dir.join("fog.ron"))?;
+    let weather_config = WeatherConfig::load(config_dir.join("weather.ron"))?;
+
+    MapCache::new(&assets_dir, cache_config.max_bytes()).cleanup();
 
     let input_bundle = InputBundle::<StringBindings>::new()
         .with_bindings_from_file(binding_path)?;
 
     let game_data = GameDataBuilder::default()
         .with_system_desc(PrefabLoaderSystemDesc::<ScenePrefabData>::default(), "", &[])
+        .with_system_desc(PrefabLoaderSystemDesc::<MapPrefabData>::default(), "", &[])
         .with_bundle(TransformBundle::new())?
-        .with_bundle(input_bundle)?
-        .with(ControllerSystem, "controller_system", &["input_system"])
+        .with_bundle(input_bundle)?;
+
+    let game_data = match input_config.backend {
+        InputBackend::Desktop => game_data
+            .with(DesktopIntentSystem::default(), "intent_system", &["input_system"]),
+        InputBackend::SimulatedXr(script) => {
+            let backend = SimulatedXr::from_file(config_dir.join(script))
+                .map_err(amethyst::Error::from_string)?;
+            game_data.with(XrIntentSystem::new(backend), "intent_system", &["input_system"])
+        },
+    };
+
+    let game_data = game_data
+        .with(ControllerSystem, "controller_system", &["intent_system"])
+        .with(TurnSystem, "turn_system", &["intent_system"])
+        .with(TeleportSystem, "teleport_system", &["controller_system"])
+        .with(DroneSystem, "drone_system", &["intent_system"])
+        .with(CameraSystem, "camera_system", &["controller_system", "teleport_system", "drone_system"])
+        .with(HandSystem, "hand_system", &["intent_system"])
+        .with(BowSystem, "bow_system", &["hand_system"])
+        .with(ProjectileSystem::default(), "projectile_system", &[])
+        .with(ColliderSyncSystem, "collider_sync_system", &["transform_system"])
+        .with(OrderSystem::default(), "order_system", &["controller_system"])
+        .with(DogSystem::default(), "dog_system", &["intent_system", "order_system"])
+        .with(FootstepSystem::default(), "footstep_system", &["controller_system", "order_system"])
+        .with(GuardSystem::default(), "guard_system", &["dog_system", "footstep_system"])
+        .with(MedicSystem::default(), "medic_system", &["intent_system", "order_system"])
+        .with(LootSystem, "loot_system", &["controller_system"])
+        .with(FogSystem::default(), "fog_system", &["order_system", "drone_system", "dog_system", "guard_system"])
+        .with(DetectionSystem, "detection_system", &["collider_sync_system", "controller_system", "guard_system"])
+        .with(GuardAttackSystem::default(), "guard_attack_system", &["detection_system"])
+        .with(HealthSystem::default(), "health_system", &["projectile_system", "dog_system", "medic_system", "guard_attack_system"])
+        .with(MissionSystem::default(), "mission_system", &["loot_system", "detection_system", "health_system"])
+        .with(ExtractionSystem, "extraction_system", &["mission_system"])
+        .with(VrRigSystem::default(), "vr_rig_system", &["intent_system"])
+        .with(DayNightSystem, "day_night_system", &[])
+        .with(WeatherSystem::default(), "weather_system", &["camera_system"])
         .with_bundle(UiBundle::<StringBindings>::new())?
-        .with_bundle(
-            RenderingBundle::<DefaultBackend>::new()
-                .with_plugin(
-                    RenderToWindow::from_config_path(display_config_path)?
-                        .with_clear([0.34, 0.36, 0.52, 1.0]),
-                )
-                .with_plugin(RenderUi::default())
-                .with_plugin(RenderFlat3D::default()),
-        )?;
-
-    let mut game = Application::new(assets_dir, MainMenuState::default(), game_data)?;
+        .with(HudSystem::default(), "hud_system", &["mission_system"])
+        .with(MinimapSystem::default(), "minimap_system", &["detection_system", "fog_system", "hud_system"])
+        .with(WorldUiSystem, "world_ui_system", &["ui_transform", "hud_system", "minimap_system"])
+        .with(LaserPointerSystem, "laser_pointer_system", &["world_ui_system", "intent_system"]);
+
+    let rendering = RenderingBundle::<DefaultBackend>::new()
+        .with_plugin(
+            RenderToWindow::from_config(display_config)
+                .with_clear(CLEAR_COLOR),
+        )
+        .with_plugin(RenderSky::new(CLEAR_COLOR))
+        .with_plugin(RenderUi::default())
+        .with_plugin(RenderDebugLines::default());
+    let rendering = match vr_config.mode {
+        VrMode::Off => rendering.with_plugin(RenderPbr3D::default()),
+        VrMode::Mock => rendering.with_plugin(RenderStereo::new(CLEAR_COLOR)),
+        VrMode::OpenXr => return Err(amethyst::Error::from_string(
+            "[ERROR][raiders] The OpenXR compositor is not available yet, use Mock instead",
+        )),
+    };
+    let game_data = game_data.with_bundle(rendering)?;
+
+    let mut game = Application::build(assets_dir, MainMenuState::default())?
+        .with_resource(movement_config)
+        .with_resource(camera_config)
+        .with_resource(vr_config)
+        .with_resource(locomotion_config)
+        .with_resource(cache_config)
+        .with_resource(terrain_config)
+        .with_resource(mission_config)
+        .with_resource(guard_config)
+        .with_resource(time_config)
+        .with_resource(drone_config)
+        .with_resource(fog_config)
+        .with_resource(weather_config)
+        .with_resource(options)
+        .build(game_data)?;
     game.run();
 
     Ok(())
@@ -0,0 +1,332 @@
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use crate::{
+    config::MissionConfig,
+    gen::{Prop, PropKind},
+};
+
+// Mixed into the map seed, so the mission does not repeat the prop placement's random numbers
+const SEED_SALT: u64 = 0x6d69_7373_696f_6e73;
+// Distance from a building's centre to the spot in front of it, in metres
+const DOOR_DISTANCE: f32 = 3.5;
+const GUARD_DISTANCE: f32 = 5.0;
+const MAX_LOOT: usize = 3;
+const LOOT_VALUES: [u32; 3] = [100, 250, 500];
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum ObjectiveKind {
+    StealLoot,
+    EliminateTarget,
+    ExtractSquad,
+    Undetected,
+}
+
+impl ObjectiveKind {
+    pub fn description(&self) -> &'static str {
+        match self {
+            ObjectiveKind::StealLoot => "Steal the loot",
+            ObjectiveKind::EliminateTarget => "Eliminate the target",
+            ObjectiveKind::ExtractSquad => "Extract the squad",
+            ObjectiveKind::Undetected => "Remain undetected",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum ObjectiveStatus {
+    Active,
+    Completed,
+    Failed,
+}
+
+// Optional objectives only add to the score; the squad can extract without them
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct Objective {
+    pub kind: ObjectiveKind,
+    pub status: ObjectiveStatus,
+    pub optional: bool,
+}
+
+/* How a raid ended */
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum Outcome {
+    Extracted,
+    Failed(FailReason),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum FailReason {
+    PlayerKilled,
+    AlarmTimeout,
+    // Every squad member but the player is down
+    SquadLost,
+}
+
+impl Outcome {
+    pub fn description(&self) -> &'static str {
+        match self {
+            Outcome::Extracted => "Squad extracted",
+            Outcome::Failed(FailReason::PlayerKilled) => "Raid failed: you were killed",
+            Outcome::Failed(FailReason::AlarmTimeout) => "Raid failed: the alarm brought reinforcements",
+            Outcome::Failed(FailReason::SquadLost) => "Raid failed: the rest of the squad was lost",
+        }
+    }
+}
+
+/* Mission layout */
+// Where the mission's entities are spawned; positions are on the terrain
+// surface, yaws in radians around the y axis
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct MissionLayout {
+    pub settlement: [f32; 3],
+    pub loot: Vec<LootSpot>,
+    pub target: Option<[f32; 3]>,
+    pub guards: Vec<GuardPost>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct LootSpot {
+    pub position: [f32; 3],
+    pub value: u32,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct GuardPost {
+    pub position: [f32; 3],
+    pub yaw: f32,
+}
+
+/* Mission resource */
+// Objectives and running tallies of the current raid. Generated from the
+// settlement a map's props form, so the same map always gets the same
+// mission. Systems report what happens through the `record_*` methods
+#[derive(Clone, Debug, Default)]
+pub struct Mission {
+    objectives: Vec<Objective>,
+    layout: MissionLayout,
+    elapsed: f32,
+    detections: u32,
+    casualties: u32,
+    loot_taken: u32,
+    loot_value: u32,
+    // Seconds the alarm has been raised for without a break
+    alarm: f32,
+    outcome: Option<Outcome>,
+}
+
+impl Mission {
+    pub fn generate(props: &[Prop], seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed ^ SEED_SALT);
+        let buildings: Vec<&Prop> = props.iter().filter(|prop| prop.kind != PropKind::Tree).collect();
+
+        let mut settlement = [0.0; 3];
+        for building in &buildings {
+            for i in 0..3 {
+                settlement[i] += building.position[i] / buildings.len() as f32;
+            }
+        }
+
+        // Loot waits outside a few of the shacks, picked at random
+        let mut shacks: Vec<&Prop> = buildings.iter().filter(|prop| prop.kind == PropKind::Shack).cloned().collect();
+        let mut loot = Vec::new();
+        while !shacks.is_empty() && loot.len() < MAX_LOOT && (loot.is_empty() || rng.gen_bool(0.5)) {
+            let shack = shacks.swap_remove(rng.gen_range(0, shacks.len()));
+            loot.push(LootSpot {
+                position: in_front(shack, DOOR_DISTANCE),
+                value: LOOT_VALUES[rng.gen_range(0, LOOT_VALUES.len())],
+            });
+        }
+
+        // The target stays at one of the tall buildings, all of which are guarded
+        let towers: Vec<&Prop> = buildings.iter().filter(|prop| prop.kind == PropKind::TallBuilding).cloned().collect();
+        let target = if towers.is_empty() {
+            None
+        } else {
+            Some(in_front(towers[rng.gen_range(0, towers.len())], DOOR_DISTANCE))
+        };
+
+        let guards = buildings.iter()
+            .enumerate()
+            .filter(|(i, building)| building.kind == PropKind::TallBuilding || i % 2 == 0)
+            .map(|(_, building)| GuardPost {
+                position: in_front(building, GUARD_DISTANCE),
+                yaw: building.yaw,
+            })
+            .collect();
+
+        let mut objectives = Vec::new();
+        if !loot.is_empty() {
+            objectives.push(Objective::new(ObjectiveKind::StealLoot, false));
+        }
+        if target.is_some() {
+            objectives.push(Objective::new(ObjectiveKind::EliminateTarget, false));
+        }
+        objectives.push(Objective::new(ObjectiveKind::ExtractSquad, false));
+        objectives.push(Objective::new(ObjectiveKind::Undetected, true));
+
+        Mission {
+            objectives: objectives,
+            layout: MissionLayout {
+                settlement: settlement,
+                loot: loot,
+                target: target,
+                guards: guards,
+            },
+            ..Default::default()
+        }
+    }
+
+    pub fn objectives(&self) -> &[Objective] { self.objectives.as_slice() }
+    pub fn layout(&self) -> &MissionLayout { &self.layout }
+    pub fn elapsed(&self) -> f32 { self.elapsed }
+    pub fn detections(&self) -> u32 { self.detections }
+    pub fn casualties(&self) -> u32 { self.casualties }
+    pub fn loot_value(&self) -> u32 { self.loot_value }
+    pub fn alarm(&self) -> f32 { self.alarm }
+    pub fn outcome(&self) -> Option<Outcome> { self.outcome }
+
+    pub fn status(&self, kind: ObjectiveKind) -> Option<ObjectiveStatus> {
+        self.objectives.iter().find(|objective| objective.kind == kind).map(|objective| objective.status)
+    }
+
+    // Whether everything but the extraction itself is done
+    pub fn ready_to_extract(&self) -> bool {
+        self.objectives.iter()
+            .filter(|objective| !objective.optional && objective.kind != ObjectiveKind::ExtractSquad)
+            .all(|objective| objective.status == ObjectiveStatus::Completed)
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.outcome.is_some()
+    }
+
+    /* Events */
+    pub fn tick(&mut self, dt: f32) {
+        if !self.is_over() {
+            self.elapsed += dt;
+        }
+    }
+
+    pub fn record_detection(&mut self) {
+        self.detections += 1;
+        self.set_status(ObjectiveKind::Undetected, ObjectiveStatus::Failed);
+    }
+
+    // Called every frame with whether any guard is alerted; the alarm
+    // clock restarts once every guard has calmed down
+    pub fn record_alarm(&mut self, raised: bool, dt: f32) {
+        self.alarm = if raised { self.alarm + dt } else { 0.0 };
+    }
+
+    pub fn record_casualty(&mut self) {
+        self.casualties += 1;
+    }
+
+    pub fn record_loot(&mut self, value: u32) {
+        self.loot_taken += 1;
+        self.loot_value += value;
+        if self.loot_taken as usize >= self.layout.loot.len() {
+            self.set_status(ObjectiveKind::StealLoot, ObjectiveStatus::Completed);
+        }
+    }
+
+    pub fn record_target_eliminated(&mut self) {
+        self.set_status(ObjectiveKind::EliminateTarget, ObjectiveStatus::Completed);
+    }
+
+    // Ends the raid; objectives still open are settled
+    pub fn record_extraction(&mut self) {
+        self.end(Outcome::Extracted);
+    }
+
+    pub fn record_failure(&mut self, reason: FailReason) {
+        self.end(Outcome::Failed(reason));
+    }
+
+    fn end(&mut self, outcome: Outcome) {
+        if self.is_over() {
+            return;
+        }
+        self.outcome = Some(outcome);
+
+        if outcome == Outcome::Extracted {
+            self.set_status(ObjectiveKind::ExtractSquad, ObjectiveStatus::Completed);
+            self.set_status(ObjectiveKind::Undetected, ObjectiveStatus::Completed);
+        }
+        for objective in &mut self.objectives {
+            if objective.status == ObjectiveStatus::Active {
+                objective.status = ObjectiveStatus::Failed;
+            }
+        }
+    }
+
+    fn set_status(&mut self, kind: ObjectiveKind, status: ObjectiveStatus) {
+        for objective in &mut self.objectives {
+            if objective.kind == kind && objective.status == ObjectiveStatus::Active {
+                objective.status = status;
+            }
+        }
+    }
+
+    pub fn score(&self, config: &MissionConfig) -> Score {
+        let completed = self.objectives.iter()
+            .filter(|objective| objective.status == ObjectiveStatus::Completed)
+            .count() as i32;
+        // Only a raid that got out earns the time bonus
+        let time = if self.outcome == Some(Outcome::Extracted) {
+            ((config.par_time - self.elapsed) * config.time_points).max(0.0).round() as i32
+        } else {
+            0
+        };
+
+        let mut score = Score {
+            outcome: self.outcome,
+            objectives: self.objectives.clone(),
+            elapsed: self.elapsed,
+            detection_count: self.detections,
+            casualty_count: self.casualties,
+            objective_points: completed * config.objective_points,
+            time_points: time,
+            detection_points: -(self.detections as i32) * config.detection_penalty,
+            casualty_points: -(self.casualties as i32) * config.casualty_penalty,
+            loot_points: self.loot_value as i32,
+            total: 0,
+        };
+        score.total = score.objective_points + score.time_points + score.detection_points
+            + score.casualty_points + score.loot_points;
+        score
+    }
+}
+
+impl Objective {
+    fn new(kind: ObjectiveKind, optional: bool) -> Self {
+        Objective {
+            kind: kind,
+            status: ObjectiveStatus::Active,
+            optional: optional,
+        }
+    }
+}
+
+/* Score breakdown */
+// Points per category as shown on the result screen; penalties are negative
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Score {
+    pub outcome: Option<Outcome>,
+    pub objectives: Vec<Objective>,
+    pub elapsed: f32,
+    pub detection_count: u32,
+    pub casualty_count: u32,
+    pub objective_points: i32,
+    pub time_points: i32,
+    pub detection_points: i32,
+    pub casualty_points: i32,
+    pub loot_points: i32,
+    pub total: i32,
+}
+
+// Point on the ground `distance` metres in front of a building's door side
+fn in_front(building: &Prop, distance: f32) -> [f32; 3] {
+    let [x, y, z] = building.position;
+    [x + building.yaw.sin() * distance, y, z + building.yaw.cos() * distance]
+}
@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+use amethyst::{
+    core::math::Vector3,
+    ecs::Entity,
+};
+use ncollide3d::{
+    bounding_volume::{self, AABB, BoundingVolume},
+    math::{Isometry, Point, Vector},
+    procedural,
+    query::{self, Proximity, Ray},
+    shape::{Ball, Capsule, Compound, Cuboid, ShapeHandle, TriMesh},
+    world::{CollisionGroups, CollisionObjectHandle, CollisionWorld, GeometricQueryType},
+};
+use crate::gen::{Prop, PropKind};
+
+/* Collision groups */
+pub const GROUP_TERRAIN: usize = 0;
+pub const GROUP_PROPS: usize = 1;
+pub const GROUP_UNITS: usize = 2;
+
+const MARGIN: f32 = 0.02;
+
+// What a collision object stands for in the game world
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Body {
+    Terrain,
+    Prop(PropKind),
+    Unit(Entity),
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct RayHit {
+    pub body: Body,
+    pub toi: f32,
+    pub point: Point<f32>,
+    pub normal: Vector<f32>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SweepHit {
+    pub body: Body,
+    pub toi: f32,
+    pub center: Point<f32>,
+}
+
+/* Collision world resource */
+// Holds the terrain, static props and unit capsules, and answers
+// the ray, sweep and overlap queries used by gameplay systems
+pub struct Physics {
+    world: CollisionWorld<f32, Body>,
+    terrain: Option<CollisionObjectHandle>,
+    units: HashMap<Entity, CollisionObjectHandle>,
+}
+
+impl Default for Physics {
+    fn default() -> Self {
+        Physics {
+            world: CollisionWorld::new(MARGIN),
+            terrain: None,
+            units: HashMap::new(),
+        }
+    }
+}
+
+impl Physics {
+    pub fn set_terrain(&mut self, mesh: &procedural::TriMesh<f32>) {
+        if let Some(handle) = self.terrain.take() {
+            self.world.remove(&[handle]);
+        }
+
+        let shape = ShapeHandle::new(TriMesh::from(mesh.clone()));
+        let handle = self.world.add(
+            Isometry::identity(),
+            shape,
+            membership(GROUP_TERRAIN),
+            GeometricQueryType::Proximity(0.0),
+            Body::Terrain,
+        ).handle();
+        self.terrain = Some(handle);
+    }
+
+    pub fn add_prop(&mut self, prop: &Prop) {
+        let [x, y, z] = prop.position;
+        let position = Isometry::new(
+            Vector::new(x, y + prop.kind.ground_offset(), z),
+            Vector::y() * prop.yaw,
+        );
+
+        self.world.add(
+            position,
+            prop_shape(prop.kind),
+            membership(GROUP_PROPS),
+            GeometricQueryType::Proximity(0.0),
+            Body::Prop(prop.kind),
+        );
+    }
+
+    // Creates or moves the capsule belonging to `entity`
+    pub fn sync_unit(&mut self, entity: Entity, center: Point<f32>, radius: f32, half_height: f32) {
+        let position = Isometry::translation(center.x, center.y, center.z);
+
+        match self.units.get(&entity) {
+            Some(handle) => self.world.set_position(*handle, position),
+            None => {
+                let handle = self.world.add(
+                    position,
+                    ShapeHandle::new(Capsule::new(half_height, radius)),
+                    membership(GROUP_UNITS),
+                    GeometricQueryType::Proximity(0.0),
+                    Body::Unit(entity),
+                ).handle();
+                self.units.insert(entity, handle);
+            },
+        }
+    }
+
+    pub fn remove_unit(&mut self, entity: Entity) {
+        if let Some(handle) = self.units.remove(&entity) {
+            self.world.remove(&[handle]);
+        }
+    }
+
+    pub fn has_terrain(&self) -> bool {
+        self.terrain.is_some()
+    }
+
+    pub fn units(&self) -> impl Iterator<Item = &Entity> {
+        self.units.keys()
+    }
+
+    pub fn update(&mut self) {
+        self.world.update();
+    }
+
+    // World-space bounds of the terrain as (min, max), if one is loaded
+    pub fn terrain_bounds(&self) -> Option<(Point<f32>, Point<f32>)> {
+        let object = self.world.collision_object(self.terrain?)?;
+        let aabb: AABB<f32> = bounding_volume::aabb(object.shape().as_ref(), object.position());
+
+        Some((*aabb.mins(), *aabb.maxs()))
+    }
+
+    /* Queries */
+    // Height of the terrain surface below (x, z), if there is terrain there
+    pub fn ground_height(&self, x: f32, z: f32) -> Option<f32> {
+        let (min, max) = self.terrain_bounds()?;
+        let above = Point::new(x, max.y + 1.0, z);
+
+        self.cast_ray(above, -up(), max.y - min.y + 2.0, &filter(&[GROUP_TERRAIN]))
+            .map(|hit| hit.point.y)
+    }
+
+    // Closest hit along `dir` (not necessarily normalised) within `max_toi` multiples of it
+    pub fn cast_ray(&self, origin: Point<f32>, dir: Vector<f32>, max_toi: f32, groups: &CollisionGroups) -> Option<RayHit> {
+        self.cast_ray_ignoring(origin, dir, max_toi, groups, |_| false)
+    }
+
+    // Like `cast_ray`, but passes through every body `ignore` picks out
+    pub fn cast_ray_ignoring<F>(&self, origin: Point<f32>, dir: Vector<f32>, max_toi: f32, groups: &CollisionGroups, ignore: F) -> Option<RayHit>
+        where F: Fn(&Body) -> bool
+    {
+        let ray = Ray::new(origin, dir);
+
+        self.world.interferences_with_ray(&ray, groups)
+            .filter(|(object, intersection)| intersection.toi <= max_toi && !ignore(object.data()))
+            .min_by(|(_, a), (_, b)| a.toi.partial_cmp(&b.toi).unwrap())
+            .map(|(object, intersection)| RayHit {
+                body: *object.data(),
+                toi: intersection.toi,
+                point: ray.point_at(intersection.toi),
+                normal: intersection.normal,
+            })
+    }
+
+    // Moves a sphere from `start` by `motion` and returns the first thing it touches
+    pub fn sweep_sphere(&self, start: Point<f32>, motion: Vector<f32>, radius: f32, groups: &CollisionGroups) -> Option<SweepHit> {
+        let ball = Ball::new(radius);
+        let from = Isometry::translation(start.x, start.y, start.z);
+        let to = Isometry::translation(start.x + motion.x, start.y + motion.y, start.z + motion.z);
+        let swept = bounding_volume::aabb(&ball, &from).merged(&bounding_volume::aabb(&ball, &to));
+
+        self.world.interferences_with_aabb(&swept, groups)
+            .filter_map(|object| {
+                query::time_of_impact(
+                    &from, &motion, &ball,
+                    object.position(), &Vector::zeros(), object.shape().as_ref(),
+                )
+                .filter(|toi| *toi <= 1.0)
+                .map(|toi| (object, toi))
+            })
+            .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
+            .map(|(object, toi)| SweepHit {
+                body: *object.data(),
+                toi: toi,
+                center: start + motion * toi,
+            })
+    }
+
+    // Everything intersecting a sphere at `center`
+    pub fn overlap_sphere(&self, center: Point<f32>, radius: f32, groups: &CollisionGroups) -> Vec<Body> {
+        let ball = Ball::new(radius);
+        let position = Isometry::translation(center.x, center.y, center.z);
+        let aabb = bounding_volume::aabb(&ball, &position);
+
+        self.world.interferences_with_aabb(&aabb, groups)
+            .filter(|object| {
+                query::proximity(&position, &ball, object.position(), object.shape().as_ref(), 0.0)
+                    == Proximity::Intersecting
+            })
+            .map(|object| *object.data())
+            .collect()
+    }
+}
+
+/* Helpers */
+pub fn membership(group: usize) -> CollisionGroups {
+    CollisionGroups::new().with_membership(&[group])
+}
+
+// Groups that only see the given layers, for use as a query filter
+pub fn filter(groups: &[usize]) -> CollisionGroups {
+    CollisionGroups::new().with_whitelist(groups)
+}
+
+// Conversion from amethyst's math types, which use a different nalgebra version
+pub fn point(v: &Vector3<f32>) -> Point<f32> {
+    Point::new(v.x, v.y, v.z)
+}
+
+pub fn vector(v: &Vector3<f32>) -> Vector<f32> {
+    Vector::new(v.x, v.y, v.z)
+}
+
+pub fn up() -> Vector<f32> {
+    Vector::y()
+}
+
+// Rough compound approximations of the prop models, in model space
+fn prop_shape(kind: PropKind) -> ShapeHandle<f32> {
+    let parts = match kind {
+        PropKind::Tree => vec![
+            (Isometry::translation(1.1, -0.9, -1.05), ShapeHandle::new(Cuboid::new(Vector::new(0.25, 1.8, 0.25)))),
+            (Isometry::translation(1.1, 2.9, -1.05), ShapeHandle::new(Ball::new(1.4))),
+        ],
+        PropKind::Shack => vec![
+            (Isometry::translation(0.0, -0.44, 0.61), ShapeHandle::new(Cuboid::new(Vector::new(1.87, 1.2, 1.73)))),
+            (Isometry::translation(0.0, 1.28, 0.61), ShapeHandle::new(Cuboid::new(Vector::new(1.87, 0.55, 1.73)))),
+        ],
+        PropKind::TallBuilding => vec![
+            (Isometry::translation(0.0, -0.05, 0.0), ShapeHandle::new(Cuboid::new(Vector::new(1.0, 2.86, 1.22)))),
+        ],
+    };
+
+    ShapeHandle::new(Compound::new(parts))
+}
@@ -0,0 +1,103 @@
+pub mod render;
+pub mod weather;
+
+use std::f32::consts::PI;
+
+// Hours of sunrise and sunset; the sun stands highest halfway between them
+const SUNRISE: f32 = 6.0;
+const SUNSET: f32 = 18.0;
+// Highest the sun climbs above the horizon, in radians
+const MAX_ELEVATION: f32 = 1.1;
+// Elevation over which the light fades in after sunrise and out before sunset, in radians
+const TWILIGHT: f32 = 0.2;
+// Strongest the sun shines, at noon
+const SUN_INTENSITY: f32 = 4.0;
+const SUN_NOON: [f32; 3] = [1.0, 0.97, 0.9];
+const SUN_LOW: [f32; 3] = [1.0, 0.55, 0.3];
+const AMBIENT_DAY: [f32; 3] = [0.35, 0.37, 0.42];
+const AMBIENT_NIGHT: [f32; 3] = [0.03, 0.04, 0.08];
+const SKY_DAY: [f32; 3] = [0.45, 0.6, 0.85];
+const SKY_LOW: [f32; 3] = [0.75, 0.45, 0.35];
+const SKY_NIGHT: [f32; 3] = [0.02, 0.03, 0.07];
+
+/* Time of day */
+// Clock of the raid in hours since midnight, running `scale` times faster
+// than real time. The sun rises towards +x at six, stands highest towards
+// +z at noon and sets towards -x at six in the evening; the light, the
+// ambient colour and the sky follow it
+#[derive(Clone, Copy, Debug)]
+pub struct TimeOfDay {
+    hours: f32,
+    scale: f32,
+}
+
+impl Default for TimeOfDay {
+    fn default() -> Self {
+        TimeOfDay::new(12.0, 0.0)
+    }
+}
+
+impl TimeOfDay {
+    pub fn new(hours: f32, scale: f32) -> Self {
+        TimeOfDay {
+            hours: hours.rem_euclid(24.0),
+            scale: scale,
+        }
+    }
+
+    pub fn hours(&self) -> f32 { self.hours }
+
+    pub fn advance(&mut self, seconds: f32) {
+        self.hours = (self.hours + seconds * self.scale / 3600.0).rem_euclid(24.0);
+    }
+
+    // Angle of the sun above the horizon in radians, negative at night
+    pub fn sun_elevation(&self) -> f32 {
+        let day = (self.hours - SUNRISE) / (SUNSET - SUNRISE);
+        (day * PI).sin() * MAX_ELEVATION
+    }
+
+    // Unit vector the sunlight travels along, from the sun towards the ground
+    pub fn sun_direction(&self) -> [f32; 3] {
+        let elevation = self.sun_elevation().max(0.0);
+        // Angle along the sun's path, from the east at sunrise to the west at sunset
+        let azimuth = (self.hours - SUNRISE) / (SUNSET - SUNRISE) * PI;
+        let (x, z) = (azimuth.cos(), azimuth.sin());
+        [-x * elevation.cos(), -elevation.sin(), -z * elevation.cos()]
+    }
+
+    // How much of the day's light is out, 0 at night and 1 once the sun is well up
+    pub fn daylight(&self) -> f32 {
+        (self.sun_elevation() / TWILIGHT + 1.0).max(0.0).min(2.0) / 2.0
+    }
+
+    pub fn is_night(&self) -> bool {
+        self.daylight() <= 0.0
+    }
+
+    pub fn sun_intensity(&self) -> f32 {
+        SUN_INTENSITY * (self.sun_elevation() / TWILIGHT).max(0.0).min(1.0)
+    }
+
+    // Reddens as the sun nears the horizon
+    pub fn sun_colour(&self) -> [f32; 3] {
+        lerp(SUN_LOW, SUN_NOON, self.sun_elevation() / (MAX_ELEVATION * 0.5))
+    }
+
+    pub fn ambient_colour(&self) -> [f32; 3] {
+        lerp(AMBIENT_NIGHT, AMBIENT_DAY, self.daylight())
+    }
+
+    pub fn sky_colour(&self) -> [f32; 4] {
+        let daylight = self.daylight();
+        // Dawn and dusk glow while the sun is near the horizon
+        let glow = 1.0 - (self.sun_elevation().abs() / TWILIGHT).min(1.0);
+        let [r, g, b] = lerp(lerp(SKY_NIGHT, SKY_DAY, daylight), SKY_LOW, glow * 0.6);
+        [r, g, b, 1.0]
+    }
+}
+
+fn lerp(a: [f32; 3], b: [f32; 3], t: f32) -> [f32; 3] {
+    let t = t.max(0.0).min(1.0);
+    [a[0] + (b[0] - a[0]) * t, a[1] + (b[1] - a[1]) * t, a[2] + (b[2] - a[2]) * t]
+}
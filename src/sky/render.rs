@@ -0,0 +1,129 @@
+use amethyst::{
+    ecs::World,
+    renderer::{
+        bundle::{RenderOrder, RenderPlan, RenderPlugin, Target},
+        rendy::{
+            command::{QueueId, RenderPassEncoder},
+            factory::Factory,
+            graph::{
+                render::{PrepareResult, RenderGroup, RenderGroupDesc},
+                GraphContext, NodeBuffer, NodeImage,
+            },
+            hal::{
+                command::{AttachmentClear, ClearColor},
+                pass::Subpass,
+                pso::{ClearRect, Rect},
+            },
+        },
+        types::Backend,
+    },
+};
+use super::{weather::Weather, TimeOfDay};
+
+// Colour the frame starts from: the sky at the current time of day seen
+// through the weather, or `fallback` before there is one
+pub fn sky_colour(world: &World, fallback: [f32; 4]) -> [f32; 4] {
+    let sky = world.try_fetch::<TimeOfDay>().map_or(fallback, |time| time.sky_colour());
+    world.try_fetch::<Weather>().map_or(sky, |weather| weather.tint(sky))
+}
+
+/* Sky render plugin */
+// The window's clear colour is fixed when the render graph is built, so
+// the sky is painted over it every frame before anything opaque is drawn
+#[derive(Debug)]
+pub struct RenderSky {
+    fallback: [f32; 4],
+}
+
+impl RenderSky {
+    // `fallback` should match the window's clear colour
+    pub fn new(fallback: [f32; 4]) -> Self {
+        RenderSky { fallback: fallback }
+    }
+}
+
+impl<B: Backend> RenderPlugin<B> for RenderSky {
+    fn on_plan(
+        &mut self,
+        plan: &mut RenderPlan<B>,
+        _factory: &mut Factory<B>,
+        _world: &World,
+        ) -> Result<(), amethyst::Error> {
+        let fallback = self.fallback;
+        plan.extend_target(Target::Main, move |ctx| {
+            ctx.add(RenderOrder::BeforeOpaque, SkyGroupDesc { fallback: fallback }.builder())?;
+            Ok(())
+        });
+
+        Ok(())
+    }
+}
+
+/* Sky render group */
+#[derive(Debug)]
+pub struct SkyGroupDesc {
+    fallback: [f32; 4],
+}
+
+impl<B: Backend> RenderGroupDesc<B, World> for SkyGroupDesc {
+    fn build(
+        self,
+        _ctx: &GraphContext<B>,
+        _factory: &mut Factory<B>,
+        _queue: QueueId,
+        _aux: &World,
+        framebuffer_width: u32,
+        framebuffer_height: u32,
+        _subpass: Subpass<'_, B>,
+        _buffers: Vec<NodeBuffer>,
+        _images: Vec<NodeImage>,
+        ) -> Result<Box<dyn RenderGroup<B, World>>, failure::Error> {
+        Ok(Box::new(SkyGroup {
+            fallback: self.fallback,
+            frame: Rect {
+                x: 0,
+                y: 0,
+                w: framebuffer_width as i16,
+                h: framebuffer_height as i16,
+            },
+        }))
+    }
+}
+
+#[derive(Debug)]
+pub struct SkyGroup {
+    fallback: [f32; 4],
+    frame: Rect,
+}
+
+impl<B: Backend> RenderGroup<B, World> for SkyGroup {
+    fn prepare(
+        &mut self,
+        _factory: &Factory<B>,
+        _queue: QueueId,
+        _index: usize,
+        _subpass: Subpass<'_, B>,
+        _aux: &World,
+        ) -> PrepareResult {
+        // Nothing is recorded ahead of time, the clear happens inline
+        PrepareResult::DrawRecord
+    }
+
+    fn draw_inline(
+        &mut self,
+        encoder: RenderPassEncoder<'_, B>,
+        _index: usize,
+        _subpass: Subpass<'_, B>,
+        aux: &World,
+        ) {
+        let mut encoder = encoder;
+        unsafe {
+            encoder.clear_attachments(
+                vec![AttachmentClear::Color { index: 0, value: ClearColor::Sfloat(sky_colour(aux, self.fallback)) }],
+                Some(ClearRect { rect: self.frame, layers: 0..1 }),
+            );
+        }
+    }
+
+    fn dispose(self: Box<Self>, _factory: &mut Factory<B>, _aux: &World) {}
+}
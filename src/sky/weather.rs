@@ -0,0 +1,143 @@
+use std::{f32::consts::PI, fmt, str::FromStr};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use crate::{config::WeatherConfig, gen::Terrain};
+use super::lerp;
+
+// Mixed into the map seed, so the weather does not repeat the map's random numbers
+const SEED_SALT: u64 = 0x7765_6174_6865_7273;
+// How much of the sky's colour fog and rain wash out to grey
+const FOG_HAZE: f32 = 0.8;
+const RAIN_HAZE: f32 = 0.4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WeatherKind {
+    Clear,
+    Fog,
+    Rain,
+    Wind,
+}
+
+impl WeatherKind {
+    // How often each kind of weather comes up on a terrain, in the order
+    // clear, fog, rain, wind. Fog settles along the coast and in the
+    // foothills' valleys, wind sweeps the mountains and the open fault
+    fn odds(terrain: &Terrain) -> [(WeatherKind, u32); 4] {
+        let [clear, fog, rain, wind] = match terrain {
+            Terrain::Mountains => [4, 2, 1, 3],
+            Terrain::Foothills => [5, 2, 2, 1],
+            Terrain::Coast => [3, 3, 2, 2],
+            Terrain::Fault => [4, 1, 1, 4],
+            Terrain::Custom(_) => [1, 1, 1, 1],
+        };
+        [(WeatherKind::Clear, clear), (WeatherKind::Fog, fog), (WeatherKind::Rain, rain), (WeatherKind::Wind, wind)]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            WeatherKind::Clear => "clear",
+            WeatherKind::Fog => "fog",
+            WeatherKind::Rain => "rain",
+            WeatherKind::Wind => "wind",
+        }
+    }
+}
+
+impl fmt::Display for WeatherKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for WeatherKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "clear" => Ok(WeatherKind::Clear),
+            "fog" => Ok(WeatherKind::Fog),
+            "rain" => Ok(WeatherKind::Rain),
+            "wind" => Ok(WeatherKind::Wind),
+            _ => Err(format!("unknown weather '{}', expected clear, fog, rain or wind", s)),
+        }
+    }
+}
+
+/* Weather */
+// Weather over the map for the whole raid. Fog hides everything beyond
+// `visibility` metres, fog and rain shorten how far everyone sees to the
+// `sight` fraction of their usual range, rain and wind carry footsteps
+// the `hearing` fraction as far, and the wind pushes arrows in flight
+#[derive(Clone, Copy, Debug)]
+pub struct Weather {
+    kind: WeatherKind,
+    visibility: Option<f32>,
+    sight: f32,
+    hearing: f32,
+    // Metres per second, along the ground
+    wind: [f32; 3],
+}
+
+impl Default for Weather {
+    fn default() -> Self {
+        Weather {
+            kind: WeatherKind::Clear,
+            visibility: None,
+            sight: 1.0,
+            hearing: 1.0,
+            wind: [0.0; 3],
+        }
+    }
+}
+
+impl Weather {
+    // The same terrain type and seed always bring the same weather; `kind`
+    // overrides the weather picked for the map but keeps the map's wind direction
+    pub fn generate(terrain: &Terrain, seed: u64, kind: Option<WeatherKind>, config: &WeatherConfig) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed ^ SEED_SALT);
+        let picked = WeatherKind::odds(terrain)
+            .choose_weighted(&mut rng, |(_, odds)| *odds)
+            .map_or(WeatherKind::Clear, |(kind, _)| *kind);
+        let kind = kind.unwrap_or(picked);
+        let heading = rng.gen_range(0.0, 2.0 * PI);
+        let gusts = rng.gen_range(0.75, 1.25);
+
+        let (visibility, sight, hearing, speed) = match kind {
+            WeatherKind::Clear => (None, 1.0, 1.0, config.breeze_speed),
+            WeatherKind::Fog => (Some(config.fog_distance), config.fog_sight, 1.0, 0.0),
+            WeatherKind::Rain => (None, config.rain_sight, config.rain_hearing, config.breeze_speed),
+            WeatherKind::Wind => (None, 1.0, config.wind_hearing, config.wind_speed),
+        };
+
+        Weather {
+            kind: kind,
+            visibility: visibility,
+            sight: sight,
+            hearing: hearing,
+            wind: [heading.cos() * speed * gusts, 0.0, heading.sin() * speed * gusts],
+        }
+    }
+
+    pub fn kind(&self) -> WeatherKind { self.kind }
+
+    // Furthest anything can be seen through the weather, if it limits sight at all
+    pub fn visibility(&self) -> Option<f32> { self.visibility }
+
+    pub fn sight(&self) -> f32 { self.sight }
+
+    pub fn hearing(&self) -> f32 { self.hearing }
+
+    pub fn wind(&self) -> [f32; 3] { self.wind }
+
+    // Fog and rain wash the sky out towards a grey just as bright
+    pub fn tint(&self, colour: [f32; 4]) -> [f32; 4] {
+        let haze = match self.kind {
+            WeatherKind::Fog => FOG_HAZE,
+            WeatherKind::Rain => RAIN_HAZE,
+            _ => return colour,
+        };
+        let [r, g, b, a] = colour;
+        let grey = (r + g + b) / 3.0;
+        let [r, g, b] = lerp([r, g, b], [grey; 3], haze);
+        [r, g, b, a]
+    }
+}
@@ -1,31 +1,91 @@
 use amethyst::{
     prelude::*,
-    ecs::world::*,
+    ecs::{world::*, Read, ReadExpect, WriteStorage},
     renderer::{
+        ImageFormat, Texture,
 	rendy::{
-            mesh::{Normal, Position, TexCoord},
+            mesh::{Normal, Position, Tangent, TexCoord},
         },
+        camera::CameraPrefab,
+        formats::GraphicsPrefab,
+        light::{DirectionalLight, Light},
     },
-    assets::{PrefabLoader, RonFormat},
+    config::Config,
+    assets::{AssetStorage, Handle, Loader, Prefab, PrefabData, PrefabLoader, ProgressCounter, RonFormat},
+    derive::PrefabData,
+    Error,
     utils::scene::BasicScenePrefab,
-    ui::{UiCreator, UiFinder, UiEvent, UiEventType},
+    ui::{UiCreator, UiFinder, UiEvent, UiEventType, UiText},
     core::{
-        transform::Transform,
+        transform::{Parent, ParentHierarchy, Transform},
     },
 };
+use std::{f32::consts::PI, path::Path};
 use crate::{
-    gen::*,
+    cli::LaunchOptions,
+    fog::FogOfWar,
+    component::{
+        Bow, CharacterController, Collider, Dog, Drone, ExtractionZone, Grabbable, Guard, HandPresence, Health, Loot, MapProp,
+        Medic, MissionTarget, Order, SquadMember, SquadRole, Sun,
+    },
+    config::{
+        CacheConfig, DroneConfig, FogConfig, MissionConfig, MovementConfig, TerrainConfig, TimeConfig, UnitDefinition, VrConfig,
+        VrMode, WeatherConfig,
+    },
+    gen::{cache::{CachedMap, MapCache}, navmesh::NavMesh, *},
+    intent::Hand,
+    mission::{Mission, ObjectiveStatus, Score},
+    physics::Physics,
+    sky::{weather::Weather, TimeOfDay},
+    vr::VrRig,
 };
 use rand::random;
-use log::info;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
 
 const BUTTON_START: &str = "start";
 const BUTTON_LOAD: &str = "load";
 const BUTTON_OPTIONS: &str = "options";
 const BUTTON_CREDITS: &str = "credits";
 const CONTAINER: &str = "container";
-
-pub type ScenePrefabData = BasicScenePrefab<(Vec<Position>, Vec<Normal>, Vec<TexCoord>)>;
+const RESULT_TITLE: &str = "result_title";
+const RESULT_OBJECTIVES: &str = "result_objectives";
+const RESULT_BREAKDOWN: &str = "result_breakdown";
+const RESULT_TOTAL: &str = "result_total";
+
+// Units stand with their centre this far above the ground
+pub const UNIT_CENTRE_HEIGHT: f32 = 0.9;
+const UNIT_HEALTH: f32 = 100.0;
+const DRONE_HEALTH: f32 = 30.0;
+// Where prefabs/player.ron puts the player on the (x, z) plane
+const PLAYER_START: [f32; 2] = [0.0, -4.0];
+// Squad members besides the player, with their offsets from the player's start
+const SQUAD: [(SquadRole, [f32; 2]); 5] = [
+    (SquadRole::Soldier, [-1.5, -1.5]),
+    (SquadRole::Marksman, [1.5, -1.5]),
+    (SquadRole::Drone, [0.0, -3.0]),
+    (SquadRole::Dog, [-3.0, 0.0]),
+    (SquadRole::Medic, [3.0, 0.0]),
+];
+// Spacing of the navigation grid units find their way on, in metres
+const NAV_SPACING: f32 = 1.0;
+
+// Shaded rendering needs tangents as well as normals on every mesh
+pub type SceneVertices = (Vec<Position>, Vec<Normal>, Vec<Tangent>, Vec<TexCoord>);
+pub type ScenePrefabData = BasicScenePrefab<SceneVertices>;
+
+// Generated maps, as written by gen::prefab::MapPrefab: the scene fields
+// of ScenePrefabData followed by the game's own components
+#[derive(Default, Deserialize, Serialize, PrefabData)]
+#[serde(default)]
+pub struct MapPrefabData {
+    graphics: Option<GraphicsPrefab<SceneVertices>>,
+    transform: Option<Transform>,
+    light: Option<Light>,
+    camera: Option<CameraPrefab>,
+    prop: Option<MapProp>,
+    extraction: Option<ExtractionZone>,
+}
 
 /* Main Menu State */
 #[derive(Default, Debug)]
@@ -49,7 +109,50 @@ impl SimpleState for MainMenuState {
             loader.load("prefabs/player.ron", RonFormat, ())
         });
         
-        let player = world.create_entity().with(player_handle).build();
+        let player = world.create_entity()
+            .with(player_handle)
+            .with(CharacterController::default())
+            .with(Collider { radius: 0.3, half_height: 0.6, offset: -0.8 })
+            .with(SquadMember::new(SquadRole::Player))
+            .with(Health::new(UNIT_HEALTH))
+            .build();
+
+        // Hands, with the bow starting out in the left one
+        let left_hand = world.create_entity()
+            .with(Parent { entity: player })
+            .with(Transform::default())
+            .with(HandPresence::new(Hand::Left))
+            .build();
+        let _right_hand = world.create_entity()
+            .with(Parent { entity: player })
+            .with(Transform::default())
+            .with(HandPresence::new(Hand::Right))
+            .build();
+
+        let (bow_handle, arrow_handle) = world.exec(|loader: PrefabLoader<'_, ScenePrefabData>| {
+            (
+                loader.load("prefabs/bow.ron", RonFormat, ()),
+                loader.load("prefabs/arrow.ron", RonFormat, ()),
+            )
+        });
+        let bow = world.create_entity()
+            .with(bow_handle)
+            .with(Parent { entity: left_hand })
+            .with(Transform::default())
+            .with(Grabbable { held_by: Some(Hand::Left) })
+            .with(Collider { radius: 0.1, half_height: 0.5, offset: 0.0 })
+            .with(Bow::default())
+            .build();
+        if let Some(hand) = world.write_storage::<HandPresence>().get_mut(left_hand) {
+            hand.holding = Some(bow);
+        }
+        world.insert(ArrowPrefab(arrow_handle));
+
+        let vr_config = world.read_resource::<VrConfig>().clone();
+        if vr_config.mode != VrMode::Off {
+            let rig = VrRig::build(world, player, &vr_config);
+            world.insert(rig);
+        }
 
         world.insert(PlayerEntity(player.id()));
     }
@@ -73,6 +176,11 @@ impl SimpleState for MainMenuState {
             });
         }
 
+        // The menu is skipped once it exists, so it is torn down the same way as on a click
+        if self.container.is_some() && world.read_resource::<LaunchOptions>().skip_menu {
+            return self.start_game(world);
+        }
+
         Trans::None
     }
 
@@ -88,11 +196,7 @@ impl SimpleState for MainMenuState {
                 target,
             }) => {
                 if Some(target) == self.button_start {
-                    info!("[Trans::Switch] Switching to LoadingState");
-                    world.delete_entity(self.container.expect("[ERROR][raiders::state] Container not found"))
-                        .unwrap();
-                    
-                    return Trans::Switch(Box::new(LoadingState::default()));
+                    return self.start_game(world);
                 }
                 if Some(target) == self.button_load || Some(target) == self.button_options || Some(target) == self.button_credits {
                     info!("This Buttons functionality is not yet implemented!");
@@ -107,6 +211,16 @@ impl SimpleState for MainMenuState {
     }
 }
 
+impl MainMenuState {
+    fn start_game(&mut self, world: &mut World) -> Trans<GameData<'static, 'static>, StateEvent> {
+        info!("[Trans::Switch] Switching to LoadingState");
+        world.delete_entity(self.container.take().expect("[ERROR][raiders::state] Container not found"))
+            .unwrap();
+
+        Trans::Switch(Box::new(LoadingState::default()))
+    }
+}
+
 /* Loading State */
 #[derive(Default, Debug)]
 pub struct LoadingState {
@@ -120,13 +234,83 @@ impl SimpleState for LoadingState {
         
         world.exec(|mut creator: UiCreator<'_>| creator.create("ui/loading.ron", ()) );
         
-        let map_type: Terrain = random();
-        let mut gen = MapGenerator::new(map_type);
-        gen.build_terrain();
-        gen.finish();
+        let options = world.read_resource::<LaunchOptions>().clone();
+        let map_type: Terrain = options.terrain.unwrap_or_else(random);
+        let seed: u64 = options.seed.unwrap_or_else(random);
+        info!("Generating {} map with seed {}", map_type, seed);
+        let weather = Weather::generate(&map_type, seed, options.weather, &world.read_resource::<WeatherConfig>());
+        let assets_dir = options.assets_dir.expect("[ERROR][raiders::state] Assets directory not set");
+        let cache_config = world.read_resource::<CacheConfig>().clone();
+        let max_error = world.read_resource::<TerrainConfig>().max_error;
+        let cache = MapCache::new(&assets_dir, cache_config.max_bytes());
+
+        let cached = if cache_config.reuse { cache.load(&map_type, seed, max_error) } else { None };
+        let map = match cached {
+            Some(map) => {
+                info!("Reusing cached map");
+                map
+            },
+            None => {
+                let mut gen = cache.generator(map_type, seed, max_error)
+                    .unwrap_or_else(|e| panic!("{}", e));
+                gen.build_terrain();
+                gen.build_props();
+                gen.build_extraction();
+                gen.finish();
+                if let Err(e) = cache.store(&gen) {
+                    warn!("{}", e);
+                }
 
-        let map_handle = world.exec(|loader: PrefabLoader<'_, ScenePrefabData>| {
-            loader.load(gen.map_path(), RonFormat, ())
+                CachedMap {
+                    mesh: gen.export_mesh().clone(),
+                    props: gen.props().to_vec(),
+                    prefab_path: String::from(gen.map_path()),
+                    minimap_path: MapCache::minimap_path(gen.map_type(), gen.seed()),
+                }
+            },
+        };
+
+        let mut physics = Physics::default();
+        physics.set_terrain(&map.mesh);
+        for prop in &map.props {
+            physics.add_prop(prop);
+        }
+        physics.update();
+
+        let max_slope = world.read_resource::<MovementConfig>().max_slope;
+        // Fog cells are a whole number of navigation cells across, and the
+        // navigation grid a whole number of fog cells, so both line up
+        let stride = (world.read_resource::<FogConfig>().spacing / NAV_SPACING).round().max(1.0) as usize;
+        if let Some((min, max)) = physics.terrain_bounds() {
+            let extent = (max.x - min.x).max(max.z - min.z);
+            let cells = (extent / NAV_SPACING).ceil() as usize;
+            let size = (cells + stride - 1) / stride * stride + 1;
+            let nav = NavMesh::sample(
+                size, (min.x, min.z), (max.x, max.z),
+                |x, z| physics.ground_height(x, z),
+                &map.props, max_slope,
+            );
+            world.insert(FogOfWar::from_nav(&nav, stride));
+            world.insert(nav);
+        }
+        world.insert(physics);
+
+        let minimap = world.exec(|(loader, textures): (ReadExpect<'_, Loader>, Read<'_, AssetStorage<Texture>>)| {
+            loader.load(map.minimap_path.as_str(), ImageFormat::default(), (), &textures)
+        });
+        world.insert(MinimapTexture(minimap));
+        spawn_squad(world, &assets_dir);
+
+        let mission = Mission::generate(&map.props, seed);
+        info!(
+            "Mission: {}",
+            mission.objectives().iter().map(|objective| objective.kind.description()).collect::<Vec<_>>().join(", "),
+        );
+        spawn_mission(world, &mission);
+        world.insert(mission);
+
+        let map_handle = world.exec(|loader: PrefabLoader<'_, MapPrefabData>| {
+            loader.load(map.prefab_path.as_str(), RonFormat, ())
         });
         
         let _map = world.create_entity()
@@ -134,6 +318,19 @@ impl SimpleState for LoadingState {
             .with(Transform::default())
             .build();
 
+        let time_config = world.read_resource::<TimeConfig>().clone();
+        let time_of_day = TimeOfDay::new(options.start_hour.unwrap_or(time_config.start_hour), time_config.time_scale);
+        let minutes = (time_of_day.hours() * 60.0) as u32;
+        info!("The raid starts at {:02}:{:02}", minutes / 60, minutes % 60);
+        world.insert(time_of_day);
+        info!("Weather: {}", weather.kind());
+        world.insert(weather);
+        // DayNightSystem points the sun and sets its colour
+        world.create_entity()
+            .with(Light::Directional(DirectionalLight::default()))
+            .with(Sun)
+            .build();
+
         self.finished = true;
     }
 
@@ -149,6 +346,9 @@ impl SimpleState for LoadingState {
             world.delete_entity(self.screen_loading.expect("[ERROR][raiders::state] Loading screen not found"))
                 .unwrap();
             self.screen_loading = None;
+
+            info!("[Trans::Switch] Switching to GameplayState");
+            return Trans::Switch(Box::new(GameplayState::default()));
         }
 
         Trans::None
@@ -170,11 +370,41 @@ impl SimpleState for LoadingState {
 }
 
 /* Gameplat State */
-pub struct GameplayState;
+#[derive(Default, Debug)]
+pub struct GameplayState {
+    hud: Option<Entity>,
+}
 
 impl SimpleState for GameplayState {
-    fn on_start(&mut self, _data: StateData<'_, GameData<'_, '_>>) {
+    fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        let StateData { world, .. } = data;
         println!("Game session started");
+
+        self.hud = Some(world.exec(|mut creator: UiCreator<'_>| creator.create("ui/hud.ron", ()) ));
+    }
+
+    fn on_stop(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        let StateData { world, .. } = data;
+
+        // Minimap markers are created at runtime, so the HUD's children go with it explicitly
+        if let Some(hud) = self.hud.take() {
+            let children: Vec<Entity> = world.read_resource::<ParentHierarchy>().all_children_iter(hud).collect();
+            world.delete_entities(&children).unwrap();
+            world.delete_entity(hud).unwrap();
+        }
+    }
+
+    fn update(&mut self, data: &mut StateData<'_, GameData<'_, '_>>) -> Trans<GameData<'static, 'static>, StateEvent> {
+        let StateData { world, .. } = data;
+
+        let mission = world.read_resource::<Mission>();
+        if mission.is_over() {
+            let score = mission.score(&world.read_resource::<MissionConfig>());
+            info!("[Trans::Switch] Switching to ResultState");
+            return Trans::Switch(Box::new(ResultState::new(score)));
+        }
+
+        Trans::None
     }
 
     fn handle_event(
@@ -216,11 +446,79 @@ impl SimpleState for PauseState {
 }
 
 /* Result State */
-pub struct ResultState;
+// Shows the score breakdown of the finished raid
+pub struct ResultState {
+    score: Score,
+    filled: bool,
+}
+
+impl ResultState {
+    pub fn new(score: Score) -> Self {
+        ResultState {
+            score: score,
+            filled: false,
+        }
+    }
+
+    fn lines(&self) -> Vec<(&'static str, String)> {
+        let score = &self.score;
+        let objectives = score.objectives.iter()
+            .map(|objective| format!(
+                "{} - {}",
+                objective.kind.description(),
+                match objective.status {
+                    ObjectiveStatus::Completed => "done",
+                    ObjectiveStatus::Failed => "failed",
+                    ObjectiveStatus::Active => "open",
+                },
+            ))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        vec![
+            (RESULT_TITLE, String::from(score.outcome.map_or("Raid over", |outcome| outcome.description()))),
+            (RESULT_OBJECTIVES, objectives),
+            (RESULT_BREAKDOWN, format!(
+                "Objectives {:+}\nTime {}:{:02} {:+}\nDetections {} {:+}\nCasualties {} {:+}\nLoot {:+}",
+                score.objective_points,
+                score.elapsed as u32 / 60, score.elapsed as u32 % 60, score.time_points,
+                score.detection_count, score.detection_points,
+                score.casualty_count, score.casualty_points,
+                score.loot_points,
+            )),
+            (RESULT_TOTAL, format!("SCORE {}", score.total)),
+        ]
+    }
+}
 
 impl SimpleState for ResultState {
-    fn on_start(&mut self, _data: StateData<'_, GameData<'_, '_>>) {
+    fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        let StateData { world, .. } = data;
         println!("Session has ended");
+
+        for (_, text) in self.lines() {
+            info!("{}", text);
+        }
+        world.exec(|mut creator: UiCreator<'_>| creator.create("ui/result.ron", ()) );
+    }
+
+    fn update(&mut self, data: &mut StateData<'_, GameData<'_, '_>>) -> Trans<GameData<'static, 'static>, StateEvent> {
+        let StateData { world, .. } = data;
+
+        // The labels only exist once the UI has loaded
+        if !self.filled {
+            let lines = self.lines();
+            world.exec(|(ui_finder, mut texts): (UiFinder<'_>, WriteStorage<'_, UiText>)| {
+                for (id, line) in lines {
+                    if let Some(text) = ui_finder.find(id).and_then(|label| texts.get_mut(label)) {
+                        text.text = line;
+                        self.filled = true;
+                    }
+                }
+            });
+        }
+
+        Trans::None
     }
 
     fn handle_event(
@@ -238,6 +536,131 @@ impl SimpleState for ResultState {
     }
 }
 
+// The player's squad, on the terrain behind the player and following
+// them. The drone starts on the ground and takes off by itself, while
+// the dog and the medic come from their unit definitions in units/
+fn spawn_squad(world: &mut World, assets_dir: &Path) {
+    let dog_unit = load_unit(assets_dir, "dog");
+    let medic_unit = load_unit(assets_dir, "medic");
+    let (soldier, drone, dog, medic) = world.exec(|loader: PrefabLoader<'_, ScenePrefabData>| {
+        (
+            loader.load("prefabs/soldier.ron", RonFormat, ()),
+            loader.load("prefabs/drone.ron", RonFormat, ()),
+            loader.load(dog_unit.prefab.as_str(), RonFormat, ()),
+            loader.load(medic_unit.prefab.as_str(), RonFormat, ()),
+        )
+    });
+    let battery = world.read_resource::<DroneConfig>().battery;
+    let bleed_time = world.read_resource::<MissionConfig>().bleed_time;
+
+    for (role, [dx, dz]) in SQUAD.iter() {
+        let (x, z) = (PLAYER_START[0] + dx, PLAYER_START[1] + dz);
+        let y = world.read_resource::<Physics>().ground_height(x, z).unwrap_or(0.0);
+        let mut transform = Transform::default();
+        let mut member = SquadMember::new(*role);
+        member.order = Order::Follow;
+
+        if *role == SquadRole::Drone {
+            transform.set_translation_xyz(x, y, z);
+            world.create_entity()
+                .with(drone.clone())
+                .with(transform)
+                .with(member)
+                .with(Drone::new(battery))
+                .with(Health::new(DRONE_HEALTH))
+                .build();
+        } else if *role == SquadRole::Dog || *role == SquadRole::Medic {
+            let (unit, prefab) = if *role == SquadRole::Dog { (&dog_unit, &dog) } else { (&medic_unit, &medic) };
+            transform.set_translation_xyz(x, y + unit.radius + unit.half_height, z);
+            member.speed = Some(unit.speed);
+            let mut builder = world.create_entity()
+                .with(prefab.clone())
+                .with(transform)
+                .with(member)
+                .with(Health { bleed_time: bleed_time, ..Health::new(unit.health) })
+                .with(Collider { radius: unit.radius, half_height: unit.half_height, offset: 0.0 });
+            if *role == SquadRole::Dog {
+                builder = builder.with(Dog::new(unit.dog.unwrap_or_default()));
+            } else {
+                builder = builder.with(Medic::new(unit.medic.unwrap_or_default()));
+            }
+            builder.build();
+        } else {
+            transform.set_translation_xyz(x, y + UNIT_CENTRE_HEIGHT, z);
+            world.create_entity()
+                .with(soldier.clone())
+                .with(transform)
+                .with(member)
+                .with(Health { bleed_time: bleed_time, ..Health::new(UNIT_HEALTH) })
+                .with(Collider { radius: 0.3, half_height: 0.6, offset: 0.0 })
+                .build();
+        }
+    }
+}
+
+// Unit definition from units/<unit>.ron, or the defaults if it cannot be read
+fn load_unit(assets_dir: &Path, unit: &str) -> UnitDefinition {
+    UnitDefinition::load(assets_dir.join(format!("units/{}.ron", unit))).unwrap_or_else(|e| {
+        warn!("Cannot load the {} unit, using defaults: {}", unit, e);
+        UnitDefinition::default()
+    })
+}
+
+// Guards, the target and the loot of a freshly generated mission, standing on the terrain
+fn spawn_mission(world: &mut World, mission: &Mission) {
+    let (guard, target, loot) = world.exec(|loader: PrefabLoader<'_, ScenePrefabData>| {
+        (
+            loader.load("prefabs/guard.ron", RonFormat, ()),
+            loader.load("prefabs/target.ron", RonFormat, ()),
+            loader.load("prefabs/loot.ron", RonFormat, ()),
+        )
+    });
+    let ground = |world: &World, [x, y, z]: [f32; 3]| {
+        let y = world.read_resource::<Physics>().ground_height(x, z).unwrap_or(y);
+        let mut transform = Transform::default();
+        transform.set_translation_xyz(x, y, z);
+        transform
+    };
+    let layout = mission.layout().clone();
+
+    for post in &layout.guards {
+        let mut transform = ground(world, post.position);
+        let feet = *transform.translation();
+        transform.prepend_translation_y(UNIT_CENTRE_HEIGHT);
+        // Posts face away from their building
+        transform.set_rotation_y_axis(post.yaw + PI);
+        world.create_entity()
+            .with(guard.clone())
+            .with(transform)
+            .with(Guard::new([feet.x, feet.y, feet.z], post.yaw + PI))
+            .with(Health::new(UNIT_HEALTH))
+            .with(Collider { radius: 0.3, half_height: 0.6, offset: 0.0 })
+            .build();
+    }
+
+    if let Some(position) = layout.target {
+        let mut transform = ground(world, position);
+        transform.prepend_translation_y(UNIT_CENTRE_HEIGHT);
+        world.create_entity()
+            .with(target)
+            .with(transform)
+            .with(MissionTarget)
+            .with(Health::new(UNIT_HEALTH))
+            .with(Collider { radius: 0.3, half_height: 0.6, offset: 0.0 })
+            .build();
+    }
+
+    for spot in &layout.loot {
+        let mut transform = ground(world, spot.position);
+        transform.prepend_translation_y(0.2);
+        world.create_entity()
+            .with(loot.clone())
+            .with(transform)
+            .with(Loot { value: spot.value })
+            .build();
+    }
+}
+
 /* Resource wrappers */
 #[derive(Default)]
 pub struct PlayerEntity(Index);
@@ -245,3 +668,15 @@ pub struct PlayerEntity(Index);
 impl PlayerEntity {
     pub fn index(&self) -> Index { return self.0 }
 }
+
+pub struct ArrowPrefab(Handle<Prefab<ScenePrefabData>>);
+
+impl ArrowPrefab {
+    pub fn handle(&self) -> Handle<Prefab<ScenePrefabData>> { self.0.clone() }
+}
+
+pub struct MinimapTexture(Handle<Texture>);
+
+impl MinimapTexture {
+    pub fn handle(&self) -> Handle<Texture> { self.0.clone() }
+}
@@ -0,0 +1,435 @@
+use crate::{
+    component::{CharacterController, Collider, Drone, Health},
+    config::{CameraConfig, MovementConfig},
+    intent::PlayerIntent,
+    physics::{self, Physics, GROUP_TERRAIN},
+    state::PlayerEntity,
+    system::{hands::world_position, route::{self, centre_height}},
+};
+use amethyst::{
+    ecs::*,
+    core::{
+        math::{UnitQuaternion, Vector3},
+        timing::Time,
+        transform::components::Transform,
+    },
+    derive::SystemDesc,
+    window::ScreenDimensions,
+};
+
+// Degrees the drone's camera looks down from the horizon
+const DRONE_CAMERA_PITCH: f32 = 30.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CameraMode {
+    FirstPerson,
+    Commander,
+    // Looking through the camera of the squad's drone, which the player flies
+    Drone,
+}
+
+impl Default for CameraMode {
+    fn default() -> Self { CameraMode::FirstPerson }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Pose {
+    translation: Vector3<f32>,
+    rotation: UnitQuaternion<f32>,
+}
+
+impl Pose {
+    fn of(transform: &Transform) -> Self {
+        Pose {
+            translation: *transform.translation(),
+            rotation: *transform.rotation(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Transition {
+    from: Pose,
+    elapsed: f32,
+}
+
+/* Camera state resource */
+// The player's camera is either the first-person view, the commander's
+// orbit view or a drone's camera. Switching between them, or possessing
+// a unit, blends the camera from its old pose to the new one. While the
+// player possesses a squad member, the first-person view walks that
+// unit's body and the player's own body waits where they left it
+#[derive(Default)]
+pub struct CameraState {
+    mode: CameraMode,
+    focus: Vector3<f32>,
+    yaw: f32,
+    distance: f32,
+    first_person: Option<Pose>,
+    transition: Option<Transition>,
+    possess: Option<Entity>,
+    release: bool,
+    possessed: Option<Entity>,
+    body: Option<Pose>,
+    drone: Option<Entity>,
+    toggle_held: bool,
+    drone_held: bool,
+}
+
+impl CameraState {
+    pub fn mode(&self) -> CameraMode { self.mode }
+    pub fn focus(&self) -> Vector3<f32> { self.focus }
+
+    // Whether the first-person controller may move the player this frame
+    pub fn controls_player(&self) -> bool {
+        self.mode == CameraMode::FirstPerson && self.transition.is_none()
+    }
+
+    // Where the player's body was left while the camera is away from it
+    pub fn body_position(&self) -> Option<Vector3<f32>> {
+        match self.body {
+            Some(body) => Some(body.translation),
+            None if self.controls_player() => None,
+            None => self.first_person.map(|pose| pose.translation),
+        }
+    }
+
+    // The squad member the player walks around as instead of their own body
+    pub fn possessed(&self) -> Option<Entity> { self.possessed }
+
+    // The drone the player flies, once the view has reached it
+    pub fn drone(&self) -> Option<Entity> {
+        if self.mode == CameraMode::Drone && self.transition.is_none() {
+            self.drone
+        } else {
+            None
+        }
+    }
+
+    // Leave the commander view and take over `unit` in first-person
+    pub fn possess(&mut self, unit: Entity) {
+        self.possess = Some(unit);
+    }
+
+    // Give the possessed unit back to its orders and return to the player's own body
+    pub fn release(&mut self) {
+        self.release = true;
+    }
+}
+
+/* RTS camera system */
+// Edge-scrolling, zoom and rotation for the commander view, following
+// the drone's camera, plus the transitions between the views. The drone
+// view ends when asked to or when the drone can no longer fly, and
+// possession when released or when the unit goes down. A possessed unit
+// follows the first-person view the controller walks around
+#[derive(SystemDesc)]
+pub struct CameraSystem;
+
+impl<'s> System<'s> for CameraSystem {
+    type SystemData = (
+        Write<'s, CameraState>,
+        WriteStorage<'s, Transform>,
+        ReadStorage<'s, Drone>,
+        ReadStorage<'s, Health>,
+        ReadStorage<'s, CharacterController>,
+        ReadStorage<'s, Collider>,
+        Read<'s, PlayerEntity>,
+        Read<'s, PlayerIntent>,
+        Read<'s, Time>,
+        Read<'s, CameraConfig>,
+        Read<'s, MovementConfig>,
+        Read<'s, Physics>,
+        ReadExpect<'s, ScreenDimensions>,
+        Entities<'s>,
+        );
+
+    fn run(&mut self, (
+        mut state, mut transforms, drones, healths, controllers, colliders, player_entity, intent,
+        time, config, movement, physics, screen, entities,
+        ): Self::SystemData) {
+        let player = entities.entity(player_entity.index());
+        let current = match transforms.get(player) {
+            Some(transform) => Pose::of(transform),
+            None => return,
+        };
+        let dt = time.delta_seconds();
+
+        let pressed = intent.toggle_camera && !state.toggle_held;
+        state.toggle_held = intent.toggle_camera;
+        let drone_pressed = intent.toggle_drone && !state.drone_held;
+        state.drone_held = intent.toggle_drone;
+
+        let flyable = |drone: Entity| {
+            drones.get(drone).map_or(false, |drone| !drone.is_empty())
+                && !healths.get(drone).map_or(false, Health::is_down)
+        };
+        let grounded = state.drone.map_or(false, |drone| !flyable(drone));
+        let lost = state.possessed.map_or(false, |unit| {
+            !entities.is_alive(unit) || healths.get(unit).map_or(false, Health::is_down)
+        });
+        let release = std::mem::replace(&mut state.release, false) || lost;
+
+        if let Some(unit) = state.possess.take() {
+            if let Some(transform) = transforms.get(unit) {
+                let mut pose = Pose::of(transform);
+                // The unit's transform sits at its centre, the player's camera at eye height above the feet
+                pose.translation = route::ground(&physics, world_position(transform)) + Vector3::y() * movement.eye_height;
+                pose.rotation = UnitQuaternion::from_axis_angle(&Vector3::y_axis(), yaw_of(&pose.rotation));
+                // Taking over another unit leaves the player's own body where it already waits
+                if state.body.is_none() {
+                    state.body = match state.mode {
+                        CameraMode::FirstPerson => Some(current),
+                        _ => state.first_person.or(Some(current)),
+                    };
+                }
+                state.possessed = Some(unit);
+                state.first_person = Some(pose);
+                state.mode = CameraMode::FirstPerson;
+                state.drone = None;
+                state.transition = Some(Transition { from: current, elapsed: 0.0 });
+            }
+        } else if release && state.possessed.is_some() {
+            // Back to the player's body
+            state.possessed = None;
+            state.first_person = state.body.take();
+            state.mode = CameraMode::FirstPerson;
+            state.drone = None;
+            state.transition = Some(Transition { from: current, elapsed: 0.0 });
+        } else if state.mode == CameraMode::Drone && state.transition.is_none() && (pressed || drone_pressed || grounded) {
+            // Back to the player's body
+            state.mode = CameraMode::FirstPerson;
+            state.drone = None;
+            state.transition = Some(Transition { from: current, elapsed: 0.0 });
+        } else if drone_pressed && state.transition.is_none() {
+            let drone = (&entities, &drones).join()
+                .map(|(entity, _)| entity)
+                .find(|&entity| flyable(entity));
+            if let Some(drone) = drone {
+                if state.mode == CameraMode::FirstPerson {
+                    state.first_person = Some(current);
+                }
+                state.drone = Some(drone);
+                state.mode = CameraMode::Drone;
+                state.transition = Some(Transition { from: current, elapsed: 0.0 });
+            }
+        } else if pressed && state.transition.is_none() {
+            match state.mode {
+                CameraMode::FirstPerson => {
+                    state.first_person = Some(current);
+                    state.focus = current.translation;
+                    state.yaw = yaw_of(&current.rotation);
+                    if state.distance <= 0.0 {
+                        state.distance = (config.min_distance + config.max_distance) * 0.5;
+                    }
+                    state.mode = CameraMode::Commander;
+                },
+                CameraMode::Commander | CameraMode::Drone => state.mode = CameraMode::FirstPerson,
+            }
+            state.transition = Some(Transition { from: current, elapsed: 0.0 });
+        }
+
+        if state.mode == CameraMode::Commander && state.transition.is_none() {
+            // Pan with the screen edges or the movement keys, relative to the view's heading
+            let mut pan = Vector3::zeros();
+            if let Some((x, y)) = intent.pointer {
+                if x < config.edge_margin { pan.x -= 1.0; }
+                if x > screen.width() - config.edge_margin { pan.x += 1.0; }
+                if y < config.edge_margin { pan.z -= 1.0; }
+                if y > screen.height() - config.edge_margin { pan.z += 1.0; }
+            }
+            pan.x += intent.locomotion.x;
+            pan.z -= intent.locomotion.y;
+            if pan.norm() > 1.0 {
+                pan = pan.normalize();
+            }
+            let heading = UnitQuaternion::from_axis_angle(&Vector3::y_axis(), state.yaw);
+            let distance = state.distance;
+            state.focus += heading * pan * config.scroll_speed * distance * dt;
+
+            state.distance = (state.distance - intent.zoom * config.zoom_speed)
+                .max(config.min_distance)
+                .min(config.max_distance);
+
+            state.yaw += intent.rotate * config.rotate_speed.to_radians() * dt;
+
+            // Keep the focus on the map and resting on the ground
+            if let Some((min, max)) = physics.terrain_bounds() {
+                state.focus.x = state.focus.x.max(min.x).min(max.x);
+                state.focus.z = state.focus.z.max(min.z).min(max.z);
+
+                let above = physics::point(&Vector3::new(state.focus.x, max.y + 1.0, state.focus.z));
+                let ground = physics.cast_ray(above, -physics::up(), max.y - min.y + 2.0, &physics::filter(&[GROUP_TERRAIN]));
+                if let Some(hit) = ground {
+                    state.focus.y = hit.point.y;
+                }
+            }
+        }
+
+        let target = match state.mode {
+            CameraMode::Commander => orbit(&state, config.pitch.to_radians()),
+            CameraMode::Drone => match state.drone.and_then(|drone| transforms.get(drone)) {
+                Some(transform) => drone_view(transform),
+                None => return,
+            },
+            CameraMode::FirstPerson => match state.first_person {
+                Some(pose) => pose,
+                None => return,
+            },
+        };
+
+        let transition = state.transition;
+        let pose = match transition {
+            Some(mut transition) => {
+                transition.elapsed += dt;
+                let t = (transition.elapsed / config.transition_time).min(1.0);
+                let s = t * t * (3.0 - 2.0 * t);
+                state.transition = if t < 1.0 { Some(transition) } else { None };
+
+                Pose {
+                    translation: transition.from.translation.lerp(&target.translation, s),
+                    rotation: transition.from.rotation.slerp(&target.rotation, s),
+                }
+            },
+            // First-person is driven by ControllerSystem once the transition is over,
+            // and the possessed unit's body goes where the controller walked
+            None if state.mode == CameraMode::FirstPerson => {
+                if let Some(unit) = state.possessed {
+                    let eye_height = controllers.get(player)
+                        .map(|controller| controller.eye_height)
+                        .filter(|height| *height > 0.0)
+                        .unwrap_or(movement.eye_height);
+                    let feet = current.translation - Vector3::y() * eye_height;
+                    if let Some(transform) = transforms.get_mut(unit) {
+                        transform.set_translation(feet + Vector3::y() * centre_height(colliders.get(unit)));
+                        transform.set_rotation_y_axis(yaw_of(&current.rotation));
+                    }
+                }
+                return;
+            },
+            None => target,
+        };
+
+        if let Some(transform) = transforms.get_mut(player) {
+            transform.set_translation(pose.translation);
+            transform.set_rotation(pose.rotation);
+        }
+    }
+}
+
+// Camera pose looking down at the focus from `pitch` above the horizon
+fn orbit(state: &CameraState, pitch: f32) -> Pose {
+    let rotation = UnitQuaternion::from_axis_angle(&Vector3::y_axis(), state.yaw)
+        * UnitQuaternion::from_axis_angle(&Vector3::x_axis(), -pitch);
+
+    Pose {
+        translation: state.focus + rotation * Vector3::z() * state.distance,
+        rotation: rotation,
+    }
+}
+
+// Camera pose of a drone: at the drone, facing its heading and tilted down
+fn drone_view(transform: &Transform) -> Pose {
+    let rotation = UnitQuaternion::from_axis_angle(&Vector3::y_axis(), yaw_of(transform.rotation()))
+        * UnitQuaternion::from_axis_angle(&Vector3::x_axis(), -DRONE_CAMERA_PITCH.to_radians());
+
+    Pose {
+        translation: *transform.translation(),
+        rotation: rotation,
+    }
+}
+
+// Heading around the y axis of a camera looking down -z
+fn yaw_of(rotation: &UnitQuaternion<f32>) -> f32 {
+    let forward = rotation * -Vector3::z();
+    (-forward.x).atan2(-forward.z)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::UNIT_CENTRE_HEIGHT;
+    use amethyst::ecs::RunNow;
+
+    // The player standing at the origin and a squad member ten metres away, with a
+    // whole second per frame so every transition ends within one
+    fn squad() -> (World, Entity, Entity) {
+        let mut world = World::new();
+        <CameraSystem as System<'_>>::SystemData::setup(&mut world);
+        world.insert(ScreenDimensions::new(800, 600, 1.0));
+        let mut time = Time::default();
+        time.set_delta_seconds(1.0);
+        world.insert(time);
+
+        let mut transform = Transform::default();
+        transform.set_translation_xyz(0.0, 1.6, 0.0);
+        let player = world.create_entity()
+            .with(transform)
+            .with(CharacterController { eye_height: 1.6, ..CharacterController::default() })
+            .build();
+        let mut transform = Transform::default();
+        transform.set_translation_xyz(10.0, 0.9, 0.0);
+        let unit = world.create_entity().with(transform).with(Health::new(100.0)).build();
+        (world, player, unit)
+    }
+
+    fn translation(world: &World, entity: Entity) -> Vector3<f32> {
+        *world.read_storage::<Transform>().get(entity).unwrap().translation()
+    }
+
+    fn assert_near(a: Vector3<f32>, b: Vector3<f32>) {
+        assert!((a - b).norm() < 1e-4, "{:?} is not {:?}", a, b);
+    }
+
+    #[test]
+    fn possessing_walks_the_unit_and_leaves_the_body_behind() {
+        let (world, player, unit) = squad();
+        world.write_resource::<CameraState>().possess(unit);
+        CameraSystem.run_now(&world);
+        {
+            let state = world.read_resource::<CameraState>();
+            assert_eq!(state.possessed(), Some(unit));
+            assert!(state.controls_player());
+            assert_eq!(state.body_position(), Some(Vector3::new(0.0, 1.6, 0.0)));
+        }
+        let eyes = translation(&world, player);
+        assert!((eyes.x - 10.0).abs() < 1e-4 && eyes.z.abs() < 1e-4);
+
+        // What ControllerSystem does when the player walks
+        world.write_storage::<Transform>().get_mut(player).unwrap().set_translation_xyz(12.0, 2.5, -3.0);
+        CameraSystem.run_now(&world);
+        assert_near(translation(&world, unit), Vector3::new(12.0, 0.9 + UNIT_CENTRE_HEIGHT, -3.0));
+        assert_eq!(world.read_resource::<CameraState>().body_position(), Some(Vector3::new(0.0, 1.6, 0.0)));
+    }
+
+    #[test]
+    fn releasing_returns_to_the_body() {
+        let (world, player, unit) = squad();
+        world.write_resource::<CameraState>().possess(unit);
+        CameraSystem.run_now(&world);
+        world.write_storage::<Transform>().get_mut(player).unwrap().set_translation_xyz(12.0, 2.5, -3.0);
+        CameraSystem.run_now(&world);
+
+        world.write_resource::<CameraState>().release();
+        CameraSystem.run_now(&world);
+        let state = world.read_resource::<CameraState>();
+        assert_eq!(state.possessed(), None);
+        assert_eq!(state.body_position(), None);
+        assert!(state.controls_player());
+        assert_near(translation(&world, player), Vector3::new(0.0, 1.6, 0.0));
+        // The unit stays where the player left it
+        assert_near(translation(&world, unit), Vector3::new(12.0, 0.9 + UNIT_CENTRE_HEIGHT, -3.0));
+    }
+
+    #[test]
+    fn a_downed_unit_hands_the_body_back() {
+        let (world, player, unit) = squad();
+        world.write_resource::<CameraState>().possess(unit);
+        CameraSystem.run_now(&world);
+
+        world.write_storage::<Health>().get_mut(unit).unwrap().damage(100.0);
+        CameraSystem.run_now(&world);
+        assert_eq!(world.read_resource::<CameraState>().possessed(), None);
+        assert_near(translation(&world, player), Vector3::new(0.0, 1.6, 0.0));
+    }
+}
@@ -1,47 +1,155 @@
-use crate::state::{
-    PlayerEntity,
+use crate::{
+    component::CharacterController,
+    config::{LocomotionConfig, MoveMode, MovementConfig},
+    intent::PlayerIntent,
+    physics::{self, Physics, GROUP_PROPS, GROUP_TERRAIN},
+    state::PlayerEntity,
+    system::camera::CameraState,
 };
 use amethyst::{
     ecs::*,
     core::{
+        math::Vector3,
+        timing::Time,
         transform::components::Transform,
     },
     derive::SystemDesc,
-    input::{InputHandler, StringBindings},
 };
-/* Keyboard controller system */
-// This is for controlling the camera with WASD keys
-// during testing
+
+// Radius of the sphere swept ahead of the player to stop them walking into props
+const BODY_RADIUS: f32 = 0.3;
+// How far below the feet the ground is still considered underfoot when walking downhill
+const GROUND_SNAP: f32 = 0.3;
+// Metres per second the eyes move when crouching or standing up
+const CROUCH_RATE: f32 = 3.0;
+
+/* Player controller system */
+// Walks the player over the terrain with gravity, step-ups and
+// a maximum slope; mouse look is applied directly to the camera
 #[derive(SystemDesc)]
 pub struct ControllerSystem;
 
 impl<'s> System<'s> for ControllerSystem {
     type SystemData = (
         WriteStorage<'s, Transform>,
+        WriteStorage<'s, CharacterController>,
         Read<'s, PlayerEntity>,
-        Read<'s, InputHandler<StringBindings>>,
+        Read<'s, PlayerIntent>,
+        Read<'s, Time>,
+        Read<'s, MovementConfig>,
+        Read<'s, LocomotionConfig>,
+        Read<'s, Physics>,
+        Read<'s, CameraState>,
         Entities<'s>,
         );
 
-    fn run (&mut self, (mut transforms, player_entity, input, entities): Self::SystemData) {
+    fn run (&mut self, (mut transforms, mut controllers, player_entity, intent, time, config, locomotion, physics, camera, entities): Self::SystemData) {
+        if !camera.controls_player() {
+            return;
+        }
+
         let player = entities.entity(player_entity.index());
-        if let Some(transform) = transforms.get_mut(player) {
-            if let Some(move_side) = input.axis_value("left_right") {
-                let scaled_amount = -0.2 * move_side as f32;
-                transform.append_translation_xyz(scaled_amount, 0.0, 0.0);
-            }
-            if let Some(move_forward) = input.axis_value("forward_back") {
-                let scaled_amount = -0.2 * move_forward as f32;
-                transform.append_translation_xyz(0.0, 0.0, scaled_amount);
-            }
-            if let Some(mouse_x) = input.axis_value("mouse_x") {
-                let scaled_amount = 0.2 * mouse_x as f32;
-                transform.append_rotation_y_axis(scaled_amount);
-            }
-            if let Some(mouse_y) = input.axis_value("mouse_y") {
-                let scaled_amount = 0.2 * mouse_y as f32;
-                transform.append_rotation_x_axis(scaled_amount);
+        let (transform, controller) = match (transforms.get_mut(player), controllers.get_mut(player)) {
+            (Some(transform), Some(controller)) => (transform, controller),
+            _ => return,
+        };
+        let dt = time.delta_seconds();
+
+        transform.append_rotation_y_axis(intent.look.x);
+        transform.append_rotation_x_axis(intent.look.y);
+
+        // Movement happens in the horizontal plane regardless of where the head is looking
+        let heading = transform.rotation() * intent.head.orientation;
+        let forward = flatten(heading * -Vector3::z());
+        let right = flatten(heading * Vector3::x());
+        // With teleport locomotion the player only falls and follows the ground
+        let wish = match locomotion.movement {
+            MoveMode::Smooth => forward * intent.locomotion.y + right * intent.locomotion.x,
+            MoveMode::Teleport => Vector3::zeros(),
+        };
+
+        controller.crouching = intent.crouch;
+        let (speed, eye_height) = if controller.crouching {
+            (config.crouch_speed, config.crouch_eye_height)
+        } else if intent.run {
+            (config.run_speed, config.eye_height)
+        } else {
+            (config.walk_speed, config.eye_height)
+        };
+        let step = wish * speed * dt;
+
+        // Feet stay put while the eyes move towards the crouching or standing height
+        if controller.eye_height <= 0.0 {
+            controller.eye_height = eye_height;
+        }
+        let feet_offset = controller.eye_height;
+        let max_change = CROUCH_RATE * dt;
+        controller.eye_height += (eye_height - controller.eye_height).max(-max_change).min(max_change);
+
+        // Before a map is loaded there is nothing to stand on
+        if !physics.has_terrain() {
+            transform.prepend_translation(step);
+            return;
+        }
+
+        let eye = *transform.translation();
+        let feet = eye - Vector3::y() * feet_offset;
+        let solid = physics::filter(&[GROUP_TERRAIN, GROUP_PROPS]);
+
+        // Horizontal move, cancelled by props in the way, steps that are too high and steep slopes
+        let mut target = feet + step;
+        if step.norm() > 0.0 {
+            let body = physics::point(&(feet + Vector3::y() * (config.step_height + BODY_RADIUS)));
+            let blocked_by_prop = physics
+                .sweep_sphere(body, physics::vector(&step), BODY_RADIUS, &physics::filter(&[GROUP_PROPS]))
+                .is_some();
+
+            let probe = physics::point(&(target + Vector3::y() * config.step_height));
+            let ground = physics.cast_ray(probe, -physics::up(), config.step_height * 2.0 + GROUND_SNAP, &solid);
+            let too_steep = match ground {
+                Some(hit) => hit.point.y > feet.y
+                    && hit.normal.y < config.max_slope.to_radians().cos(),
+                None => false,
+            };
+
+            if blocked_by_prop || too_steep {
+                target = feet;
             }
         }
+
+        // Vertical move: snap to the ground while grounded, otherwise fall
+        let probe = physics::point(&(target + Vector3::y() * config.step_height));
+        let snap = if controller.grounded { GROUND_SNAP } else { 0.0 };
+        let ground = physics.cast_ray(probe, -physics::up(), config.step_height + snap, &solid);
+        match ground {
+            Some(hit) if controller.vertical_speed <= 0.0 => {
+                target.y = hit.point.y;
+                controller.vertical_speed = 0.0;
+                controller.grounded = true;
+            },
+            _ => {
+                controller.vertical_speed -= config.gravity * dt;
+                let fall = controller.vertical_speed * dt;
+                let landing = physics.cast_ray(probe, -physics::up(), config.step_height - fall, &solid);
+                match landing {
+                    Some(hit) => {
+                        target.y = hit.point.y;
+                        controller.vertical_speed = 0.0;
+                        controller.grounded = true;
+                    },
+                    None => {
+                        target.y += fall;
+                        controller.grounded = false;
+                    },
+                }
+            },
+        }
+
+        transform.set_translation(target + Vector3::y() * controller.eye_height);
     }
 }
+
+fn flatten(v: Vector3<f32>) -> Vector3<f32> {
+    let flat = Vector3::new(v.x, 0.0, v.z);
+    if flat.norm() > 0.0 { flat.normalize() } else { flat }
+}
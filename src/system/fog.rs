@@ -0,0 +1,114 @@
+use crate::{
+    component::{Dog, Drone, Guard, Health, MissionTarget, SquadMember},
+    config::{DroneConfig, FogConfig},
+    fog::FogOfWar,
+    sky::weather::Weather,
+    state::PlayerEntity,
+    system::{camera::{CameraMode, CameraState}, hands::world_position},
+};
+use amethyst::{
+    core::{
+        math::Vector3,
+        timing::Time,
+        transform::components::Transform,
+        HiddenPropagate,
+    },
+    ecs::*,
+};
+
+/* Fog system */
+// Shares what the squad sees. Squad members on foot see around them as far
+// as the terrain lets them, flying drones see further from above and dogs
+// give away the guards they smell. Fog and rain shorten how far they all
+// see. Guards in sight become known to the squad. From the commander
+// view, enemies out of sight are not drawn
+#[derive(Default)]
+pub struct FogSystem {
+    // Seconds since the fog was last updated
+    elapsed: f32,
+}
+
+impl<'s> System<'s> for FogSystem {
+    type SystemData = (
+        WriteStorage<'s, Guard>,
+        WriteStorage<'s, HiddenPropagate>,
+        ReadStorage<'s, SquadMember>,
+        ReadStorage<'s, MissionTarget>,
+        ReadStorage<'s, Transform>,
+        ReadStorage<'s, Health>,
+        ReadStorage<'s, Drone>,
+        ReadStorage<'s, Dog>,
+        Option<Write<'s, FogOfWar>>,
+        Option<Read<'s, Weather>>,
+        Read<'s, FogConfig>,
+        Read<'s, DroneConfig>,
+        Read<'s, PlayerEntity>,
+        Read<'s, CameraState>,
+        Read<'s, Time>,
+        Entities<'s>,
+        );
+
+    fn run(&mut self, (mut guards, mut hidden, squad, targets, transforms, healths, drones, dogs, fog, weather, config, drone_config, player, camera, time, entities): Self::SystemData) {
+        let mut fog = match fog {
+            Some(fog) => fog,
+            None => return,
+        };
+
+        self.elapsed += time.delta_seconds();
+        if self.elapsed >= config.update_interval {
+            self.elapsed = 0.0;
+            fog.clear_visible();
+            let sight = weather.map_or(1.0, |weather| weather.sight());
+
+            for (entity, _, transform, health) in (&entities, &squad, &transforms, healths.maybe()).join() {
+                if health.map_or(false, Health::is_down) {
+                    continue;
+                }
+                let position = match camera.body_position() {
+                    Some(body) if entity.id() == player.index() => body,
+                    _ => world_position(transform),
+                };
+                let ground = fog.ground_height(position.x, position.z);
+                match drones.get(entity) {
+                    Some(drone) if drone.is_empty() => {},
+                    Some(_) => fog.reveal([position.x, position.y.max(ground + config.eye_height), position.z], drone_config.reveal_radius * sight),
+                    None => fog.reveal([position.x, ground + config.eye_height, position.z], config.view_radius * sight),
+                }
+            }
+
+            let smelled: Vec<Entity> = (&dogs, healths.maybe()).join()
+                .filter(|(_, health)| !health.map_or(false, Health::is_down))
+                .filter_map(|(dog, _)| dog.scent)
+                .collect();
+            for guard in smelled {
+                let position = transforms.get(guard).map(world_position);
+                if let Some(cell) = position.and_then(|position| fog.cell_at(position.x, position.z)) {
+                    fog.reveal_cell(cell);
+                }
+            }
+
+            for (guard, transform) in (&mut guards, &transforms).join() {
+                let position = world_position(transform);
+                if !guard.known && fog.is_visible(position.x, position.z) {
+                    guard.known = true;
+                }
+            }
+        }
+
+        // Enemies stay on screen in the other views, which show what the player's eyes see
+        let commander = camera.mode() == CameraMode::Commander;
+        let enemies: Vec<(Entity, Vector3<f32>)> = (&entities, &guards, &transforms).join()
+            .map(|(entity, _, transform)| (entity, world_position(transform)))
+            .chain((&entities, &targets, &transforms).join().map(|(entity, _, transform)| (entity, world_position(transform))))
+            .collect();
+        for (entity, position) in enemies {
+            if commander && !fog.is_visible(position.x, position.z) {
+                if !hidden.contains(entity) {
+                    let _ = hidden.insert(entity, HiddenPropagate::default());
+                }
+            } else {
+                hidden.remove(entity);
+            }
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+use crate::{
+    component::{Dog, Drone, Health, SquadMember},
+    config::MovementConfig,
+    sky::weather::Weather,
+    state::PlayerEntity,
+    system::{
+        camera::CameraState,
+        guard::NoiseEvent,
+        hands::world_position,
+        route::distance_xz,
+    },
+};
+use amethyst::{
+    core::{
+        math::Vector3,
+        timing::Time,
+        transform::components::Transform,
+    },
+    ecs::*,
+    shrev::EventChannel,
+};
+
+// Metres walked between two footsteps guards can hear
+const STRIDE: f32 = 1.5;
+
+/* Footstep system */
+// Squad members on foot make a noise every few steps, carrying further
+// the faster they go. Crouching is silent, and rain and wind drown out
+// some of the noise. Drones fly and dogs pad along without a sound
+#[derive(Default)]
+pub struct FootstepSystem {
+    // Where each unit stood last frame, and how far it has walked since its last footstep
+    steps: HashMap<Entity, (Vector3<f32>, f32)>,
+}
+
+impl<'s> System<'s> for FootstepSystem {
+    type SystemData = (
+        ReadStorage<'s, SquadMember>,
+        ReadStorage<'s, Transform>,
+        ReadStorage<'s, Health>,
+        ReadStorage<'s, Drone>,
+        ReadStorage<'s, Dog>,
+        Write<'s, EventChannel<NoiseEvent>>,
+        Option<Read<'s, Weather>>,
+        Read<'s, MovementConfig>,
+        Read<'s, PlayerEntity>,
+        Read<'s, CameraState>,
+        Read<'s, Time>,
+        Entities<'s>,
+        );
+
+    fn run(&mut self, (squad, transforms, healths, drones, dogs, mut noises, weather, config, player, camera, time, entities): Self::SystemData) {
+        let dt = time.delta_seconds();
+        if dt <= 0.0 {
+            return;
+        }
+        let hearing = weather.map_or(1.0, |weather| weather.hearing());
+        self.steps.retain(|entity, _| entities.is_alive(*entity));
+
+        for (entity, _, transform, health) in (&entities, &squad, &transforms, healths.maybe()).join() {
+            if drones.contains(entity) || dogs.contains(entity) || health.map_or(false, Health::is_down) {
+                self.steps.remove(&entity);
+                continue;
+            }
+            let position = match camera.body_position() {
+                Some(body) if entity.id() == player.index() => body,
+                _ => world_position(transform),
+            };
+
+            let (last, walked) = self.steps.entry(entity).or_insert((position, 0.0));
+            let moved = distance_xz(last, &position);
+            *last = position;
+            let speed = moved / dt;
+            // Teleporting makes no sound
+            if speed > config.run_speed * 2.0 {
+                continue;
+            }
+            *walked += moved;
+            if *walked < STRIDE {
+                continue;
+            }
+            *walked = 0.0;
+
+            let radius = if speed > (config.walk_speed + config.run_speed) * 0.5 {
+                config.run_noise
+            } else if speed > (config.crouch_speed + config.walk_speed) * 0.5 {
+                config.walk_noise
+            } else {
+                0.0
+            };
+            if radius > 0.0 {
+                noises.single_write(NoiseEvent { position: position, radius: radius * hearing });
+            }
+        }
+    }
+}
@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+use crate::{
+    component::{Collider, Drone, Guard, Health, SquadMember},
+    config::GuardConfig,
+    gen::navmesh::NavMesh,
+    physics::{self, Physics, GROUP_PROPS, GROUP_TERRAIN},
+    state::PlayerEntity,
+    system::{
+        camera::CameraState,
+        hands::world_position,
+        route::{self, centre_height, distance_xz, Route},
+    },
+};
+use amethyst::{
+    ecs::*,
+    core::{
+        math::Vector3,
+        timing::Time,
+        transform::components::Transform,
+    },
+    shrev::{EventChannel, ReaderId},
+};
+use log::info;
+
+// Height of a guard's eyes above its transform, which sits at the body's centre
+pub const GUARD_EYE_HEIGHT: f32 = 0.7;
+// Guards search a noise once this close to where it came from
+const SEARCH_DISTANCE: f32 = 1.5;
+// Guards count as back at their post within this distance of it
+const POST_DISTANCE: f32 = 0.3;
+// How fast a searching guard looks around, in radians per second
+const SEARCH_TURN_RATE: f32 = 1.0;
+
+/* Noise */
+// Sound made at `position` that guards within `radius` metres hear
+#[derive(Clone, Copy, Debug)]
+pub struct NoiseEvent {
+    pub position: Vector3<f32>,
+    pub radius: f32,
+}
+
+/* Guard system */
+// Sends guards that hear a noise over to it along the navigation grid.
+// There they look around for a while before walking back to their post
+// and facing the way they did before
+#[derive(Default)]
+pub struct GuardSystem {
+    reader: Option<ReaderId<NoiseEvent>>,
+    routes: HashMap<Entity, Route>,
+}
+
+impl<'s> System<'s> for GuardSystem {
+    type SystemData = (
+        WriteStorage<'s, Guard>,
+        WriteStorage<'s, Transform>,
+        ReadStorage<'s, Health>,
+        ReadStorage<'s, Collider>,
+        Read<'s, EventChannel<NoiseEvent>>,
+        Option<Read<'s, NavMesh>>,
+        Read<'s, Physics>,
+        Read<'s, GuardConfig>,
+        Read<'s, Time>,
+        Entities<'s>,
+        );
+
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+        self.reader = Some(world.fetch_mut::<EventChannel<NoiseEvent>>().register_reader());
+    }
+
+    fn run(&mut self, (mut guards, mut transforms, healths, colliders, noises, nav, physics, config, time, entities): Self::SystemData) {
+        let noises: Vec<NoiseEvent> = noises.read(self.reader.as_mut().unwrap()).cloned().collect();
+        let nav = match nav {
+            Some(nav) => nav,
+            None => return,
+        };
+        let dt = time.delta_seconds();
+        self.routes.retain(|entity, _| entities.is_alive(*entity));
+
+        for (entity, guard, transform, health, collider) in (&entities, &mut guards, &mut transforms, healths.maybe(), colliders.maybe()).join() {
+            if health.map_or(false, Health::is_down) {
+                self.routes.remove(&entity);
+                continue;
+            }
+            let feet = route::ground(&physics, world_position(transform));
+
+            // The latest noise within earshot wins
+            if let Some(noise) = noises.iter().rev().find(|noise| (noise.position - feet).norm() <= noise.radius) {
+                info!("A guard heard a noise");
+                guard.investigate = Some([noise.position.x, noise.position.y, noise.position.z]);
+                guard.searched = 0.0;
+                self.routes.remove(&entity);
+            }
+
+            let (goal, stop) = match guard.investigate {
+                Some([x, y, z]) => (Vector3::new(x, y, z), SEARCH_DISTANCE),
+                None => {
+                    let [x, y, z] = guard.post;
+                    (Vector3::new(x, y, z), POST_DISTANCE)
+                },
+            };
+
+            if distance_xz(&goal, &feet) <= stop {
+                self.routes.remove(&entity);
+                if guard.investigate.is_some() {
+                    guard.searched += dt;
+                    transform.append_rotation_y_axis(SEARCH_TURN_RATE * dt);
+                    if guard.searched >= config.search_time {
+                        guard.investigate = None;
+                        guard.searched = 0.0;
+                    }
+                } else {
+                    transform.set_rotation_y_axis(guard.post_yaw);
+                }
+                continue;
+            }
+
+            if !self.routes.contains_key(&entity) {
+                match Route::plan(&nav, feet, goal) {
+                    Some(route) => {
+                        self.routes.insert(entity, route);
+                    },
+                    None => {
+                        // Out of reach: search from here, or keep watch from here instead of the post
+                        match guard.investigate {
+                            Some(_) => guard.investigate = Some([feet.x, feet.y, feet.z]),
+                            None => guard.post = [feet.x, feet.y, feet.z],
+                        }
+                        continue;
+                    },
+                }
+            }
+
+            let route = self.routes.get_mut(&entity).unwrap();
+            route.walk(transform, feet, config.speed * dt, centre_height(collider), &physics);
+            if route.is_finished() {
+                self.routes.remove(&entity);
+            }
+        }
+    }
+}
+
+/* Guard attack system */
+// Guards that raised the alarm turn on the closest squad member they can
+// see and shoot at it every so often. Drones fly too high to hit
+#[derive(Default)]
+pub struct GuardAttackSystem {
+    // Seconds until each guard can shoot again
+    cooldowns: HashMap<Entity, f32>,
+}
+
+impl<'s> System<'s> for GuardAttackSystem {
+    type SystemData = (
+        WriteStorage<'s, Health>,
+        WriteStorage<'s, Transform>,
+        ReadStorage<'s, Guard>,
+        ReadStorage<'s, SquadMember>,
+        ReadStorage<'s, Collider>,
+        ReadStorage<'s, Drone>,
+        Read<'s, Physics>,
+        Read<'s, GuardConfig>,
+        Read<'s, PlayerEntity>,
+        Read<'s, CameraState>,
+        Read<'s, Time>,
+        Entities<'s>,
+        );
+
+    fn run(&mut self, (mut healths, mut transforms, guards, squad, colliders, drones, physics, config, player, camera, time, entities): Self::SystemData) {
+        let dt = time.delta_seconds();
+        let blocking = physics::filter(&[GROUP_TERRAIN, GROUP_PROPS]);
+        self.cooldowns.retain(|entity, _| entities.is_alive(*entity));
+
+        let members: Vec<(Entity, Vector3<f32>)> = (&entities, &squad, &transforms, colliders.maybe(), !&drones).join()
+            .filter(|(entity, _, _, _, _)| !healths.get(*entity).map_or(false, Health::is_down))
+            .map(|(entity, _, transform, collider, _)| {
+                let position = match camera.body_position() {
+                    Some(body) if entity.id() == player.index() => body,
+                    _ => world_position(transform),
+                };
+                (entity, position + Vector3::y() * collider.map_or(0.0, |collider| collider.offset))
+            })
+            .collect();
+
+        for (entity, guard, transform) in (&entities, &guards, &mut transforms).join() {
+            if !guard.alerted || healths.get(entity).map_or(false, Health::is_down) {
+                self.cooldowns.remove(&entity);
+                continue;
+            }
+            let cooldown = self.cooldowns.entry(entity).or_insert(config.attack_interval);
+            *cooldown = (*cooldown - dt).max(0.0);
+            if *cooldown > 0.0 {
+                continue;
+            }
+
+            let eye = world_position(transform) + Vector3::y() * GUARD_EYE_HEIGHT;
+            let target = members.iter()
+                .map(|(member, position)| (*member, position - eye))
+                .filter(|(_, to)| to.norm() <= config.attack_range)
+                .filter(|(_, to)| physics.cast_ray(physics::point(&eye), physics::vector(to), 1.0, &blocking).is_none())
+                .min_by(|(_, a), (_, b)| a.norm().partial_cmp(&b.norm()).unwrap());
+            if let Some((member, to)) = target {
+                // Units look along -z
+                transform.set_rotation_y_axis((-to.x).atan2(-to.z));
+                if let Some(health) = healths.get_mut(member) {
+                    health.damage(config.attack_damage);
+                }
+                *cooldown = config.attack_interval;
+            }
+        }
+    }
+}
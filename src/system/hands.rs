@@ -0,0 +1,456 @@
+use std::collections::HashMap;
+use crate::{
+    component::{Bow, Grabbable, HandPresence, Health, Projectile, SquadMember},
+    intent::{Hand, PlayerIntent},
+    physics::{self, Body, Physics, GROUP_PROPS, GROUP_TERRAIN, GROUP_UNITS},
+    sky::weather::Weather,
+    state::ArrowPrefab,
+};
+use amethyst::{
+    ecs::*,
+    core::{
+        math::{Matrix3, Rotation3, UnitQuaternion, Vector3},
+        timing::Time,
+        transform::{Parent, Transform},
+    },
+    derive::SystemDesc,
+};
+
+// Analog values above this count as pressed
+const PRESS_THRESHOLD: f32 = 0.5;
+// Radius around the hand in which objects can be grabbed
+const GRAB_RADIUS: f32 = 0.15;
+// How close the string hand must come to the bow to nock an arrow
+const NOCK_RADIUS: f32 = 0.2;
+const MIN_DRAW: f32 = 0.1;
+const MAX_DRAW: f32 = 0.7;
+// Arrow speed at full draw, in metres per second
+const MAX_ARROW_SPEED: f32 = 60.0;
+// Seconds from being loosed until an arrow is removed, in flight or stuck in something
+const ARROW_LIFETIME: f32 = 30.0;
+// Damage dealt by an arrow at full speed; slower arrows deal proportionally less
+const ARROW_DAMAGE: f32 = 100.0;
+const GRAVITY: f32 = 9.81;
+// How quickly an arrow in flight picks up the wind's speed on top of its own, per second
+const WIND_DRIFT: f32 = 0.4;
+
+/* Hand system */
+// Poses the hands from PlayerIntent and picks up or drops
+// grabbable objects when the grip is pressed or released
+#[derive(SystemDesc)]
+pub struct HandSystem;
+
+impl<'s> System<'s> for HandSystem {
+    type SystemData = (
+        WriteStorage<'s, Transform>,
+        WriteStorage<'s, HandPresence>,
+        WriteStorage<'s, Grabbable>,
+        WriteStorage<'s, Parent>,
+        Read<'s, PlayerIntent>,
+        Read<'s, Physics>,
+        Entities<'s>,
+        );
+
+    fn run(&mut self, (mut transforms, mut hands, mut grabbables, mut parents, intent, physics, entities): Self::SystemData) {
+        let mut changes = Vec::new();
+        for (entity, hand, transform) in (&entities, &mut hands, &mut transforms).join() {
+            let state = intent.hand(hand.hand);
+            transform.set_translation(state.pose.position);
+            transform.set_rotation(state.pose.orientation);
+
+            let gripping = state.tracked && state.grip > PRESS_THRESHOLD;
+            if gripping != hand.gripping {
+                changes.push((entity, hand.hand, gripping, world_position(transform)));
+            }
+            hand.gripping = gripping;
+        }
+
+        for (hand_entity, side, gripping, position) in changes {
+            let hand = match hands.get_mut(hand_entity) {
+                Some(hand) => hand,
+                None => continue,
+            };
+
+            if gripping && hand.holding.is_none() {
+                let target = physics
+                    .overlap_sphere(physics::point(&position), GRAB_RADIUS, &physics::filter(&[GROUP_UNITS]))
+                    .into_iter()
+                    .filter_map(|body| match body {
+                        Body::Unit(entity) => Some(entity),
+                        _ => None,
+                    })
+                    .find(|entity| grabbables.get(*entity).map_or(false, |g| g.held_by.is_none()));
+
+                if let Some(target) = target {
+                    let _ = parents.insert(target, Parent { entity: hand_entity });
+                    if let Some(transform) = transforms.get_mut(target) {
+                        *transform = Transform::default();
+                    }
+                    if let Some(grabbable) = grabbables.get_mut(target) {
+                        grabbable.held_by = Some(side);
+                    }
+                    hand.holding = Some(target);
+                }
+            } else if !gripping {
+                if let Some(target) = hand.holding.take() {
+                    // Leave the object where it is in the world
+                    if let Some(transform) = transforms.get_mut(target) {
+                        *transform = world_transform(transform);
+                    }
+                    parents.remove(target);
+                    if let Some(grabbable) = grabbables.get_mut(target) {
+                        grabbable.held_by = None;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/* Bow system */
+// While one hand holds a bow, the other hand nocks by pulling its trigger
+// near the bow. Releasing the trigger looses an arrow whose speed grows
+// with the draw distance
+#[derive(SystemDesc)]
+pub struct BowSystem;
+
+impl<'s> System<'s> for BowSystem {
+    type SystemData = (
+        WriteStorage<'s, Bow>,
+        ReadStorage<'s, Grabbable>,
+        ReadStorage<'s, HandPresence>,
+        ReadStorage<'s, Transform>,
+        Read<'s, PlayerIntent>,
+        Option<Read<'s, ArrowPrefab>>,
+        Read<'s, LazyUpdate>,
+        Entities<'s>,
+        );
+
+    fn run(&mut self, (mut bows, grabbables, hands, transforms, intent, arrow, lazy, entities): Self::SystemData) {
+        let hand_position = |side: Hand| {
+            (&hands, &transforms).join()
+                .find(|(hand, _)| hand.hand == side)
+                .map(|(_, transform)| world_position(transform))
+        };
+
+        for (bow, grabbable, transform) in (&mut bows, &grabbables, &transforms).join() {
+            let string_hand = match grabbable.held_by {
+                Some(Hand::Left) => Hand::Right,
+                Some(Hand::Right) => Hand::Left,
+                None => {
+                    bow.drawing = None;
+                    bow.draw = 0.0;
+                    continue;
+                },
+            };
+            let string = match hand_position(string_hand) {
+                Some(position) => position,
+                None => continue,
+            };
+            let grip = world_position(transform);
+            let distance = (grip - string).norm();
+            let pulling = intent.hand(string_hand).trigger > PRESS_THRESHOLD;
+
+            match bow.drawing {
+                None => if pulling && distance < NOCK_RADIUS {
+                    bow.drawing = Some(string_hand);
+                    bow.draw = 0.0;
+                },
+                Some(_) if pulling => {
+                    bow.draw = distance.min(MAX_DRAW);
+                },
+                Some(_) => {
+                    if bow.draw >= MIN_DRAW && distance > 0.0 {
+                        let direction = (grip - string) / distance;
+                        let speed = bow.draw / MAX_DRAW * MAX_ARROW_SPEED;
+
+                        let mut arrow_transform = Transform::default();
+                        arrow_transform.set_translation(grip);
+                        let mut builder = lazy.create_entity(&entities)
+                            .with(arrow_transform)
+                            .with(Projectile { velocity: direction * speed, drift: Vector3::zeros(), age: 0.0 });
+                        if let Some(arrow) = &arrow {
+                            builder = builder.with(arrow.handle());
+                        }
+                        builder.build();
+                    }
+                    bow.drawing = None;
+                    bow.draw = 0.0;
+                },
+            }
+        }
+    }
+}
+
+/* Projectile system */
+// Moves arrows under gravity and the wind and sticks them into whatever
+// they hit, hurting units that have Health. Arrows stuck in a unit move
+// with it. Arrows fly through squad members and held objects, so the
+// player cannot shoot themselves, their own squad or the bow they were
+// loosed from
+#[derive(Default)]
+pub struct ProjectileSystem {
+    // Age of every arrow that has landed
+    landed: HashMap<Entity, f32>,
+}
+
+impl<'s> System<'s> for ProjectileSystem {
+    type SystemData = (
+        WriteStorage<'s, Projectile>,
+        WriteStorage<'s, Transform>,
+        WriteStorage<'s, Parent>,
+        WriteStorage<'s, Health>,
+        ReadStorage<'s, SquadMember>,
+        ReadStorage<'s, Grabbable>,
+        Option<Read<'s, Weather>>,
+        Read<'s, Physics>,
+        Read<'s, Time>,
+        Entities<'s>,
+        );
+
+    fn run(&mut self, (mut projectiles, mut transforms, mut parents, mut healths, squad, grabbables, weather, physics, time, entities): Self::SystemData) {
+        let dt = time.delta_seconds();
+        let wind = weather.map_or(Vector3::zeros(), |weather| Vector3::from(weather.wind()));
+        let solid = physics::filter(&[GROUP_TERRAIN, GROUP_PROPS, GROUP_UNITS]);
+        let friendly = |body: &Body| match body {
+            Body::Unit(unit) => squad.contains(*unit) || grabbables.contains(*unit),
+            _ => false,
+        };
+        let mut landed = Vec::new();
+
+        // Landed arrows go once they are old enough, or with the unit they stuck in
+        self.landed.retain(|arrow, _| entities.is_alive(*arrow));
+        let expired: Vec<Entity> = self.landed.iter_mut()
+            .filter_map(|(arrow, age)| {
+                *age += dt;
+                let orphaned = parents.get(*arrow).map_or(false, |parent| !entities.is_alive(parent.entity));
+                if *age > ARROW_LIFETIME || orphaned { Some(*arrow) } else { None }
+            })
+            .collect();
+        for arrow in expired {
+            self.landed.remove(&arrow);
+            let _ = entities.delete(arrow);
+        }
+
+        for (entity, projectile, transform) in (&entities, &mut projectiles, &mut transforms).join() {
+            projectile.age += dt;
+            if projectile.age > ARROW_LIFETIME {
+                let _ = entities.delete(entity);
+                continue;
+            }
+
+            projectile.velocity.y -= GRAVITY * dt;
+            // The wind carries the arrow along until it drifts at the wind's own speed;
+            // in still air it flies exactly as loosed
+            let gust = (wind - projectile.drift) * WIND_DRIFT * dt;
+            projectile.drift += gust;
+            projectile.velocity += gust;
+            let start = *transform.translation();
+            let motion = projectile.velocity * dt;
+
+            let hit = physics.cast_ray_ignoring(physics::point(&start), physics::vector(&motion), 1.0, &solid, friendly);
+            let end = match hit {
+                Some(hit) => {
+                    let unit = match hit.body {
+                        Body::Unit(unit) => Some(unit),
+                        _ => None,
+                    };
+                    landed.push((entity, projectile.age, unit));
+                    if let Some(health) = unit.and_then(|unit| healths.get_mut(unit)) {
+                        health.damage(ARROW_DAMAGE * projectile.velocity.norm() / MAX_ARROW_SPEED);
+                    }
+                    start + motion * hit.toi
+                },
+                None => start + motion,
+            };
+
+            transform.set_translation(end);
+            transform.face_towards(end + projectile.velocity, Vector3::y());
+        }
+
+        for (entity, age, unit) in landed {
+            projectiles.remove(entity);
+            self.landed.insert(entity, age);
+
+            // Units stand at the top of the hierarchy, so their own transform places them in the world
+            let anchor = unit.and_then(|unit| transforms.get(unit).map(|anchor| (unit, *anchor.translation(), anchor.rotation().inverse())));
+            let (unit, origin, inverse) = match anchor {
+                Some(anchor) => anchor,
+                None => continue,
+            };
+            if let Some(transform) = transforms.get_mut(entity) {
+                let local = inverse * (transform.translation() - origin);
+                let rotation = inverse * transform.rotation();
+                transform.set_translation(local);
+                transform.set_rotation(rotation);
+                let _ = parents.insert(entity, Parent { entity: unit });
+            }
+        }
+    }
+}
+
+pub fn world_position(transform: &Transform) -> Vector3<f32> {
+    let m = transform.global_matrix();
+    Vector3::new(m[(0, 3)], m[(1, 3)], m[(2, 3)])
+}
+
+// Local transform that puts an entity where `transform` currently is in the world
+pub fn world_transform(transform: &Transform) -> Transform {
+    let m = transform.global_matrix();
+    let column = |i: usize| Vector3::new(m[(0, i)], m[(1, i)], m[(2, i)]).normalize();
+    let basis = Matrix3::from_columns(&[column(0), column(1), column(2)]);
+    let rotation = UnitQuaternion::from_rotation_matrix(&Rotation3::from_matrix_unchecked(basis));
+
+    let mut result = transform.clone();
+    result.set_translation(world_position(transform));
+    result.set_rotation(rotation);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use amethyst::ecs::RunNow;
+
+    // A player holding a bow at the origin, facing a guard standing `range` metres away along -z
+    fn range(range: f32) -> (World, Entity) {
+        let mut world = World::new();
+        <ProjectileSystem as System<'_>>::SystemData::setup(&mut world);
+
+        let player = world.create_entity().with(SquadMember::new(crate::component::SquadRole::Player)).build();
+        let bow = world.create_entity().with(Grabbable { held_by: Some(Hand::Left) }).build();
+        let guard = world.create_entity().with(Health::new(100.0)).build();
+
+        let mut physics = Physics::default();
+        physics.sync_unit(player, physics::point(&Vector3::new(0.0, 0.9, 0.2)), 0.3, 0.6);
+        physics.sync_unit(bow, physics::point(&Vector3::new(0.0, 1.4, 0.0)), 0.1, 0.5);
+        physics.sync_unit(guard, physics::point(&Vector3::new(0.0, 0.9, -range)), 0.4, 0.5);
+        physics.update();
+        world.insert(physics);
+
+        let mut time = Time::default();
+        time.set_delta_seconds(1.0 / 60.0);
+        world.insert(time);
+        (world, guard)
+    }
+
+    fn loose(world: &mut World, velocity: Vector3<f32>) -> Entity {
+        let mut transform = Transform::default();
+        transform.set_translation_xyz(0.0, 1.4, 0.0);
+        world.create_entity()
+            .with(transform)
+            .with(Projectile { velocity: velocity, drift: Vector3::zeros(), age: 0.0 })
+            .build()
+    }
+
+    #[test]
+    fn arrow_leaves_the_bow_and_hits_the_guard() {
+        let (mut world, guard) = range(10.0);
+        let arrow = loose(&mut world, Vector3::new(0.0, 0.0, -MAX_ARROW_SPEED));
+
+        let mut system = ProjectileSystem::default();
+        system.run_now(&world);
+        world.maintain();
+        // Still flying after the first frame, clear of the bow and the player
+        assert!(world.read_storage::<Projectile>().contains(arrow));
+        assert!(world.read_storage::<Transform>().get(arrow).unwrap().translation().z < -0.5);
+
+        for _ in 0..60 {
+            system.run_now(&world);
+            world.maintain();
+        }
+        assert!(!world.read_storage::<Projectile>().contains(arrow));
+        let z = world.read_storage::<Transform>().get(arrow).unwrap().translation().z;
+        assert!((z - -9.6).abs() < 0.1, "arrow stopped at z = {}", z);
+        let health = *world.read_storage::<Health>().get(guard).unwrap();
+        assert!(health.current < health.max);
+    }
+
+    #[test]
+    fn arrow_falls_short_of_a_distant_guard() {
+        let (mut world, guard) = range(200.0);
+        let arrow = loose(&mut world, Vector3::new(0.0, 0.0, -20.0));
+
+        let mut system = ProjectileSystem::default();
+        for _ in 0..600 {
+            system.run_now(&world);
+            world.maintain();
+        }
+        // Nothing to stick into without terrain, so the arrow keeps falling
+        assert!(world.read_storage::<Projectile>().contains(arrow));
+        assert!(world.read_storage::<Transform>().get(arrow).unwrap().translation().y < 0.0);
+        let health = *world.read_storage::<Health>().get(guard).unwrap();
+        assert_eq!(health.current, health.max);
+    }
+
+    #[test]
+    fn arrow_drifts_with_the_wind_but_never_outruns_it() {
+        use crate::{config::WeatherConfig, gen::Terrain, sky::weather::WeatherKind};
+
+        let (mut world, _) = range(200.0);
+        let weather = Weather::generate(&Terrain::Mountains, 7, Some(WeatherKind::Wind), &WeatherConfig::default());
+        let wind = Vector3::from(weather.wind());
+        world.insert(weather);
+        let arrow = loose(&mut world, Vector3::new(0.0, 50.0, 0.0));
+
+        let mut system = ProjectileSystem::default();
+        let mut drift = 0.0;
+        for _ in 0..600 {
+            system.run_now(&world);
+            world.maintain();
+            let velocity = world.read_storage::<Projectile>().get(arrow).unwrap().velocity;
+            let along_wind = Vector3::new(velocity.x, 0.0, velocity.z).dot(&wind) / wind.norm();
+            assert!(along_wind >= drift && along_wind <= wind.norm());
+            drift = along_wind;
+        }
+        // Ten seconds in, the arrow all but moves with the wind
+        assert!(drift > wind.norm() * 0.95, "drifting at {} in a {} wind", drift, wind.norm());
+    }
+
+    #[test]
+    fn arrow_keeps_its_speed_in_still_air() {
+        let (mut world, _) = range(500.0);
+        world.insert(Weather::default());
+        let arrow = loose(&mut world, Vector3::new(3.0, 5.0, -20.0));
+
+        let mut system = ProjectileSystem::default();
+        for _ in 0..120 {
+            system.run_now(&world);
+            world.maintain();
+        }
+        let velocity = world.read_storage::<Projectile>().get(arrow).unwrap().velocity;
+        assert_eq!((velocity.x, velocity.z), (3.0, -20.0));
+    }
+
+    #[test]
+    fn arrow_sticks_in_the_guard_and_goes_after_its_lifetime() {
+        let (mut world, guard) = range(10.0);
+        let mut transform = Transform::default();
+        transform.set_translation_xyz(0.0, 0.9, -10.0);
+        transform.set_rotation_y_axis(std::f32::consts::FRAC_PI_2);
+        world.write_storage::<Transform>().insert(guard, transform).unwrap();
+        let arrow = loose(&mut world, Vector3::new(0.0, 0.0, -MAX_ARROW_SPEED));
+
+        let mut system = ProjectileSystem::default();
+        for _ in 0..60 {
+            system.run_now(&world);
+            world.maintain();
+        }
+        assert!(!world.read_storage::<Projectile>().contains(arrow));
+        assert_eq!(world.read_storage::<Parent>().get(arrow).map(|parent| parent.entity), Some(guard));
+        // Still where it hit, now held relative to the guard
+        {
+            let transforms = world.read_storage::<Transform>();
+            let (local, anchor) = (transforms.get(arrow).unwrap(), transforms.get(guard).unwrap());
+            let at = anchor.rotation() * local.translation() + anchor.translation();
+            assert!((at - Vector3::new(0.0, 1.4, -9.6)).norm() < 0.1, "arrow stuck at {:?}", at);
+        }
+
+        world.write_resource::<Time>().set_delta_seconds(10.0);
+        for _ in 0..4 {
+            system.run_now(&world);
+            world.maintain();
+        }
+        assert!(!world.is_alive(arrow));
+    }
+}
@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+use crate::component::Health;
+use amethyst::{
+    core::timing::Time,
+    ecs::*,
+    shrev::EventChannel,
+};
+
+/* Health events */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HealthEventKind {
+    Downed,
+    Revived,
+    // Started healing over time
+    Healed,
+    Died,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct HealthEvent {
+    pub entity: Entity,
+    pub kind: HealthEventKind,
+}
+
+// What a unit's health allows it to do
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Condition {
+    Standing,
+    Downed,
+    Dead,
+}
+
+impl Condition {
+    fn of(health: &Health) -> Self {
+        if health.is_downed() {
+            Condition::Downed
+        } else if health.is_dead() {
+            Condition::Dead
+        } else {
+            Condition::Standing
+        }
+    }
+}
+
+/* Health system */
+// Runs down the clock of downed units until they bleed out and heals
+// units over time. Reports units going down, getting back up and dying,
+// whatever hurt or revived them
+#[derive(Default)]
+pub struct HealthSystem {
+    conditions: HashMap<Entity, Condition>,
+}
+
+impl<'s> System<'s> for HealthSystem {
+    type SystemData = (
+        WriteStorage<'s, Health>,
+        Write<'s, EventChannel<HealthEvent>>,
+        Read<'s, Time>,
+        Entities<'s>,
+        );
+
+    fn run(&mut self, (mut healths, mut events, time, entities): Self::SystemData) {
+        let dt = time.delta_seconds();
+        self.conditions.retain(|entity, _| entities.is_alive(*entity));
+
+        for (entity, health) in (&entities, &mut healths).join() {
+            if let Some(left) = health.bleeding {
+                health.bleeding = if left > dt { Some(left - dt) } else { None };
+            }
+            if let Some((rate, left)) = health.regen {
+                health.current = (health.current + rate * dt.min(left)).min(health.max);
+                health.regen = if left > dt && health.current < health.max { Some((rate, left - dt)) } else { None };
+            }
+
+            let condition = Condition::of(health);
+            let previous = self.conditions.insert(entity, condition).unwrap_or(Condition::Standing);
+            let kind = match (previous, condition) {
+                (Condition::Standing, Condition::Downed) => HealthEventKind::Downed,
+                (Condition::Downed, Condition::Standing) => HealthEventKind::Revived,
+                (Condition::Standing, Condition::Dead) | (Condition::Downed, Condition::Dead) => HealthEventKind::Died,
+                _ => continue,
+            };
+            events.single_write(HealthEvent { entity: entity, kind: kind });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use amethyst::ecs::RunNow;
+
+    // A world with one unit and a second of game time every frame
+    fn unit(health: Health) -> (World, Entity, ReaderId<HealthEvent>) {
+        let mut world = World::new();
+        <HealthSystem as System<'_>>::SystemData::setup(&mut world);
+        let reader = world.fetch_mut::<EventChannel<HealthEvent>>().register_reader();
+        let entity = world.create_entity().with(health).build();
+
+        let mut time = Time::default();
+        time.set_delta_seconds(1.0);
+        world.insert(time);
+        (world, entity, reader)
+    }
+
+    fn frame(system: &mut HealthSystem, world: &World, reader: &mut ReaderId<HealthEvent>) -> Vec<HealthEventKind> {
+        system.run_now(world);
+        world.fetch::<EventChannel<HealthEvent>>().read(reader).map(|event| event.kind).collect()
+    }
+
+    fn hurt(world: &World, entity: Entity, amount: f32) {
+        world.write_storage::<Health>().get_mut(entity).unwrap().damage(amount);
+    }
+
+    #[test]
+    fn reports_going_down_getting_up_and_dying() {
+        let (world, entity, mut reader) = unit(Health { bleed_time: 3.0, ..Health::new(100.0) });
+        let mut system = HealthSystem::default();
+        assert!(frame(&mut system, &world, &mut reader).is_empty());
+
+        hurt(&world, entity, 100.0);
+        assert_eq!(frame(&mut system, &world, &mut reader), vec![HealthEventKind::Downed]);
+        assert!(frame(&mut system, &world, &mut reader).is_empty());
+
+        world.write_storage::<Health>().get_mut(entity).unwrap().revive(0.5);
+        assert_eq!(frame(&mut system, &world, &mut reader), vec![HealthEventKind::Revived]);
+
+        hurt(&world, entity, 100.0);
+        assert_eq!(frame(&mut system, &world, &mut reader), vec![HealthEventKind::Downed]);
+        hurt(&world, entity, 1.0);
+        assert_eq!(frame(&mut system, &world, &mut reader), vec![HealthEventKind::Died]);
+        assert!(frame(&mut system, &world, &mut reader).is_empty());
+    }
+
+    #[test]
+    fn downed_units_bleed_out() {
+        let (world, entity, mut reader) = unit(Health { bleed_time: 2.5, ..Health::new(100.0) });
+        let mut system = HealthSystem::default();
+        hurt(&world, entity, 100.0);
+        assert_eq!(frame(&mut system, &world, &mut reader), vec![HealthEventKind::Downed]);
+        assert!(frame(&mut system, &world, &mut reader).is_empty());
+        assert_eq!(frame(&mut system, &world, &mut reader), vec![HealthEventKind::Died]);
+        assert!(world.read_storage::<Health>().get(entity).unwrap().is_dead());
+    }
+
+    #[test]
+    fn units_without_a_bleed_time_die_outright() {
+        let (world, entity, mut reader) = unit(Health::new(100.0));
+        let mut system = HealthSystem::default();
+        hurt(&world, entity, 100.0);
+        assert_eq!(frame(&mut system, &world, &mut reader), vec![HealthEventKind::Died]);
+    }
+
+    #[test]
+    fn healing_stops_after_its_duration() {
+        let (world, entity, mut reader) = unit(Health::new(100.0));
+        let mut system = HealthSystem::default();
+        hurt(&world, entity, 50.0);
+        world.write_storage::<Health>().get_mut(entity).unwrap().heal_over_time(5.0, 2.5);
+
+        for _ in 0..5 {
+            assert!(frame(&mut system, &world, &mut reader).is_empty());
+        }
+        let health = *world.read_storage::<Health>().get(entity).unwrap();
+        assert_eq!(health.current, 62.5);
+        assert_eq!(health.regen, None);
+    }
+
+    #[test]
+    fn healing_stops_at_full_health() {
+        let (world, entity, _) = unit(Health::new(100.0));
+        let mut system = HealthSystem::default();
+        hurt(&world, entity, 10.0);
+        world.write_storage::<Health>().get_mut(entity).unwrap().heal_over_time(4.0, 10.0);
+
+        for _ in 0..3 {
+            system.run_now(&world);
+        }
+        let health = *world.read_storage::<Health>().get(entity).unwrap();
+        assert_eq!(health.current, 100.0);
+        assert_eq!(health.regen, None);
+    }
+}
@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+use crate::{
+    component::{Drone, Guard, Health, SquadMember, SquadRole},
+    config::MissionConfig,
+    mission::{Mission, ObjectiveStatus},
+    state::PlayerEntity,
+    system::{
+        camera::CameraState,
+        health::{HealthEvent, HealthEventKind},
+    },
+};
+use amethyst::{
+    assets::{AssetStorage, Handle, Loader},
+    core::{timing::Time, HiddenPropagate},
+    ecs::*,
+    renderer::{ImageFormat, Texture},
+    shrev::{EventChannel, ReaderId},
+    ui::{UiImage, UiText, UiTransform},
+};
+
+// Number of squad slots laid out in ui/hud.ron
+const SQUAD_SLOTS: usize = 6;
+// Full widths of the bars in ui/hud.ron, in pixels; battery bars match health bars
+const HEALTH_BAR_WIDTH: f32 = 96.0;
+const DETECTION_BAR_WIDTH: f32 = 300.0;
+const CALM_COLOUR: [f32; 4] = [1.0, 0.65, 0.0, 1.0];
+const ALERT_COLOUR: [f32; 4] = [0.9, 0.1, 0.1, 1.0];
+// Seconds a squad report stays on screen
+const REPORT_TIME: f32 = 4.0;
+
+/* HUD system */
+// Fills in the HUD created from ui/hud.ron while a raid is on: a slot per
+// squad member with its icon, health, current order and a drone's
+// battery, the awareness of the most suspicious guard, the state of every
+// objective, the drone's battery while looking through its camera and a
+// report whenever a squad member goes down, gets back up, is treated or dies
+#[derive(Default)]
+pub struct HudSystem {
+    icons: HashMap<SquadRole, Handle<Texture>>,
+    reader: Option<ReaderId<HealthEvent>>,
+    // Latest squad report and the seconds it has been shown for
+    report: Option<(String, f32)>,
+}
+
+impl<'s> System<'s> for HudSystem {
+    type SystemData = (
+        ReadStorage<'s, SquadMember>,
+        ReadStorage<'s, Health>,
+        ReadStorage<'s, Guard>,
+        ReadStorage<'s, Drone>,
+        WriteStorage<'s, UiTransform>,
+        WriteStorage<'s, UiImage>,
+        WriteStorage<'s, UiText>,
+        WriteStorage<'s, HiddenPropagate>,
+        Option<Read<'s, Mission>>,
+        Read<'s, MissionConfig>,
+        Read<'s, PlayerEntity>,
+        Read<'s, CameraState>,
+        Read<'s, EventChannel<HealthEvent>>,
+        Read<'s, Time>,
+        ReadExpect<'s, Loader>,
+        Read<'s, AssetStorage<Texture>>,
+        Entities<'s>,
+        );
+
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+        self.reader = Some(world.fetch_mut::<EventChannel<HealthEvent>>().register_reader());
+    }
+
+    fn run(&mut self, (squad, healths, guards, drones, mut ui_transforms, mut images, mut texts, mut hidden, mission, config, player, camera, events, time, loader, texture_storage, entities): Self::SystemData) {
+        for event in events.read(self.reader.as_mut().unwrap()) {
+            if let Some(member) = squad.get(event.entity) {
+                let name = member.role.name();
+                let report = match (event.kind, member.role) {
+                    (HealthEventKind::Downed, SquadRole::Player) => String::from("You are down"),
+                    (HealthEventKind::Downed, _) => format!("{} is down", name),
+                    (HealthEventKind::Revived, _) => format!("{} is back up", name),
+                    (HealthEventKind::Healed, _) => format!("{} was treated", name),
+                    (HealthEventKind::Died, SquadRole::Player) => String::from("You were killed"),
+                    (HealthEventKind::Died, _) => format!("{} was killed", name),
+                };
+                self.report = Some((report, 0.0));
+            }
+        }
+        if let Some((_, shown)) = self.report.as_mut() {
+            *shown += time.delta_seconds();
+        }
+        if self.report.as_ref().map_or(false, |(_, shown)| *shown > REPORT_TIME) {
+            self.report = None;
+        }
+
+        let mission = match mission {
+            Some(mission) => mission,
+            None => return,
+        };
+        // UiFinder would borrow the UiTransforms this system resizes, so the widgets are looked up here
+        let ids: HashMap<String, Entity> = (&entities, &ui_transforms).join()
+            .map(|(entity, transform)| (transform.id.clone(), entity))
+            .collect();
+        let find = |id: &str| ids.get(id).cloned();
+        // Nothing to fill in until the HUD has loaded
+        if find("hud").is_none() {
+            return;
+        }
+
+        /* Squad bar */
+        let mut members: Vec<(Entity, &SquadMember, Option<&Health>, Option<&Drone>)> =
+            (&entities, &squad, healths.maybe(), drones.maybe()).join().collect();
+        members.sort_by_key(|(entity, _, _, _)| (entity.id() != player.index(), entity.id()));
+
+        for slot in 0..SQUAD_SLOTS {
+            let container = match find(&format!("squad_{}", slot)) {
+                Some(container) => container,
+                None => continue,
+            };
+            let (member, health, drone) = match members.get(slot) {
+                Some((_, member, health, drone)) => (member, health, drone),
+                None => {
+                    if !hidden.contains(container) {
+                        let _ = hidden.insert(container, HiddenPropagate::default());
+                    }
+                    continue;
+                },
+            };
+            hidden.remove(container);
+
+            if let Some(icon) = find(&format!("squad_{}_icon", slot)) {
+                let texture = self.icons.entry(member.role)
+                    .or_insert_with(|| loader.load(member.role.icon(), ImageFormat::default(), (), &texture_storage))
+                    .clone();
+                let image = UiImage::Texture(texture);
+                if images.get(icon) != Some(&image) {
+                    let _ = images.insert(icon, image);
+                }
+            }
+
+            let fraction = health.map_or(1.0, |health| health.current / health.max.max(std::f32::EPSILON));
+            if let Some(bar) = find(&format!("squad_{}_health", slot)).and_then(|bar| ui_transforms.get_mut(bar)) {
+                bar.width = HEALTH_BAR_WIDTH * fraction;
+            }
+
+            if let Some(bar) = find(&format!("squad_{}_battery", slot)) {
+                match drone {
+                    Some(drone) => {
+                        hidden.remove(bar);
+                        if let Some(transform) = ui_transforms.get_mut(bar) {
+                            transform.width = HEALTH_BAR_WIDTH * drone.charge();
+                        }
+                    },
+                    None => if !hidden.contains(bar) {
+                        let _ = hidden.insert(bar, HiddenPropagate::default());
+                    },
+                }
+            }
+
+            if let Some(text) = find(&format!("squad_{}_order", slot)).and_then(|label| texts.get_mut(label)) {
+                text.text = if let Some(left) = health.and_then(|health| health.bleeding) {
+                    format!("Bleeding {:.0}s", left)
+                } else if health.map_or(false, Health::is_dead) {
+                    String::from("Down")
+                } else if drone.map_or(false, Drone::is_empty) {
+                    String::from("Landed")
+                } else if member.role == SquadRole::Player {
+                    String::from("Leading")
+                } else {
+                    String::from(member.order.description())
+                };
+            }
+        }
+
+        /* Detection meter */
+        let awareness = (&guards, healths.maybe()).join()
+            .filter(|(_, health)| !health.map_or(false, Health::is_down))
+            .map(|(guard, _)| guard.awareness)
+            .fold(0.0, f32::max);
+        let alarm = mission.alarm() > 0.0;
+
+        if let Some(bar) = find("detection_bar") {
+            if let Some(transform) = ui_transforms.get_mut(bar) {
+                transform.width = DETECTION_BAR_WIDTH * if alarm { 1.0 } else { awareness };
+            }
+            let image = UiImage::SolidColor(if alarm { ALERT_COLOUR } else { CALM_COLOUR });
+            if images.get(bar) != Some(&image) {
+                let _ = images.insert(bar, image);
+            }
+        }
+        if let Some(text) = find("detection_text").and_then(|label| texts.get_mut(label)) {
+            text.text = if alarm {
+                format!("ALARM {:.0}s", (config.alarm_timeout - mission.alarm()).max(0.0))
+            } else if awareness > 0.0 {
+                String::from("Suspicious")
+            } else {
+                String::from("Unseen")
+            };
+        }
+
+        /* Drone feed */
+        if let Some(text) = find("drone_feed").and_then(|label| texts.get_mut(label)) {
+            text.text = match camera.drone().and_then(|drone| drones.get(drone)) {
+                Some(drone) => format!("DRONE FEED  Battery {:.0}%", drone.charge() * 100.0),
+                None => String::new(),
+            };
+        }
+
+        /* Squad reports */
+        if let Some(text) = find("squad_report").and_then(|label| texts.get_mut(label)) {
+            text.text = self.report.as_ref().map_or_else(String::new, |(report, _)| report.clone());
+        }
+
+        /* Objective tracker */
+        if let Some(text) = find("objectives").and_then(|label| texts.get_mut(label)) {
+            text.text = mission.objectives().iter()
+                .map(|objective| format!(
+                    "{}{} {}",
+                    objective.kind.description(),
+                    if objective.optional { " (optional)" } else { "" },
+                    match objective.status {
+                        ObjectiveStatus::Active => "[ ]",
+                        ObjectiveStatus::Completed => "[x]",
+                        ObjectiveStatus::Failed => "[-]",
+                    },
+                ))
+                .collect::<Vec<_>>()
+                .join("\n");
+        }
+    }
+}
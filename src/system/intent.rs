@@ -0,0 +1,149 @@
+use crate::intent::{self, HandState, PlayerIntent, XrBackend};
+use amethyst::{
+    ecs::*,
+    core::{
+        math::{Vector2, Vector3},
+        timing::Time,
+    },
+    input::{InputHandler, StringBindings},
+};
+
+// Resting hand positions relative to the camera when there is no tracking
+const LEFT_HAND_REST: [f32; 3] = [-0.2, -0.25, -0.45];
+const RIGHT_HAND_REST: [f32; 3] = [0.2, -0.25, -0.45];
+// How fast the emulated string hand pulls back, in metres per second
+const EMULATED_DRAW_RATE: f32 = 0.8;
+const EMULATED_MAX_DRAW: f32 = 0.7;
+
+/* Desktop intent system */
+// Keyboard and mouse backend for PlayerIntent. Hands are emulated in
+// front of the camera: F toggles the left grip, the right mouse button
+// holds the right grip, and holding the left mouse button brings the
+// right hand to the left one and pulls it back as if drawing a bow
+#[derive(Default)]
+pub struct DesktopIntentSystem {
+    left_grip: bool,
+    grab_held: bool,
+    draw: f32,
+}
+
+impl<'s> System<'s> for DesktopIntentSystem {
+    type SystemData = (
+        Write<'s, PlayerIntent>,
+        Read<'s, InputHandler<StringBindings>>,
+        Read<'s, Time>,
+        );
+
+    fn run(&mut self, (mut intent, input, time): Self::SystemData) {
+        let forward = input.axis_value("forward_back").unwrap_or(0.0) as f32;
+        let side = -input.axis_value("left_right").unwrap_or(0.0) as f32;
+        let mut locomotion = Vector2::new(side, forward);
+        if locomotion.norm() > 1.0 {
+            locomotion = locomotion.normalize();
+        }
+        intent.locomotion = locomotion;
+
+        intent.look = Vector2::new(
+            0.2 * input.axis_value("mouse_x").unwrap_or(0.0) as f32,
+            0.2 * input.axis_value("mouse_y").unwrap_or(0.0) as f32,
+        );
+
+        intent.run = input.action_is_down("run").unwrap_or(false);
+        intent.crouch = input.action_is_down("crouch").unwrap_or(false);
+        intent.toggle_camera = input.action_is_down("toggle_camera").unwrap_or(false);
+        intent.toggle_drone = input.action_is_down("toggle_drone").unwrap_or(false);
+        intent.dog_takedown = input.action_is_down("dog_takedown").unwrap_or(false);
+        intent.dog_bark = input.action_is_down("dog_bark").unwrap_or(false);
+        intent.medic_revive = input.action_is_down("medic_revive").unwrap_or(false);
+        intent.medic_heal = input.action_is_down("medic_heal").unwrap_or(false);
+
+        intent.teleport = input.action_is_down("teleport").unwrap_or(false);
+        intent.turn = 0.0;
+        if input.action_is_down("turn_left").unwrap_or(false) {
+            intent.turn += 1.0;
+        }
+        if input.action_is_down("turn_right").unwrap_or(false) {
+            intent.turn -= 1.0;
+        }
+
+        intent.rotate = 0.0;
+        if input.action_is_down("rotate_left").unwrap_or(false) {
+            intent.rotate += 1.0;
+        }
+        if input.action_is_down("rotate_right").unwrap_or(false) {
+            intent.rotate -= 1.0;
+        }
+        intent.zoom = input.mouse_wheel_value(false);
+        intent.pointer = input.mouse_position();
+
+        let grab = input.action_is_down("grab_left").unwrap_or(false);
+        if grab && !self.grab_held {
+            self.left_grip = !self.left_grip;
+        }
+        self.grab_held = grab;
+
+        let trigger = input.action_is_down("trigger_right").unwrap_or(false);
+        self.draw = if trigger {
+            (self.draw + EMULATED_DRAW_RATE * time.delta_seconds()).min(EMULATED_MAX_DRAW)
+        } else {
+            0.0
+        };
+
+        let mut left = intent::pose((LEFT_HAND_REST, [0.0; 3]));
+        let mut right = intent::pose((RIGHT_HAND_REST, [0.0; 3]));
+        if trigger {
+            right.position = left.position + Vector3::z() * self.draw;
+        }
+        left.orientation = intent.head.orientation;
+        right.orientation = intent.head.orientation;
+
+        intent.left = HandState {
+            pose: left,
+            tracked: true,
+            trigger: 0.0,
+            grip: if self.left_grip { 1.0 } else { 0.0 },
+        };
+        intent.right = HandState {
+            pose: right,
+            tracked: true,
+            trigger: if trigger { 1.0 } else { 0.0 },
+            grip: if input.action_is_down("grab_right").unwrap_or(false) { 1.0 } else { 0.0 },
+        };
+    }
+}
+
+/* XR intent system */
+// Feeds PlayerIntent from a tracking backend. Buttons the headset has no
+// equivalent for yet still come from the keyboard
+pub struct XrIntentSystem<B: XrBackend> {
+    backend: B,
+}
+
+impl<B: XrBackend> XrIntentSystem<B> {
+    pub fn new(backend: B) -> Self {
+        XrIntentSystem { backend: backend }
+    }
+}
+
+impl<'s, B: XrBackend> System<'s> for XrIntentSystem<B> {
+    type SystemData = (
+        Write<'s, PlayerIntent>,
+        Read<'s, InputHandler<StringBindings>>,
+        Read<'s, Time>,
+        );
+
+    fn run(&mut self, (mut intent, input, time): Self::SystemData) {
+        intent.look = Vector2::zeros();
+        intent.run = input.action_is_down("run").unwrap_or(false);
+        intent.crouch = input.action_is_down("crouch").unwrap_or(false);
+        intent.toggle_camera = input.action_is_down("toggle_camera").unwrap_or(false);
+        intent.toggle_drone = input.action_is_down("toggle_drone").unwrap_or(false);
+        intent.dog_takedown = input.action_is_down("dog_takedown").unwrap_or(false);
+        intent.dog_bark = input.action_is_down("dog_bark").unwrap_or(false);
+        intent.medic_revive = input.action_is_down("medic_revive").unwrap_or(false);
+        intent.medic_heal = input.action_is_down("medic_heal").unwrap_or(false);
+        intent.pointer = None;
+
+        self.backend.poll(time.absolute_time_seconds() as f32, &mut intent);
+    }
+}
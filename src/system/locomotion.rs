@@ -0,0 +1,255 @@
+use crate::{
+    component::CharacterController,
+    config::{LocomotionConfig, MoveMode, TurnMode},
+    intent::PlayerIntent,
+    physics::{self, Body, Physics, GROUP_PROPS, GROUP_TERRAIN},
+    state::PlayerEntity,
+    system::{
+        camera::CameraState,
+        hands::world_position,
+    },
+};
+use amethyst::{
+    ecs::*,
+    core::{
+        math::{Point3, Vector3},
+        timing::Time,
+        transform::components::Transform,
+    },
+    derive::SystemDesc,
+    renderer::{debug_drawing::DebugLines, palette::Srgba},
+    ui::{Anchor, Stretch, UiImage, UiTransform},
+};
+
+// Time between points of the teleport arc, in seconds of simulated flight
+const ARC_STEP: f32 = 0.05;
+const ARC_STEPS: usize = 60;
+const GRAVITY: f32 = 9.81;
+
+/* Turn system */
+// Snap or smooth turning around the vertical axis
+#[derive(SystemDesc)]
+pub struct TurnSystem;
+
+impl<'s> System<'s> for TurnSystem {
+    type SystemData = (
+        WriteStorage<'s, Transform>,
+        Write<'s, TurnState>,
+        Read<'s, PlayerEntity>,
+        Read<'s, PlayerIntent>,
+        Read<'s, LocomotionConfig>,
+        Read<'s, CameraState>,
+        Read<'s, Time>,
+        Entities<'s>,
+        );
+
+    fn run(&mut self, (mut transforms, mut state, player_entity, intent, config, camera, time, entities): Self::SystemData) {
+        let engaged = intent.turn.abs() > 0.5;
+        let pressed = engaged && !state.engaged;
+        state.engaged = engaged;
+
+        if !camera.controls_player() {
+            return;
+        }
+        let player = entities.entity(player_entity.index());
+        let transform = match transforms.get_mut(player) {
+            Some(transform) => transform,
+            None => return,
+        };
+
+        match config.turn {
+            TurnMode::Snap => if pressed {
+                transform.prepend_rotation_y_axis(config.snap_angle.to_radians() * intent.turn.signum());
+            },
+            TurnMode::Smooth => {
+                transform.prepend_rotation_y_axis(config.turn_speed.to_radians() * intent.turn * time.delta_seconds());
+            },
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct TurnState {
+    engaged: bool,
+}
+
+/* Teleport state resource */
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Fade {
+    None,
+    Out(f32),
+    In(f32),
+}
+
+impl Default for Fade {
+    fn default() -> Self { Fade::None }
+}
+
+#[derive(Default)]
+pub struct TeleportState {
+    // Points of the arc being aimed, for rendering
+    pub arc: Vec<Vector3<f32>>,
+    // Where the arc lands and whether the player may go there
+    pub target: Option<(Vector3<f32>, bool)>,
+    aiming: bool,
+    fade: Fade,
+    destination: Option<Vector3<f32>>,
+    overlay: Option<Entity>,
+}
+
+/* Teleport system */
+// While the teleport action is held a parabolic arc is traced from the
+// right hand; on release the player fades out, moves to where the arc
+// landed if the ground there is flat enough, and fades back in
+#[derive(SystemDesc)]
+pub struct TeleportSystem;
+
+impl<'s> System<'s> for TeleportSystem {
+    type SystemData = (
+        Write<'s, TeleportState>,
+        WriteStorage<'s, Transform>,
+        WriteStorage<'s, CharacterController>,
+        WriteStorage<'s, UiTransform>,
+        WriteStorage<'s, UiImage>,
+        Write<'s, DebugLines>,
+        Read<'s, PlayerEntity>,
+        Read<'s, PlayerIntent>,
+        Read<'s, LocomotionConfig>,
+        Read<'s, CameraState>,
+        Read<'s, Physics>,
+        Read<'s, Time>,
+        Entities<'s>,
+        );
+
+    fn run(&mut self, (mut state, mut transforms, mut controllers, mut ui_transforms, mut ui_images, mut debug_lines, player_entity, intent, config, camera, physics, time, entities): Self::SystemData) {
+        let dt = time.delta_seconds();
+        let player = entities.entity(player_entity.index());
+
+        // Fade, moving the player while the screen is dark
+        let fade = state.fade;
+        let alpha = match fade {
+            Fade::None => 0.0,
+            Fade::Out(t) => {
+                let t = t + dt;
+                if t >= config.fade_time {
+                    if let (Some(destination), Some(transform)) = (state.destination.take(), transforms.get_mut(player)) {
+                        let eye_height = controllers.get(player).map_or(0.0, |c| c.eye_height);
+                        transform.set_translation(destination + Vector3::y() * eye_height);
+                    }
+                    if let Some(controller) = controllers.get_mut(player) {
+                        controller.vertical_speed = 0.0;
+                    }
+                    state.fade = Fade::In(0.0);
+                    1.0
+                } else {
+                    state.fade = Fade::Out(t);
+                    t / config.fade_time
+                }
+            },
+            Fade::In(t) => {
+                let t = t + dt;
+                state.fade = if t >= config.fade_time { Fade::None } else { Fade::In(t) };
+                1.0 - (t / config.fade_time).min(1.0)
+            },
+        };
+        set_overlay(&mut state, alpha, &mut ui_transforms, &mut ui_images, &entities);
+
+        if config.movement != MoveMode::Teleport || !camera.controls_player() {
+            state.aiming = false;
+            state.arc.clear();
+            state.target = None;
+            return;
+        }
+
+        if intent.teleport {
+            let origin = match transforms.get(player) {
+                Some(transform) => transform,
+                None => return,
+            };
+            let (start, direction) = aim(origin, &intent);
+            let (arc, target) = trace_arc(&physics, start, direction * config.launch_speed, config.max_slope);
+            for pair in arc.windows(2) {
+                let colour = match target {
+                    Some((_, true)) => Srgba::new(0.2, 0.8, 1.0, 1.0),
+                    _ => Srgba::new(1.0, 0.2, 0.2, 1.0),
+                };
+                debug_lines.draw_line(Point3::from(pair[0]), Point3::from(pair[1]), colour);
+            }
+
+            state.arc = arc;
+            state.target = target;
+            state.aiming = true;
+        } else if state.aiming {
+            state.aiming = false;
+            state.arc.clear();
+            if let Some((destination, true)) = state.target.take() {
+                if state.fade == Fade::None {
+                    state.destination = Some(destination);
+                    state.fade = Fade::Out(0.0);
+                }
+            }
+        }
+    }
+}
+
+// Start and direction of the arc: the right hand if it is tracked, otherwise the view
+fn aim(player: &Transform, intent: &PlayerIntent) -> (Vector3<f32>, Vector3<f32>) {
+    let pose = if intent.right.tracked { intent.right.pose } else { intent.head };
+    let rotation = player.rotation() * pose.orientation;
+
+    (world_position(player) + player.rotation() * pose.position, rotation * -Vector3::z())
+}
+
+// Simulates a projectile from `start` and returns the sampled arc and
+// where it lands, along with whether that spot is a valid destination
+pub fn trace_arc(physics: &Physics, start: Vector3<f32>, velocity: Vector3<f32>, max_slope: f32) -> (Vec<Vector3<f32>>, Option<(Vector3<f32>, bool)>) {
+    let solid = physics::filter(&[GROUP_TERRAIN, GROUP_PROPS]);
+    let mut arc = vec![start];
+    let mut position = start;
+    let mut velocity = velocity;
+
+    for _ in 0..ARC_STEPS {
+        velocity.y -= GRAVITY * ARC_STEP;
+        let motion = velocity * ARC_STEP;
+
+        if let Some(hit) = physics.cast_ray(physics::point(&position), physics::vector(&motion), 1.0, &solid) {
+            let landing = position + motion * hit.toi;
+            arc.push(landing);
+            let valid = hit.body == Body::Terrain && hit.normal.y >= max_slope.to_radians().cos();
+            return (arc, Some((landing, valid)));
+        }
+
+        position += motion;
+        arc.push(position);
+    }
+
+    (arc, None)
+}
+
+// Full-screen black image whose alpha follows the fade
+fn set_overlay(
+    state: &mut TeleportState,
+    alpha: f32,
+    ui_transforms: &mut WriteStorage<'_, UiTransform>,
+    ui_images: &mut WriteStorage<'_, UiImage>,
+    entities: &Entities<'_>,
+    ) {
+    let overlay = match state.overlay {
+        Some(overlay) if entities.is_alive(overlay) => overlay,
+        _ if alpha <= 0.0 => return,
+        _ => {
+            let overlay = entities.create();
+            let transform = UiTransform::new(
+                String::from("teleport_fade"), Anchor::Middle, Anchor::Middle,
+                0.0, 0.0, 100.0, 1.0, 1.0,
+            )
+            .into_percent()
+            .with_stretch(Stretch::XY { x_margin: 0.0, y_margin: 0.0, keep_aspect_ratio: false });
+            let _ = ui_transforms.insert(overlay, transform);
+            state.overlay = Some(overlay);
+            overlay
+        },
+    };
+
+    let _ = ui_images.insert(overlay, UiImage::SolidColor([0.0, 0.0, 0.0, alpha]));
+}
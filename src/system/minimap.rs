@@ -0,0 +1,227 @@
+use std::collections::HashMap;
+use crate::{
+    component::{Drone, ExtractionZone, Guard, Health, Loot, MissionTarget, Order, SquadMember},
+    fog::FogOfWar,
+    intent::PlayerIntent,
+    physics::Physics,
+    state::{MinimapTexture, PlayerEntity},
+    system::{camera::{CameraMode, CameraState}, hands::world_position},
+};
+use amethyst::{
+    core::{
+        math::Vector3,
+        transform::{Parent, Transform},
+    },
+    ecs::*,
+    shrev::{EventChannel, ReaderId},
+    ui::{Anchor, UiEvent, UiEventType, UiImage, UiTransform},
+    window::ScreenDimensions,
+};
+
+const MINIMAP: &str = "minimap";
+const MARKER_SIZE: f32 = 8.0;
+const ZONE_MARKER_SIZE: f32 = 14.0;
+const PLAYER_COLOUR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+const SQUAD_COLOUR: [f32; 4] = [0.2, 0.9, 0.3, 1.0];
+const GUARD_COLOUR: [f32; 4] = [0.9, 0.1, 0.1, 1.0];
+const OBJECTIVE_COLOUR: [f32; 4] = [1.0, 0.8, 0.1, 1.0];
+const EXTRACTION_COLOUR: [f32; 4] = [0.1, 0.8, 0.9, 0.8];
+// Units sent by a click spread out this far around the clicked point, in metres
+const FORMATION_SPACING: f32 = 1.5;
+
+/* Minimap system */
+// Shows the map's minimap in the HUD with a marker on top for every
+// squad member, guard in the squad's sight, objective and extraction
+// zone. In the commander view a click on a squad member's marker takes
+// that unit over in first-person, one on the player's marker returns to
+// the player's own body, and a click anywhere else on the minimap orders
+// the rest of the squad to move there. Units down, drones with an empty
+// battery and dogs and medics busy with their own orders stay put
+#[derive(Default)]
+pub struct MinimapSystem {
+    reader: Option<ReaderId<UiEvent>>,
+    // Marker UI entity of each marked world entity
+    markers: HashMap<Entity, Entity>,
+}
+
+impl<'s> System<'s> for MinimapSystem {
+    type SystemData = (
+        WriteStorage<'s, UiTransform>,
+        WriteStorage<'s, UiImage>,
+        WriteStorage<'s, Parent>,
+        WriteStorage<'s, SquadMember>,
+        ReadStorage<'s, Transform>,
+        ReadStorage<'s, Health>,
+        ReadStorage<'s, Guard>,
+        ReadStorage<'s, Loot>,
+        ReadStorage<'s, MissionTarget>,
+        ReadStorage<'s, ExtractionZone>,
+        ReadStorage<'s, Drone>,
+        Read<'s, EventChannel<UiEvent>>,
+        Option<Read<'s, MinimapTexture>>,
+        Option<Read<'s, FogOfWar>>,
+        Read<'s, Physics>,
+        Read<'s, PlayerEntity>,
+        Write<'s, CameraState>,
+        Read<'s, PlayerIntent>,
+        ReadExpect<'s, ScreenDimensions>,
+        Entities<'s>,
+        );
+
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+        self.reader = Some(world.fetch_mut::<EventChannel<UiEvent>>().register_reader());
+    }
+
+    fn run(&mut self, (mut ui_transforms, mut images, mut parents, mut squad, transforms, healths, guards, loot, targets, zones, drones, events, texture, fog, physics, player, mut camera, intent, screen, entities): Self::SystemData) {
+        let clicks: Vec<Entity> = events.read(self.reader.as_mut().unwrap())
+            .filter(|event| event.event_type == UiEventType::Click)
+            .map(|event| event.target)
+            .collect();
+
+        // UiFinder would borrow the UiTransforms this system creates, so the minimap is looked up here
+        let minimap = (&entities, &ui_transforms).join()
+            .find(|(_, transform)| transform.id == MINIMAP)
+            .map(|(entity, _)| entity);
+        let (minimap, texture, (min, max)) = match (minimap, texture, physics.terrain_bounds()) {
+            (Some(minimap), Some(texture), Some(bounds)) => (minimap, texture, bounds),
+            _ => return,
+        };
+        let image = UiImage::Texture(texture.handle());
+        if images.get(minimap) != Some(&image) {
+            let _ = images.insert(minimap, image);
+        }
+        let (width, height) = match ui_transforms.get(minimap) {
+            Some(transform) => (transform.width, transform.height),
+            None => return,
+        };
+        let extent = (max.x - min.x, max.z - min.z);
+
+        /* Markers */
+        let player = entities.entity(player.index());
+        let alive = |entity: Entity| !healths.get(entity).map_or(false, Health::is_dead);
+        let mut wanted: Vec<(Entity, Vector3<f32>, [f32; 4], f32)> = Vec::new();
+        for (entity, _, transform) in (&entities, &squad, &transforms).join() {
+            if !alive(entity) {
+                continue;
+            }
+            if entity == player {
+                let position = camera.body_position().unwrap_or_else(|| world_position(transform));
+                wanted.push((entity, position, PLAYER_COLOUR, MARKER_SIZE));
+            } else {
+                wanted.push((entity, world_position(transform), SQUAD_COLOUR, MARKER_SIZE));
+            }
+        }
+        for (entity, guard, transform) in (&entities, &guards, &transforms).join() {
+            let position = world_position(transform);
+            // Without a fog of war, every guard the squad has seen stays marked
+            let seen = fog.as_ref().map_or(guard.known, |fog| fog.is_visible(position.x, position.z));
+            if seen && alive(entity) {
+                wanted.push((entity, position, GUARD_COLOUR, MARKER_SIZE));
+            }
+        }
+        for (entity, _, transform) in (&entities, &loot, &transforms).join() {
+            wanted.push((entity, world_position(transform), OBJECTIVE_COLOUR, MARKER_SIZE));
+        }
+        for (entity, _, transform) in (&entities, &targets, &transforms).join() {
+            if alive(entity) {
+                wanted.push((entity, world_position(transform), OBJECTIVE_COLOUR, MARKER_SIZE));
+            }
+        }
+        for (entity, _, transform) in (&entities, &zones, &transforms).join() {
+            wanted.push((entity, world_position(transform), EXTRACTION_COLOUR, ZONE_MARKER_SIZE));
+        }
+
+        // Markers of entities no longer shown, or whose minimap went away with the HUD
+        let kept: Vec<Entity> = wanted.iter().map(|(entity, _, _, _)| *entity).collect();
+        let stale: Vec<Entity> = self.markers.iter()
+            .filter(|(entity, marker)| !kept.contains(entity) || parents.get(**marker).map(|p| p.entity) != Some(minimap))
+            .map(|(entity, _)| *entity)
+            .collect();
+        for entity in stale {
+            if let Some(marker) = self.markers.remove(&entity) {
+                let _ = entities.delete(marker);
+            }
+        }
+
+        for (entity, position, colour, size) in wanted {
+            // Image columns run along x and rows along z, from the top
+            let x = ((position.x - min.x) / extent.0 - 0.5) * width;
+            let y = (0.5 - (position.z - min.z) / extent.1) * height;
+            let marker = *self.markers.entry(entity).or_insert_with(|| {
+                let marker = entities.create();
+                let transform = UiTransform::new(
+                    format!("minimap_marker_{}", entity.id()), Anchor::Middle, Anchor::Middle,
+                    x, y, 1.0, size, size,
+                ).into_transparent();
+                let _ = ui_transforms.insert(marker, transform);
+                let _ = images.insert(marker, UiImage::SolidColor(colour));
+                let _ = parents.insert(marker, Parent { entity: minimap });
+                marker
+            });
+            if let Some(transform) = ui_transforms.get_mut(marker) {
+                transform.local_x = x;
+                transform.local_y = y;
+            }
+        }
+
+        /* Orders */
+        if camera.mode() != CameraMode::Commander || !clicks.contains(&minimap) {
+            return;
+        }
+        let (pointer, rect) = match (intent.pointer, ui_transforms.get(minimap)) {
+            (Some(pointer), Some(transform)) => (pointer, transform),
+            _ => return,
+        };
+        // UI pixels count up from the bottom of the window, the pointer down from the top
+        let u = (pointer.0 - rect.pixel_x()) / rect.pixel_width() + 0.5;
+        let v = 0.5 - (screen.height() - pointer.1 - rect.pixel_y()) / rect.pixel_height();
+        if u < 0.0 || u > 1.0 || v < 0.0 || v > 1.0 {
+            return;
+        }
+
+        let clicked = |position: Vector3<f32>| {
+            let x = ((position.x - min.x) / extent.0 - u) * width;
+            let y = ((position.z - min.z) / extent.1 - v) * height;
+            (x * x + y * y).sqrt() <= MARKER_SIZE
+        };
+        if camera.possessed().is_some() && camera.body_position().map_or(false, clicked) {
+            camera.release();
+            return;
+        }
+        // Drones have their own view, and downed units cannot be taken over
+        let possessed = (&entities, &mut squad, &transforms, !&drones).join()
+            .filter(|(entity, _, _, _)| *entity != player && !healths.get(*entity).map_or(false, Health::is_down))
+            .find(|(_, _, transform, _)| clicked(world_position(transform)));
+        if let Some((unit, member, _, _)) = possessed {
+            // The unit drops what it was doing, and stays where the player leaves it
+            member.order = Order::Hold;
+            camera.possess(unit);
+            return;
+        }
+
+        let target = Vector3::new(min.x + u * extent.0, 0.0, min.z + v * extent.1);
+
+        let mut members: Vec<&mut SquadMember> = (&entities, &mut squad).join()
+            .filter(|(entity, _)| *entity != player && camera.possessed() != Some(*entity))
+            .filter(|(entity, _)| !healths.get(*entity).map_or(false, Health::is_down))
+            .filter(|(entity, _)| !drones.get(*entity).map_or(false, Drone::is_empty))
+            .filter(|(_, member)| match member.order {
+                Order::Takedown(_) | Order::Bark(_) | Order::Revive(_) | Order::Heal(_) => false,
+                _ => true,
+            })
+            .map(|(_, member)| member)
+            .collect();
+        let count = members.len();
+        for (i, member) in members.iter_mut().enumerate() {
+            // The first unit takes the clicked point, the rest a ring around it
+            let offset = if i == 0 {
+                Vector3::zeros()
+            } else {
+                let angle = i as f32 / (count - 1) as f32 * std::f32::consts::PI * 2.0;
+                Vector3::new(angle.cos(), 0.0, angle.sin()) * FORMATION_SPACING
+            };
+            member.order = Order::MoveTo(target + offset);
+        }
+    }
+}
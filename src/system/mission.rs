@@ -0,0 +1,302 @@
+use crate::{
+    component::{Collider, Drone, ExtractionZone, Guard, Health, Loot, MissionTarget, SquadMember},
+    config::MissionConfig,
+    mission::{FailReason, Mission, ObjectiveKind, ObjectiveStatus},
+    physics::{self, Physics, GROUP_PROPS, GROUP_TERRAIN},
+    sky::weather::Weather,
+    state::PlayerEntity,
+    system::{
+        camera::CameraState,
+        guard::GUARD_EYE_HEIGHT,
+        hands::world_position,
+        health::{HealthEvent, HealthEventKind},
+    },
+};
+use amethyst::{
+    ecs::*,
+    core::{
+        math::{Point3, Vector3},
+        timing::Time,
+        transform::components::Transform,
+    },
+    derive::SystemDesc,
+    renderer::{debug_drawing::DebugLines, palette::Srgba},
+    shrev::{EventChannel, ReaderId},
+};
+use log::info;
+
+// Awareness drains this many times slower than it fills
+const AWARENESS_DECAY: f32 = 2.0;
+const ZONE_SEGMENTS: usize = 32;
+
+/* Loot system */
+// A living squad member coming close enough to loot steals it
+#[derive(SystemDesc)]
+pub struct LootSystem;
+
+impl<'s> System<'s> for LootSystem {
+    type SystemData = (
+        ReadStorage<'s, Loot>,
+        ReadStorage<'s, SquadMember>,
+        ReadStorage<'s, Transform>,
+        ReadStorage<'s, Collider>,
+        ReadStorage<'s, Health>,
+        ReadStorage<'s, Drone>,
+        Option<Write<'s, Mission>>,
+        Read<'s, MissionConfig>,
+        Read<'s, PlayerEntity>,
+        Read<'s, CameraState>,
+        Entities<'s>,
+        );
+
+    fn run(&mut self, (loot, squad, transforms, colliders, healths, drones, mission, config, player, camera, entities): Self::SystemData) {
+        let mut mission = match mission {
+            Some(mission) => mission,
+            None => return,
+        };
+        let members = squad_positions(&entities, &squad, &transforms, &colliders, &healths, &drones, &player, &camera);
+
+        for (entity, loot, transform) in (&entities, &loot, &transforms).join() {
+            let position = world_position(transform);
+            if members.iter().any(|member| (member - position).norm() < config.loot_radius) {
+                info!("Stole loot worth {}", loot.value);
+                mission.record_loot(loot.value);
+                let _ = entities.delete(entity);
+            }
+        }
+    }
+}
+
+/* Detection system */
+// Guards grow aware of squad members they can see within their view cone
+// and raise the alarm once fully aware. Terrain and props block the view,
+// both ways: a guard in sight of the squad becomes known to it. Fog and
+// rain shorten how far guards see
+#[derive(SystemDesc)]
+pub struct DetectionSystem;
+
+impl<'s> System<'s> for DetectionSystem {
+    type SystemData = (
+        WriteStorage<'s, Guard>,
+        ReadStorage<'s, SquadMember>,
+        ReadStorage<'s, Transform>,
+        ReadStorage<'s, Collider>,
+        ReadStorage<'s, Health>,
+        ReadStorage<'s, Drone>,
+        Option<Write<'s, Mission>>,
+        Option<Read<'s, Weather>>,
+        Read<'s, MissionConfig>,
+        Read<'s, Physics>,
+        Read<'s, PlayerEntity>,
+        Read<'s, CameraState>,
+        Read<'s, Time>,
+        Entities<'s>,
+        );
+
+    fn run(&mut self, (mut guards, squad, transforms, colliders, healths, drones, mission, weather, config, physics, player, camera, time, entities): Self::SystemData) {
+        let mut mission = match mission {
+            Some(mission) => mission,
+            None => return,
+        };
+        let dt = time.delta_seconds();
+        let members = squad_positions(&entities, &squad, &transforms, &colliders, &healths, &drones, &player, &camera);
+        let blocking = physics::filter(&[GROUP_TERRAIN, GROUP_PROPS]);
+        let half_angle = (config.view_angle * 0.5).to_radians();
+        let view_distance = config.view_distance * weather.map_or(1.0, |weather| weather.sight());
+
+        for (guard, transform, health) in (&mut guards, &transforms, healths.maybe()).join() {
+            if health.map_or(false, Health::is_down) {
+                continue;
+            }
+
+            let eye = world_position(transform) + Vector3::y() * GUARD_EYE_HEIGHT;
+            let forward = transform.rotation() * -Vector3::z();
+            let in_sight = |member: &Vector3<f32>| {
+                let to = member - eye;
+                to.norm() < view_distance
+                    && physics.cast_ray(physics::point(&eye), physics::vector(&to), 1.0, &blocking).is_none()
+            };
+            let sees = members.iter().any(|member| {
+                let to = member - eye;
+                (to.norm() <= 0.0 || forward.angle(&to) < half_angle) && in_sight(member)
+            });
+            if !guard.known && members.iter().any(in_sight) {
+                guard.known = true;
+            }
+
+            let rate = dt / config.detection_time.max(std::f32::EPSILON);
+            guard.awareness = if sees {
+                (guard.awareness + rate).min(1.0)
+            } else {
+                (guard.awareness - rate / AWARENESS_DECAY).max(0.0)
+            };
+
+            if guard.awareness >= 1.0 && !guard.alerted {
+                info!("A guard spotted the squad");
+                guard.alerted = true;
+                mission.record_detection();
+            } else if guard.awareness <= 0.0 {
+                guard.alerted = false;
+            }
+        }
+    }
+}
+
+/* Mission system */
+// Keeps the mission clock and the alarm, counts squad members that die,
+// notices the target's death and fails the raid when the player dies,
+// every other squad member is down or the alarm goes on for too long
+#[derive(Default)]
+pub struct MissionSystem {
+    reader: Option<ReaderId<HealthEvent>>,
+}
+
+impl<'s> System<'s> for MissionSystem {
+    type SystemData = (
+        ReadStorage<'s, SquadMember>,
+        ReadStorage<'s, MissionTarget>,
+        ReadStorage<'s, Guard>,
+        ReadStorage<'s, Health>,
+        Option<Write<'s, Mission>>,
+        Read<'s, EventChannel<HealthEvent>>,
+        Read<'s, MissionConfig>,
+        Read<'s, PlayerEntity>,
+        Read<'s, Time>,
+        Entities<'s>,
+        );
+
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+        self.reader = Some(world.fetch_mut::<EventChannel<HealthEvent>>().register_reader());
+    }
+
+    fn run(&mut self, (squad, targets, guards, healths, mission, events, config, player, time, entities): Self::SystemData) {
+        let deaths: Vec<Entity> = events.read(self.reader.as_mut().unwrap())
+            .filter(|event| event.kind == HealthEventKind::Died)
+            .map(|event| event.entity)
+            .collect();
+        let mut mission = match mission {
+            Some(mission) => mission,
+            None => return,
+        };
+        if mission.is_over() {
+            return;
+        }
+        let dt = time.delta_seconds();
+        mission.tick(dt);
+
+        for entity in deaths {
+            if squad.contains(entity) {
+                info!("A squad member fell");
+                mission.record_casualty();
+            }
+        }
+        // The player's own death is a failure of its own, so the squad is lost once everyone else is down
+        let player = entities.entity(player.index());
+        let (others, standing) = (&entities, &squad, healths.maybe()).join()
+            .filter(|(entity, _, _)| *entity != player)
+            .fold((0, 0), |(others, standing), (_, _, health)| {
+                (others + 1, standing + if health.map_or(false, Health::is_down) { 0 } else { 1 })
+            });
+
+        let target_down = (&targets, &healths).join().any(|(_, health)| health.is_down());
+        if target_down && mission.status(ObjectiveKind::EliminateTarget) == Some(ObjectiveStatus::Active) {
+            info!("Target eliminated");
+            mission.record_target_eliminated();
+        }
+
+        let raised = (&guards, healths.maybe()).join()
+            .any(|(guard, health)| guard.alerted && !health.map_or(false, Health::is_down));
+        mission.record_alarm(raised, dt);
+
+        let player_dead = healths.get(player).map_or(false, Health::is_dead);
+        if player_dead {
+            info!("The player was killed");
+            mission.record_failure(FailReason::PlayerKilled);
+        } else if others > 0 && standing == 0 {
+            info!("The rest of the squad was lost");
+            mission.record_failure(FailReason::SquadLost);
+        } else if mission.alarm() >= config.alarm_timeout {
+            info!("The alarm lasted {:.0} seconds", mission.alarm());
+            mission.record_failure(FailReason::AlarmTimeout);
+        }
+    }
+}
+
+/* Extraction system */
+// Ends the raid once every required objective is done and every living
+// squad member stands in the same extraction zone. The zones are drawn
+// as rings, which light up once the squad may leave
+#[derive(SystemDesc)]
+pub struct ExtractionSystem;
+
+impl<'s> System<'s> for ExtractionSystem {
+    type SystemData = (
+        ReadStorage<'s, ExtractionZone>,
+        ReadStorage<'s, SquadMember>,
+        ReadStorage<'s, Transform>,
+        ReadStorage<'s, Collider>,
+        ReadStorage<'s, Health>,
+        ReadStorage<'s, Drone>,
+        Option<Write<'s, Mission>>,
+        Read<'s, PlayerEntity>,
+        Read<'s, CameraState>,
+        Write<'s, DebugLines>,
+        Entities<'s>,
+        );
+
+    fn run(&mut self, (zones, squad, transforms, colliders, healths, drones, mission, player, camera, mut debug_lines, entities): Self::SystemData) {
+        let mut mission = match mission {
+            Some(mission) => mission,
+            None => return,
+        };
+        let ready = mission.ready_to_extract();
+        let members = squad_positions(&entities, &squad, &transforms, &colliders, &healths, &drones, &player, &camera);
+
+        for (zone, transform) in (&zones, &transforms).join() {
+            let centre = world_position(transform);
+            let colour = if ready { Srgba::new(0.2, 1.0, 0.3, 1.0) } else { Srgba::new(0.6, 0.6, 0.6, 1.0) };
+            let point = |i: usize| {
+                let angle = i as f32 / ZONE_SEGMENTS as f32 * std::f32::consts::PI * 2.0;
+                Point3::new(centre.x + angle.cos() * zone.radius, centre.y + 0.2, centre.z + angle.sin() * zone.radius)
+            };
+            for i in 0..ZONE_SEGMENTS {
+                debug_lines.draw_line(point(i), point(i + 1), colour);
+            }
+
+            let inside = members.iter()
+                .all(|member| ((member.x - centre.x).powi(2) + (member.z - centre.z).powi(2)).sqrt() <= zone.radius);
+            if ready && inside && !members.is_empty() && !mission.is_over() {
+                info!("Squad extracted after {:.0} seconds", mission.elapsed());
+                mission.record_extraction();
+            }
+        }
+    }
+}
+
+// Centres of the living squad members. The player's transform follows the
+// camera in the other views, so the body's last position stands in then.
+// Downed members count, as they cannot be left behind, but drones with an
+// empty battery are left behind and do not count
+fn squad_positions(
+    entities: &Entities,
+    squad: &ReadStorage<SquadMember>,
+    transforms: &ReadStorage<Transform>,
+    colliders: &ReadStorage<Collider>,
+    healths: &ReadStorage<Health>,
+    drones: &ReadStorage<Drone>,
+    player: &PlayerEntity,
+    camera: &CameraState,
+    ) -> Vec<Vector3<f32>> {
+    (entities, squad, transforms, colliders.maybe(), healths.maybe()).join()
+        .filter(|(_, _, _, _, health)| !health.map_or(false, Health::is_dead))
+        .filter(|(entity, _, _, _, _)| !drones.get(*entity).map_or(false, Drone::is_empty))
+        .map(|(entity, _, transform, collider, _)| {
+            let position = match camera.body_position() {
+                Some(body) if entity.id() == player.index() => body,
+                _ => world_position(transform),
+            };
+            position + Vector3::y() * collider.map_or(0.0, |collider| collider.offset)
+        })
+        .collect()
+}
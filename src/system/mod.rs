@@ -1 +1,18 @@
+pub mod camera;
 pub mod control;
+pub mod fog;
+pub mod footstep;
+pub mod guard;
+pub mod hands;
+pub mod health;
+pub mod hud;
+pub mod intent;
+pub mod locomotion;
+pub mod minimap;
+pub mod mission;
+pub mod physics;
+pub mod route;
+pub mod sky;
+pub mod squad;
+pub mod vr;
+pub mod world_ui;
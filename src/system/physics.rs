@@ -0,0 +1,46 @@
+use crate::{
+    component::Collider,
+    physics::{self, Physics},
+    system::hands::world_position,
+};
+use amethyst::{
+    ecs::*,
+    core::{
+        transform::components::Transform,
+    },
+    derive::SystemDesc,
+};
+
+/* Collider sync system */
+// Moves unit capsules to follow their transforms, drops the
+// capsules of deleted entities, then updates the collision world
+#[derive(SystemDesc)]
+pub struct ColliderSyncSystem;
+
+impl<'s> System<'s> for ColliderSyncSystem {
+    type SystemData = (
+        Write<'s, Physics>,
+        ReadStorage<'s, Transform>,
+        ReadStorage<'s, Collider>,
+        Entities<'s>,
+        );
+
+    fn run(&mut self, (mut physics, transforms, colliders, entities): Self::SystemData) {
+        for (entity, transform, collider) in (&entities, &transforms, &colliders).join() {
+            // Held objects are parented to a hand, so use the world position
+            let mut center = physics::point(&world_position(transform));
+            center.y += collider.offset;
+            physics.sync_unit(entity, center, collider.radius, collider.half_height);
+        }
+
+        let stale: Vec<Entity> = physics.units()
+            .filter(|entity| !entities.is_alive(**entity) || !colliders.contains(**entity))
+            .cloned()
+            .collect();
+        for entity in stale {
+            physics.remove_unit(entity);
+        }
+
+        physics.update();
+    }
+}
@@ -0,0 +1,96 @@
+use crate::{
+    component::Collider,
+    gen::navmesh::NavMesh,
+    physics::Physics,
+    state::UNIT_CENTRE_HEIGHT,
+};
+use amethyst::core::{math::Vector3, transform::components::Transform};
+
+/* Route */
+// Planned walk of a unit over the navigation grid: the goal it was planned
+// for and the waypoints on the ground still ahead, nearest last
+pub struct Route {
+    pub goal: Vector3<f32>,
+    waypoints: Vec<Vector3<f32>>,
+}
+
+impl Route {
+    // Route from `from` to `goal`, or to the walkable ground closest to it
+    // when `goal` itself is not walkable; None when the unit cannot get
+    // any closer
+    pub fn plan(nav: &NavMesh, from: Vector3<f32>, goal: Vector3<f32>) -> Option<Route> {
+        let start = nav.cell_at(from.x, from.z)
+            .filter(|&cell| nav.is_walkable(cell))
+            .or_else(|| nav.nearest_walkable(from.x, from.z))?;
+        let goal_cell = nav.cell_at(goal.x, goal.z).filter(|&cell| nav.is_walkable(cell));
+        let end = goal_cell.or_else(|| nav.nearest_walkable(goal.x, goal.z))?;
+
+        let mut waypoints: Vec<Vector3<f32>> = nav.find_path(start, end)?
+            .into_iter()
+            // The unit already stands in the first cell
+            .skip(1)
+            .map(|cell| {
+                let [x, y, z] = nav.position(cell);
+                Vector3::new(x, y, z)
+            })
+            .collect();
+        // Cell centres can lie further from the goal than units stop at, so the last step is to the goal itself
+        if goal_cell.is_some() {
+            waypoints.push(goal);
+        }
+        if waypoints.is_empty() {
+            return None;
+        }
+        waypoints.reverse();
+
+        Some(Route {
+            goal: goal,
+            waypoints: waypoints,
+        })
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.waypoints.is_empty()
+    }
+
+    // Moves a unit standing at `feet` up to `step` metres along the route,
+    // keeping its transform `centre` metres above the ground and facing
+    // the way it walks
+    pub fn walk(&mut self, transform: &mut Transform, feet: Vector3<f32>, mut step: f32, centre: f32, physics: &Physics) {
+        let mut position = feet;
+        while let Some(&waypoint) = self.waypoints.last() {
+            let to = Vector3::new(waypoint.x - position.x, 0.0, waypoint.z - position.z);
+            let distance = to.norm();
+            if distance <= step {
+                position = waypoint;
+                step -= distance;
+                self.waypoints.pop();
+                continue;
+            }
+
+            position += to / distance * step;
+            // Units look along -z
+            transform.set_rotation_y_axis((-to.x).atan2(-to.z));
+            break;
+        }
+
+        let y = physics.ground_height(position.x, position.z).unwrap_or(position.y);
+        transform.set_translation_xyz(position.x, y + centre, position.z);
+    }
+}
+
+// Point on the ground below `position`
+pub fn ground(physics: &Physics, position: Vector3<f32>) -> Vector3<f32> {
+    let y = physics.ground_height(position.x, position.z).unwrap_or(position.y);
+    Vector3::new(position.x, y, position.z)
+}
+
+// Height of a unit's transform above its feet that puts the bottom of its capsule on the ground
+pub fn centre_height(collider: Option<&Collider>) -> f32 {
+    collider.map_or(UNIT_CENTRE_HEIGHT, |collider| collider.radius + collider.half_height - collider.offset)
+}
+
+// Distance between two points on the (x, z) plane
+pub fn distance_xz(a: &Vector3<f32>, b: &Vector3<f32>) -> f32 {
+    ((a.x - b.x).powi(2) + (a.z - b.z).powi(2)).sqrt()
+}
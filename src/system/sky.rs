@@ -0,0 +1,187 @@
+use crate::{
+    component::Sun,
+    config::CameraConfig,
+    sky::{weather::Weather, TimeOfDay},
+    state::SceneVertices,
+    system::camera::{CameraMode, CameraState},
+    vr::{panel, VrRig},
+};
+use amethyst::{
+    assets::{AssetStorage, Handle, Loader},
+    core::{
+        math::Vector3,
+        timing::Time,
+        transform::{Parent, Transform},
+    },
+    derive::SystemDesc,
+    ecs::*,
+    renderer::{
+        camera::Camera,
+        light::Light,
+        palette::{LinSrgba, Srgb, Srgba},
+        resources::{AmbientColor, Tint},
+        shape::Shape,
+        transparent::Transparent,
+        Material, MaterialDefaults, Mesh, Texture,
+    },
+};
+
+// Sheets of fog hung in front of the view, and the fraction of the
+// visibility the first one hangs at
+const FOG_SHEETS: usize = 12;
+const FOG_START: f32 = 0.3;
+// Half the width of a sheet over its distance, wide enough to fill any field of view
+const FOG_SPREAD: f32 = 3.0;
+// How far past the last sheet the far plane sits, as a multiple of its distance
+pub const FOG_FAR_MARGIN: f32 = 1.1;
+
+/* Day and night system */
+// Runs the raid's clock and moves the sun with it, changing its colour
+// and strength along with the ambient light. The sky's colour is picked
+// up by the renderer straight from the time of day
+#[derive(SystemDesc)]
+pub struct DayNightSystem;
+
+impl<'s> System<'s> for DayNightSystem {
+    type SystemData = (
+        WriteStorage<'s, Light>,
+        ReadStorage<'s, Sun>,
+        Option<Write<'s, TimeOfDay>>,
+        Write<'s, AmbientColor>,
+        Read<'s, Time>,
+        );
+
+    fn run(&mut self, (mut lights, suns, time_of_day, mut ambient, time): Self::SystemData) {
+        let mut time_of_day = match time_of_day {
+            Some(time_of_day) => time_of_day,
+            None => return,
+        };
+        time_of_day.advance(time.delta_seconds());
+
+        let [x, y, z] = time_of_day.sun_direction();
+        let [r, g, b] = time_of_day.sun_colour();
+        for (light, _) in (&mut lights, &suns).join() {
+            if let Light::Directional(sun) = light {
+                sun.direction = Vector3::new(x, y, z);
+                sun.color = Srgb::new(r, g, b);
+                sun.intensity = time_of_day.sun_intensity();
+            }
+        }
+
+        let [r, g, b] = time_of_day.ambient_colour();
+        ambient.0 = Srgba::new(r, g, b, 1.0);
+    }
+}
+
+/* Weather system */
+// Fades the scene into the sky's washed out colour with distance. Sheets
+// tinted like the sky hang in front of the view, each hiding a little
+// more of what lies behind it, so fog starts at `FOG_START` of the
+// visibility and nothing shows through past it. The far plane sits just
+// beyond the last sheet. The commander view looks down through the fog
+// from above it, so it sees as far again as it can be from the ground
+#[derive(Default)]
+pub struct WeatherSystem {
+    sheets: Vec<Entity>,
+}
+
+impl<'s> System<'s> for WeatherSystem {
+    type SystemData = (
+        WriteStorage<'s, Camera>,
+        WriteStorage<'s, Transform>,
+        WriteStorage<'s, Parent>,
+        WriteStorage<'s, Tint>,
+        WriteStorage<'s, Handle<Mesh>>,
+        WriteStorage<'s, Handle<Material>>,
+        WriteStorage<'s, Transparent>,
+        Option<Read<'s, Weather>>,
+        Option<Read<'s, TimeOfDay>>,
+        Option<Read<'s, VrRig>>,
+        Read<'s, CameraState>,
+        Read<'s, CameraConfig>,
+        ReadExpect<'s, Loader>,
+        Read<'s, AssetStorage<Mesh>>,
+        Read<'s, AssetStorage<Texture>>,
+        Read<'s, AssetStorage<Material>>,
+        ReadExpect<'s, MaterialDefaults>,
+        Entities<'s>,
+        );
+
+    fn run(&mut self, (
+        mut cameras, mut transforms, mut parents, mut tints, mut meshes, mut materials, mut transparent,
+        weather, time_of_day, rig, camera, config, loader, mesh_storage, texture_storage,
+        material_storage, material_defaults, entities,
+        ): Self::SystemData) {
+        self.sheets.retain(|sheet| entities.is_alive(*sheet));
+        let (weather, time_of_day) = match (weather, time_of_day) {
+            (Some(weather), Some(time_of_day)) => (weather, time_of_day),
+            _ => return,
+        };
+        let visibility = match weather.visibility() {
+            Some(visibility) => visibility,
+            None => return,
+        };
+        // The headset's eyes are set up by VrRigSystem, with the same far plane
+        let anchor = match rig {
+            Some(rig) => rig.head,
+            None => match (&entities, &cameras).join().next() {
+                Some((entity, _)) => entity,
+                None => return,
+            },
+        };
+        let offset = match camera.mode() {
+            CameraMode::Commander if rig.is_none() => config.max_distance,
+            _ => 0.0,
+        };
+
+        if rig.is_none() {
+            let far = (offset + visibility) * FOG_FAR_MARGIN;
+            for view in (&mut cameras).join() {
+                if let Some(perspective) = view.projection_mut().as_perspective_mut() {
+                    if (perspective.far() - far).abs() > std::f32::EPSILON {
+                        perspective.set_far(far);
+                    }
+                }
+            }
+        }
+
+        if self.sheets.len() != FOG_SHEETS {
+            for sheet in self.sheets.drain(..) {
+                let _ = entities.delete(sheet);
+            }
+            let mesh = loader.load_from_data(Shape::Plane(None).generate::<SceneVertices>(None).into(), (), &mesh_storage);
+            // Black, so the sun leaves it alone, glowing white for the tint to colour
+            let material = Material {
+                albedo: loader.load_from_data(panel::solid_texture([0.0, 0.0, 0.0, 1.0]), (), &texture_storage),
+                emission: loader.load_from_data(panel::solid_texture([1.0; 4]), (), &texture_storage),
+                ..material_defaults.0.clone()
+            };
+            let material = loader.load_from_data(material, (), &material_storage);
+            for _ in 0..FOG_SHEETS {
+                let sheet = entities.create();
+                let _ = transforms.insert(sheet, Transform::default());
+                let _ = meshes.insert(sheet, mesh.clone());
+                let _ = materials.insert(sheet, material.clone());
+                let _ = transparent.insert(sheet, Transparent);
+                self.sheets.push(sheet);
+            }
+        }
+
+        // Sheet `i` of `n` hides 1 / (n - i) of what is left, so the fog thickens evenly with distance
+        let [r, g, b, _] = weather.tint(time_of_day.sky_colour());
+        for (i, sheet) in self.sheets.iter().enumerate() {
+            let distance = offset + visibility * (FOG_START + (1.0 - FOG_START) * (i + 1) as f32 / FOG_SHEETS as f32);
+            let opacity = 1.0 / (FOG_SHEETS - i) as f32;
+
+            if parents.get(*sheet).map_or(true, |parent| parent.entity != anchor) {
+                let _ = parents.insert(*sheet, Parent { entity: anchor });
+            }
+            if let Some(transform) = transforms.get_mut(*sheet) {
+                transform.set_translation_xyz(0.0, 0.0, -distance);
+                transform.set_scale(Vector3::new(distance * FOG_SPREAD, distance * FOG_SPREAD, 1.0));
+            }
+            // The sky is cleared to in linear colour
+            let _ = tints.insert(*sheet, Tint(Srgba::from_linear(LinSrgba::new(r, g, b, opacity))));
+        }
+    }
+}
@@ -0,0 +1,442 @@
+use std::collections::HashMap;
+use crate::{
+    component::{Collider, Dog, Drone, Guard, Health, Medic, Order, SquadMember},
+    config::{DroneConfig, MovementConfig},
+    gen::navmesh::NavMesh,
+    intent::PlayerIntent,
+    physics::Physics,
+    state::PlayerEntity,
+    system::{
+        camera::CameraState,
+        guard::NoiseEvent,
+        hands::world_position,
+        health::{HealthEvent, HealthEventKind},
+        route::{self, centre_height, distance_xz, Route},
+    },
+};
+use amethyst::{
+    ecs::*,
+    core::{
+        math::Vector3,
+        timing::Time,
+        transform::components::Transform,
+    },
+    derive::SystemDesc,
+    shrev::EventChannel,
+};
+use log::info;
+
+// Followers stop once this close to the player, in metres
+const FOLLOW_DISTANCE: f32 = 3.0;
+// A unit plans a new route once its goal has moved this far from the old one
+const REPLAN_DISTANCE: f32 = 2.0;
+// Units sent somewhere count as arrived within this distance of it
+const ARRIVE_DISTANCE: f32 = 0.3;
+// Dogs close in on a guard or a spot to bark at to within this distance
+const REACH_DISTANCE: f32 = 1.0;
+// A landed drone rests with its centre this far above the ground
+const LANDED_HEIGHT: f32 = 0.2;
+
+/* Squad order system */
+// Walks squad members other than the player, the unit the player
+// possesses and drones along the navigation grid to carry out their orders. Units sent somewhere hold
+// once they arrive, followers keep a few metres behind the player, and
+// dogs and medics close in on the unit or spot their order names
+#[derive(Default)]
+pub struct OrderSystem {
+    routes: HashMap<Entity, Route>,
+}
+
+impl<'s> System<'s> for OrderSystem {
+    type SystemData = (
+        WriteStorage<'s, SquadMember>,
+        WriteStorage<'s, Transform>,
+        ReadStorage<'s, Health>,
+        ReadStorage<'s, Collider>,
+        ReadStorage<'s, Drone>,
+        Option<Read<'s, NavMesh>>,
+        Read<'s, Physics>,
+        Read<'s, MovementConfig>,
+        Read<'s, PlayerEntity>,
+        Read<'s, CameraState>,
+        Read<'s, Time>,
+        Entities<'s>,
+        );
+
+    fn run(&mut self, (mut squad, mut transforms, healths, colliders, drones, nav, physics, config, player, camera, time, entities): Self::SystemData) {
+        let nav = match nav {
+            Some(nav) => nav,
+            None => return,
+        };
+        let dt = time.delta_seconds();
+        let player = entities.entity(player.index());
+        let leader = leader_position(&transforms, player, &camera, &physics);
+        // Units that orders name, which the loop below cannot look up while it moves the squad
+        let targets: HashMap<Entity, Vector3<f32>> = (&squad).join()
+            .filter_map(|member| match member.order {
+                Order::Takedown(target) | Order::Revive(target) | Order::Heal(target) => Some(target),
+                _ => None,
+            })
+            .filter_map(|target| {
+                let position = if target == player { leader } else { transforms.get(target).map(|t| route::ground(&physics, world_position(t))) };
+                position.map(|position| (target, position))
+            })
+            .collect();
+
+        self.routes.retain(|entity, _| entities.is_alive(*entity));
+
+        for (entity, member, transform, health, collider, _) in (&entities, &mut squad, &mut transforms, healths.maybe(), colliders.maybe(), !&drones).join() {
+            if entity == player || camera.possessed() == Some(entity) || health.map_or(false, Health::is_down) {
+                self.routes.remove(&entity);
+                continue;
+            }
+
+            let feet = route::ground(&physics, world_position(transform));
+            // Where the order takes the unit, and how close it needs to get
+            let goal = match member.order {
+                Order::Hold => None,
+                Order::Follow => leader.map(|leader| (leader, FOLLOW_DISTANCE)),
+                Order::MoveTo(target) => Some((target, ARRIVE_DISTANCE)),
+                Order::Bark(spot) => Some((spot, REACH_DISTANCE)),
+                Order::Takedown(target) | Order::Revive(target) | Order::Heal(target) =>
+                    targets.get(&target).map(|position| (*position, REACH_DISTANCE)),
+            };
+            let (goal, stop) = match goal {
+                Some(goal) => goal,
+                None => {
+                    self.routes.remove(&entity);
+                    continue;
+                },
+            };
+            if distance_xz(&goal, &feet) <= stop {
+                self.routes.remove(&entity);
+                if let Order::MoveTo(_) = member.order {
+                    member.order = Order::Hold;
+                }
+                continue;
+            }
+
+            let stale = self.routes.get(&entity).map_or(true, |route| (route.goal - goal).norm() > REPLAN_DISTANCE);
+            if stale {
+                match Route::plan(&nav, feet, goal) {
+                    Some(route) => {
+                        self.routes.insert(entity, route);
+                    },
+                    None => {
+                        // As close as the ground allows; nothing more to do for this order
+                        self.routes.remove(&entity);
+                        if member.order != Order::Follow {
+                            member.order = Order::Hold;
+                        }
+                        continue;
+                    },
+                }
+            }
+
+            let route = self.routes.get_mut(&entity).unwrap();
+            let speed = member.speed.unwrap_or(config.walk_speed);
+            route.walk(transform, feet, speed * dt, centre_height(collider), &physics);
+            if route.is_finished() {
+                self.routes.remove(&entity);
+            }
+        }
+    }
+}
+
+/* Dog system */
+// Dogs smell the guards around them, walls or not, and track the closest
+// one. On command every dog goes after the guard it tracks and brings it
+// down without a sound once it has been close enough for long enough, or
+// barks where it stands, which guards within earshot come to investigate.
+// A dog the player possesses only follows the player's lead
+#[derive(Default)]
+pub struct DogSystem {
+    takedown_held: bool,
+    bark_held: bool,
+}
+
+impl<'s> System<'s> for DogSystem {
+    type SystemData = (
+        WriteStorage<'s, Dog>,
+        WriteStorage<'s, SquadMember>,
+        WriteStorage<'s, Health>,
+        WriteStorage<'s, Guard>,
+        ReadStorage<'s, Transform>,
+        Write<'s, EventChannel<NoiseEvent>>,
+        Read<'s, PlayerIntent>,
+        Read<'s, CameraState>,
+        Read<'s, Time>,
+        Entities<'s>,
+        );
+
+    fn run(&mut self, (mut dogs, mut squad, mut healths, mut guards, transforms, mut noises, intent, camera, time, entities): Self::SystemData) {
+        let dt = time.delta_seconds();
+        let takedown = intent.dog_takedown && !self.takedown_held;
+        self.takedown_held = intent.dog_takedown;
+        let bark = intent.dog_bark && !self.bark_held;
+        self.bark_held = intent.dog_bark;
+
+        let prey: Vec<(Entity, Vector3<f32>)> = (&entities, &guards, &transforms).join()
+            .filter(|(entity, _, _)| !healths.get(*entity).map_or(false, Health::is_down))
+            .map(|(entity, _, transform)| (entity, world_position(transform)))
+            .collect();
+        let mut smelled = Vec::new();
+        let mut downed = Vec::new();
+
+        for (entity, dog, member, transform) in (&entities, &mut dogs, &mut squad, &transforms).join() {
+            if camera.possessed() == Some(entity) || healths.get(entity).map_or(false, Health::is_down) {
+                continue;
+            }
+            let position = world_position(transform);
+            dog.cooldown = (dog.cooldown - dt).max(0.0);
+
+            let scents: Vec<&(Entity, Vector3<f32>)> = prey.iter()
+                .filter(|(_, guard)| distance_xz(guard, &position) <= dog.traits.scent_radius)
+                .collect();
+            smelled.extend(scents.iter().map(|(guard, _)| *guard));
+            dog.scent = scents.iter()
+                .min_by(|(_, a), (_, b)| distance_xz(a, &position).partial_cmp(&distance_xz(b, &position)).unwrap())
+                .map(|(guard, _)| *guard);
+
+            if takedown {
+                if let Some(target) = dog.scent {
+                    member.order = Order::Takedown(target);
+                    dog.takedown = 0.0;
+                }
+            }
+            if bark && dog.cooldown <= 0.0 {
+                member.order = Order::Bark(position);
+            }
+
+            match member.order {
+                Order::Takedown(target) => match prey.iter().find(|(guard, _)| *guard == target) {
+                    Some((_, guard)) if distance_xz(guard, &position) <= dog.traits.takedown_range => {
+                        dog.takedown += dt;
+                        if dog.takedown >= dog.traits.takedown_time {
+                            downed.push(target);
+                            member.order = Order::Hold;
+                            dog.takedown = 0.0;
+                        }
+                    },
+                    Some(_) => dog.takedown = 0.0,
+                    // Already down, or gone
+                    None => member.order = Order::Hold,
+                },
+                Order::Bark(spot) if distance_xz(&spot, &position) <= REACH_DISTANCE && dog.cooldown <= 0.0 => {
+                    noises.single_write(NoiseEvent { position: position, radius: dog.traits.bark_radius });
+                    dog.cooldown = dog.traits.bark_cooldown;
+                    member.order = Order::Hold;
+                },
+                _ => {},
+            }
+        }
+
+        for guard in smelled {
+            if let Some(guard) = guards.get_mut(guard) {
+                guard.known = true;
+            }
+        }
+        for target in downed {
+            if let Some(health) = healths.get_mut(target) {
+                info!("A dog took a guard down");
+                let max = health.max;
+                health.damage(max);
+            }
+        }
+    }
+}
+
+/* Medic system */
+// On command every medic goes to the closest downed squad member and gets
+// them back up after working on them for a while, or treats the squad
+// member with the least health left, who then heals over time. A medic
+// the player possesses leaves the squad to the other medics
+#[derive(Default)]
+pub struct MedicSystem {
+    revive_held: bool,
+    heal_held: bool,
+}
+
+impl<'s> System<'s> for MedicSystem {
+    type SystemData = (
+        WriteStorage<'s, Medic>,
+        WriteStorage<'s, SquadMember>,
+        WriteStorage<'s, Health>,
+        ReadStorage<'s, Transform>,
+        ReadStorage<'s, Drone>,
+        Write<'s, EventChannel<HealthEvent>>,
+        Read<'s, PlayerIntent>,
+        Read<'s, PlayerEntity>,
+        Read<'s, CameraState>,
+        Read<'s, Time>,
+        Entities<'s>,
+        );
+
+    fn run(&mut self, (mut medics, mut squad, mut healths, transforms, drones, mut events, intent, player, camera, time, entities): Self::SystemData) {
+        let dt = time.delta_seconds();
+        let revive = intent.medic_revive && !self.revive_held;
+        self.revive_held = intent.medic_revive;
+        let heal = intent.medic_heal && !self.heal_held;
+        self.heal_held = intent.medic_heal;
+
+        // Squad members a medic can treat, where their bodies are
+        let patients: Vec<(Entity, Vector3<f32>)> = (&entities, &squad, &transforms, !&drones).join()
+            .map(|(entity, _, transform, _)| {
+                let position = match camera.body_position() {
+                    Some(body) if entity.id() == player.index() => body,
+                    _ => world_position(transform),
+                };
+                (entity, position)
+            })
+            .collect();
+
+        for (entity, medic, member, transform) in (&entities, &mut medics, &mut squad, &transforms).join() {
+            if camera.possessed() == Some(entity) || healths.get(entity).map_or(false, Health::is_down) {
+                continue;
+            }
+            let position = world_position(transform);
+            let distance = |patient: &Vector3<f32>| distance_xz(patient, &position);
+
+            if revive {
+                let downed = patients.iter()
+                    .filter(|(patient, _)| healths.get(*patient).map_or(false, Health::is_downed))
+                    .min_by(|(_, a), (_, b)| distance(a).partial_cmp(&distance(b)).unwrap());
+                if let Some((patient, _)) = downed {
+                    member.order = Order::Revive(*patient);
+                    medic.progress = 0.0;
+                }
+            }
+            if heal {
+                let fraction = |patient: Entity| healths.get(patient).map_or(1.0, |health| health.current / health.max);
+                let injured = patients.iter()
+                    .filter(|(patient, _)| healths.get(*patient).map_or(false, Health::is_injured))
+                    .min_by(|(a, _), (b, _)| fraction(*a).partial_cmp(&fraction(*b)).unwrap());
+                if let Some((patient, _)) = injured {
+                    member.order = Order::Heal(*patient);
+                }
+            }
+
+            match member.order {
+                Order::Revive(target) => {
+                    let patient = patients.iter().find(|(patient, _)| *patient == target);
+                    let health = healths.get_mut(target).filter(|health| health.is_downed());
+                    match (patient, health) {
+                        (Some((_, at)), Some(health)) if distance(at) <= medic.traits.reach => {
+                            medic.progress += dt;
+                            if medic.progress >= medic.traits.revive_time {
+                                info!("A medic revived a squad member");
+                                health.revive(medic.traits.revive_health);
+                                member.order = Order::Hold;
+                                medic.progress = 0.0;
+                            }
+                        },
+                        (Some(_), Some(_)) => medic.progress = 0.0,
+                        // Back up already, bled out or gone
+                        _ => member.order = Order::Hold,
+                    }
+                },
+                Order::Heal(target) => {
+                    let patient = patients.iter().find(|(patient, _)| *patient == target);
+                    let health = healths.get_mut(target).filter(|health| health.is_injured());
+                    match (patient, health) {
+                        (Some((_, at)), Some(health)) if distance(at) <= medic.traits.reach => {
+                            health.heal_over_time(medic.traits.heal_rate, medic.traits.heal_time);
+                            events.single_write(HealthEvent { entity: target, kind: HealthEventKind::Healed });
+                            member.order = Order::Hold;
+                        },
+                        (Some(_), Some(_)) => {},
+                        _ => member.order = Order::Hold,
+                    }
+                },
+                _ => {},
+            }
+        }
+    }
+}
+
+/* Drone system */
+// Flies drones at their altitude above the ground: steered by the player
+// while they look through the drone's camera, otherwise straight towards
+// where the drone's order takes it. Flying drains the battery and a drone
+// with an empty one lands where it is
+#[derive(SystemDesc)]
+pub struct DroneSystem;
+
+impl<'s> System<'s> for DroneSystem {
+    type SystemData = (
+        WriteStorage<'s, Drone>,
+        WriteStorage<'s, SquadMember>,
+        WriteStorage<'s, Transform>,
+        ReadStorage<'s, Health>,
+        Read<'s, Physics>,
+        Read<'s, DroneConfig>,
+        Read<'s, PlayerEntity>,
+        Read<'s, CameraState>,
+        Read<'s, PlayerIntent>,
+        Read<'s, Time>,
+        Entities<'s>,
+        );
+
+    fn run(&mut self, (mut drones, mut squad, mut transforms, healths, physics, config, player, camera, intent, time, entities): Self::SystemData) {
+        let bounds = match physics.terrain_bounds() {
+            Some(bounds) => bounds,
+            None => return,
+        };
+        let dt = time.delta_seconds();
+        let player = entities.entity(player.index());
+        let leader = leader_position(&transforms, player, &camera, &physics);
+
+        for (entity, drone, member, transform) in (&entities, &mut drones, &mut squad, &mut transforms).join() {
+            if healths.get(entity).map_or(false, Health::is_down) {
+                continue;
+            }
+            let position = *transform.translation();
+            let ground = physics.ground_height(position.x, position.z).unwrap_or(bounds.0.y);
+
+            if drone.is_empty() {
+                let y = (position.y - config.climb_rate * dt).max(ground + LANDED_HEIGHT);
+                transform.set_translation_y(y);
+                continue;
+            }
+            drone.battery = (drone.battery - dt).max(0.0);
+
+            let velocity = if camera.drone() == Some(entity) {
+                transform.append_rotation_y_axis(intent.look.x);
+                transform.rotation() * Vector3::new(intent.locomotion.x, 0.0, -intent.locomotion.y) * config.speed
+            } else {
+                let goal = match member.order {
+                    Order::MoveTo(target) => Some(target),
+                    Order::Follow => leader,
+                    // Drones cannot take guards down or bark
+                    _ => None,
+                };
+                let to = goal.map_or(Vector3::zeros(), |goal| Vector3::new(goal.x - position.x, 0.0, goal.z - position.z));
+                let distance = to.norm();
+                let stop = if member.order == Order::Follow { FOLLOW_DISTANCE } else { ARRIVE_DISTANCE };
+                if distance <= stop {
+                    if let Order::MoveTo(_) = member.order {
+                        member.order = Order::Hold;
+                    }
+                    Vector3::zeros()
+                } else {
+                    transform.set_rotation_y_axis((-to.x).atan2(-to.z));
+                    to / distance * config.speed.min(distance / dt.max(std::f32::EPSILON))
+                }
+            };
+
+            // Stay over the map, easing towards the flying height over the ground below
+            let x = (position.x + velocity.x * dt).max(bounds.0.x).min(bounds.1.x);
+            let z = (position.z + velocity.z * dt).max(bounds.0.z).min(bounds.1.z);
+            let target = physics.ground_height(x, z).unwrap_or(ground) + config.altitude;
+            let climb = (target - position.y).max(-config.climb_rate * dt).min(config.climb_rate * dt);
+            transform.set_translation_xyz(x, position.y + climb, z);
+        }
+    }
+}
+
+// Point on the ground below the player's body
+fn leader_position(transforms: &WriteStorage<Transform>, player: Entity, camera: &CameraState, physics: &Physics) -> Option<Vector3<f32>> {
+    camera.body_position()
+        .or_else(|| transforms.get(player).map(world_position))
+        .map(|position| route::ground(physics, position))
+}
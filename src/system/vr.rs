@@ -0,0 +1,64 @@
+use crate::{
+    config::VrConfig,
+    intent::PlayerIntent,
+    sky::weather::Weather,
+    system::sky::FOG_FAR_MARGIN,
+    vr::{self, Eye, VrRig},
+};
+use amethyst::{
+    ecs::*,
+    core::transform::components::Transform,
+    renderer::camera::Camera,
+    window::ScreenDimensions,
+};
+
+/* VR rig system */
+// Poses the head from the tracked head pose and keeps the eye offsets
+// and projections in line with the IPD, window size and the weather
+#[derive(Default)]
+pub struct VrRigSystem {
+    aspect: f32,
+    zfar: f32,
+}
+
+impl<'s> System<'s> for VrRigSystem {
+    type SystemData = (
+        WriteStorage<'s, Transform>,
+        WriteStorage<'s, Camera>,
+        Option<Read<'s, VrRig>>,
+        Option<Read<'s, Weather>>,
+        Read<'s, PlayerIntent>,
+        Read<'s, VrConfig>,
+        ReadExpect<'s, ScreenDimensions>,
+        );
+
+    fn run(&mut self, (mut transforms, mut cameras, rig, weather, intent, config, screen): Self::SystemData) {
+        let rig = match rig {
+            Some(rig) => *rig,
+            None => return,
+        };
+
+        if let Some(head) = transforms.get_mut(rig.head) {
+            head.set_translation(intent.head.position);
+            head.set_rotation(intent.head.orientation);
+        }
+
+        // Each eye gets half of the window
+        let aspect = screen.width() * 0.5 / screen.height();
+        // The fog's last sheet hides the far side of the map, the same as on the desktop
+        let zfar = weather.and_then(|weather| weather.visibility())
+            .map_or(config.zfar, |visibility| (visibility * FOG_FAR_MARGIN).min(config.zfar));
+        let changed = (aspect - self.aspect).abs() > std::f32::EPSILON || (zfar - self.zfar).abs() > std::f32::EPSILON;
+        for eye in [Eye::Left, Eye::Right].iter() {
+            let entity = rig.eye(*eye);
+            if let Some(transform) = transforms.get_mut(entity) {
+                transform.set_translation_x(eye.offset(config.ipd));
+            }
+            if changed {
+                let _ = cameras.insert(entity, vr::eye_camera(*eye, aspect, zfar, &config));
+            }
+        }
+        self.aspect = aspect;
+        self.zfar = zfar;
+    }
+}
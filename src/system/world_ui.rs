@@ -0,0 +1,315 @@
+use std::collections::HashMap;
+use crate::{
+    config::VrConfig,
+    intent::PlayerIntent,
+    state::{PlayerEntity, SceneVertices},
+    system::hands::world_position,
+    vr::panel,
+};
+use amethyst::{
+    assets::{AssetStorage, Handle, Loader},
+    core::{
+        math::{Point3, UnitQuaternion, Vector3},
+        transform::{Parent, Transform},
+        HiddenPropagate,
+    },
+    ecs::*,
+    renderer::{
+        debug_drawing::DebugLines,
+        palette::Srgba,
+        shape::Shape,
+        transparent::Transparent,
+        Material, MaterialDefaults, Mesh, Texture,
+    },
+    shrev::EventChannel,
+    ui::{FontAsset, Interactable, UiEvent, UiEventType, UiImage, UiText, UiTransform},
+    window::ScreenDimensions,
+};
+
+// Depth between stacked UI layers on the panel, in metres per unit of UiTransform z
+const LAYER_SPACING: f32 = 0.0005;
+const PRESS_THRESHOLD: f32 = 0.5;
+// Length of the laser drawn when it misses the panel
+const LASER_LENGTH: f32 = 5.0;
+
+// What a mirrored widget currently shows, so its texture is only rebuilt on change
+#[derive(Clone, PartialEq)]
+enum Look {
+    Image(UiImage),
+    Text(String, [f32; 4], f32),
+}
+
+/* World UI resource */
+// The panel the screen-space UI is mirrored onto and the quads standing in for each widget
+#[derive(Default)]
+pub struct WorldUi {
+    panel: Option<Entity>,
+    quads: HashMap<Entity, (Entity, Option<Look>)>,
+    mesh: Option<Handle<Mesh>>,
+    hovered: Option<Entity>,
+    pressed: Option<Entity>,
+    trigger_held: bool,
+}
+
+/* World UI system */
+// Mirrors every UI widget onto a quad on a panel in front of the player,
+// using the same layout the screen-space UI computed, and hides the
+// screen-space version
+#[derive(Default)]
+pub struct WorldUiSystem;
+
+impl<'s> System<'s> for WorldUiSystem {
+    type SystemData = (
+        Write<'s, WorldUi>,
+        ReadStorage<'s, UiTransform>,
+        ReadStorage<'s, UiImage>,
+        ReadStorage<'s, UiText>,
+        WriteStorage<'s, Transform>,
+        WriteStorage<'s, Parent>,
+        WriteStorage<'s, HiddenPropagate>,
+        WriteStorage<'s, Handle<Mesh>>,
+        WriteStorage<'s, Handle<Material>>,
+        WriteStorage<'s, Transparent>,
+        ReadExpect<'s, Loader>,
+        Read<'s, AssetStorage<Mesh>>,
+        Read<'s, AssetStorage<Texture>>,
+        Read<'s, AssetStorage<Material>>,
+        Read<'s, AssetStorage<FontAsset>>,
+        ReadExpect<'s, MaterialDefaults>,
+        Read<'s, VrConfig>,
+        Read<'s, PlayerEntity>,
+        ReadExpect<'s, ScreenDimensions>,
+        Entities<'s>,
+        );
+
+    fn run(&mut self, (
+        mut world_ui, ui_transforms, ui_images, ui_texts, mut transforms, mut parents, mut hidden,
+        mut meshes, mut materials, mut transparent, loader, mesh_storage, texture_storage,
+        material_storage, font_storage, material_defaults, config, player_entity, screen, entities,
+        ): Self::SystemData) {
+        if !config.world_ui {
+            return;
+        }
+
+        // Forget quads whose widget is gone
+        let gone: Vec<Entity> = world_ui.quads.keys()
+            .filter(|ui| !entities.is_alive(**ui) || !ui_transforms.contains(**ui))
+            .cloned()
+            .collect();
+        for ui in gone {
+            if let Some((quad, _)) = world_ui.quads.remove(&ui) {
+                let _ = entities.delete(quad);
+            }
+        }
+
+        let any_ui = (&ui_transforms).join().next().is_some();
+        let panel = match world_ui.panel {
+            Some(panel) if !any_ui => {
+                let _ = entities.delete(panel);
+                world_ui.panel = None;
+                return;
+            },
+            None if !any_ui => return,
+            Some(panel) => panel,
+            // Put a new panel in front of the player, facing them
+            None => {
+                let player = entities.entity(player_entity.index());
+                let (position, yaw) = match transforms.get(player) {
+                    Some(transform) => {
+                        let forward = transform.rotation() * -Vector3::z();
+                        (world_position(transform), (-forward.x).atan2(-forward.z))
+                    },
+                    None => (Vector3::zeros(), 0.0),
+                };
+                let rotation = UnitQuaternion::from_axis_angle(&Vector3::y_axis(), yaw);
+
+                let mut transform = Transform::default();
+                transform.set_translation(position + rotation * -Vector3::z() * config.ui_distance);
+                transform.set_rotation(rotation);
+
+                let panel = entities.create();
+                let _ = transforms.insert(panel, transform);
+                world_ui.panel = Some(panel);
+                panel
+            },
+        };
+
+        if world_ui.mesh.is_none() {
+            let data = Shape::Plane(None).generate::<SceneVertices>(None);
+            world_ui.mesh = Some(loader.load_from_data(data.into(), (), &mesh_storage));
+        }
+        let mesh = world_ui.mesh.clone().unwrap();
+
+        for (ui, ui_transform) in (&entities, &ui_transforms).join() {
+            // The screen-space UI stays around for layout but is not drawn
+            if parents.get(ui).is_none() && !hidden.contains(ui) {
+                let _ = hidden.insert(ui, HiddenPropagate::default());
+            }
+
+            let look = if let Some(text) = ui_texts.get(ui) {
+                Some(Look::Text(text.text.clone(), text.color, text.font_size))
+            } else {
+                ui_images.get(ui).map(|image| Look::Image(image.clone()))
+            };
+
+            let quad = match world_ui.quads.get(&ui) {
+                Some((quad, _)) => *quad,
+                None => {
+                    let quad = entities.create();
+                    let _ = parents.insert(quad, Parent { entity: panel });
+                    let _ = meshes.insert(quad, mesh.clone());
+                    world_ui.quads.insert(ui, (quad, None));
+                    quad
+                },
+            };
+
+            // Lay the quad out where the widget sits on screen
+            let mut transform = Transform::default();
+            transform.set_translation_xyz(
+                (ui_transform.pixel_x() - screen.width() * 0.5) * config.ui_scale,
+                (ui_transform.pixel_y() - screen.height() * 0.5) * config.ui_scale,
+                ui_transform.global_z() * LAYER_SPACING,
+            );
+            transform.set_scale(Vector3::new(
+                ui_transform.pixel_width() * config.ui_scale * 0.5,
+                ui_transform.pixel_height() * config.ui_scale * 0.5,
+                1.0,
+            ));
+            let _ = transforms.insert(quad, transform);
+
+            let previous = world_ui.quads.get(&ui).and_then(|(_, look)| look.clone());
+            if look == previous {
+                continue;
+            }
+
+            let texture = match &look {
+                Some(Look::Image(UiImage::Texture(texture))) => Some(texture.clone()),
+                Some(Look::Image(UiImage::SolidColor(colour))) => {
+                    Some(loader.load_from_data(panel::solid_texture(*colour), (), &texture_storage))
+                },
+                Some(Look::Text(text, colour, size)) => {
+                    let font = ui_texts.get(ui).and_then(|t| font_storage.get(&t.font));
+                    match font {
+                        Some(font) => Some(loader.load_from_data(
+                            panel::rasterize_text(
+                                &font.0, text, *size, *colour,
+                                ui_transform.pixel_width() as u32, ui_transform.pixel_height() as u32,
+                            ),
+                            (),
+                            &texture_storage,
+                        )),
+                        // Font still loading; try again next frame
+                        None => continue,
+                    }
+                },
+                _ => None,
+            };
+
+            match texture {
+                Some(texture) => {
+                    // Lit like the rest of the scene, the panel would go dark at night, so it glows instead
+                    let material = Material {
+                        albedo: texture.clone(),
+                        emission: texture,
+                        ..material_defaults.0.clone()
+                    };
+                    let _ = materials.insert(quad, loader.load_from_data(material, (), &material_storage));
+                    let _ = transparent.insert(quad, Transparent);
+                },
+                None => {
+                    materials.remove(quad);
+                },
+            }
+            world_ui.quads.insert(ui, (quad, look));
+        }
+    }
+}
+
+/* Laser pointer system */
+// Points the right hand at the panel and turns hovering and trigger
+// presses into the same UiEvents the mouse would produce
+#[derive(Default)]
+pub struct LaserPointerSystem;
+
+impl<'s> System<'s> for LaserPointerSystem {
+    type SystemData = (
+        Write<'s, WorldUi>,
+        Write<'s, EventChannel<UiEvent>>,
+        Write<'s, DebugLines>,
+        ReadStorage<'s, UiTransform>,
+        ReadStorage<'s, Interactable>,
+        ReadStorage<'s, Transform>,
+        Read<'s, PlayerIntent>,
+        Read<'s, PlayerEntity>,
+        Read<'s, VrConfig>,
+        ReadExpect<'s, ScreenDimensions>,
+        Entities<'s>,
+        );
+
+    fn run(&mut self, (mut world_ui, mut events, mut debug_lines, ui_transforms, interactables, transforms, intent, player_entity, config, screen, entities): Self::SystemData) {
+        if !config.world_ui || !intent.right.tracked {
+            return;
+        }
+        let (panel, player) = match (world_ui.panel, transforms.get(entities.entity(player_entity.index()))) {
+            (Some(panel), Some(player)) => match transforms.get(panel) {
+                Some(panel) => (panel, player),
+                None => return,
+            },
+            _ => return,
+        };
+
+        let pose = intent.right.pose;
+        let origin = world_position(player) + player.rotation() * pose.position;
+        let direction = player.rotation() * pose.orientation * -Vector3::z();
+
+        let pointer = panel::pointer_position(
+            *panel.translation(), panel.rotation(), origin, direction,
+            config.ui_scale, (screen.width(), screen.height()),
+        );
+        let target = pointer.and_then(|(x, y)| {
+            (&entities, &ui_transforms, &interactables).join()
+                .filter(|(_, transform, _)| transform.position_inside(x, y))
+                .max_by(|(_, a, _), (_, b, _)| a.global_z().partial_cmp(&b.global_z()).unwrap())
+                .map(|(entity, _, _)| entity)
+        });
+
+        let end = match pointer {
+            Some((x, y)) => {
+                let local = Vector3::new(
+                    (x - screen.width() * 0.5) * config.ui_scale,
+                    (y - screen.height() * 0.5) * config.ui_scale,
+                    0.0,
+                );
+                panel.translation() + panel.rotation() * local
+            },
+            None => origin + direction * LASER_LENGTH,
+        };
+        debug_lines.draw_line(Point3::from(origin), Point3::from(end), Srgba::new(1.0, 0.65, 0.0, 1.0));
+
+        if target != world_ui.hovered {
+            if let Some(old) = world_ui.hovered {
+                events.single_write(UiEvent::new(UiEventType::HoverStop, old));
+            }
+            if let Some(new) = target {
+                events.single_write(UiEvent::new(UiEventType::HoverStart, new));
+            }
+            world_ui.hovered = target;
+        }
+
+        let trigger = intent.right.trigger > PRESS_THRESHOLD;
+        if trigger && !world_ui.trigger_held {
+            if let Some(target) = target {
+                events.single_write(UiEvent::new(UiEventType::ClickStart, target));
+                world_ui.pressed = Some(target);
+            }
+        } else if !trigger && world_ui.trigger_held {
+            if let Some(pressed) = world_ui.pressed.take() {
+                events.single_write(UiEvent::new(UiEventType::ClickStop, pressed));
+                if Some(pressed) == target {
+                    events.single_write(UiEvent::new(UiEventType::Click, pressed));
+                }
+            }
+        }
+        world_ui.trigger_held = trigger;
+    }
+}
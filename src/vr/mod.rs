@@ -0,0 +1,91 @@
+pub mod panel;
+pub mod render;
+
+use amethyst::{
+    prelude::*,
+    core::{
+        math::{Matrix4, Vector3},
+        transform::{Parent, Transform},
+    },
+    ecs::Entity,
+    renderer::camera::{Camera, CustomMatrix, Projection},
+};
+use crate::config::VrConfig;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Eye {
+    Left,
+    Right,
+}
+
+impl Eye {
+    // Sideways offset of this eye from the centre of the head, given the IPD
+    pub fn offset(&self, ipd: f32) -> f32 {
+        match self {
+            Eye::Left => -ipd * 0.5,
+            Eye::Right => ipd * 0.5,
+        }
+    }
+}
+
+/* VR camera rig resource */
+// The head follows the tracked head pose as a child of the player
+// entity; each eye is a child of the head offset by half the IPD
+#[derive(Clone, Copy, Debug)]
+pub struct VrRig {
+    pub head: Entity,
+    pub left_eye: Entity,
+    pub right_eye: Entity,
+}
+
+impl VrRig {
+    pub fn build(world: &mut World, player: Entity, config: &VrConfig) -> Self {
+        let head = world.create_entity()
+            .with(Parent { entity: player })
+            .with(Transform::default())
+            .build();
+
+        let mut eye = |eye: Eye| {
+            let mut transform = Transform::default();
+            transform.set_translation_x(eye.offset(config.ipd));
+
+            world.create_entity()
+                .with(Parent { entity: head })
+                .with(transform)
+                .with(eye_camera(eye, 1.0, config.zfar, config))
+                .build()
+        };
+        let left_eye = eye(Eye::Left);
+        let right_eye = eye(Eye::Right);
+
+        VrRig {
+            head: head,
+            left_eye: left_eye,
+            right_eye: right_eye,
+        }
+    }
+
+    pub fn eye(&self, eye: Eye) -> Entity {
+        match eye {
+            Eye::Left => self.left_eye,
+            Eye::Right => self.right_eye,
+        }
+    }
+}
+
+// Camera for one eye. In the mock compositor both eyes share one window,
+// so the right eye's image is squeezed into the right half of clip space;
+// the left eye is drawn with a half-width viewport instead. Nothing beyond
+// `zfar` is drawn
+pub fn eye_camera(eye: Eye, aspect: f32, zfar: f32, config: &VrConfig) -> Camera {
+    let projection = Projection::perspective(aspect, config.fov.to_radians(), config.znear, zfar);
+
+    match eye {
+        Eye::Left => Camera::from(projection),
+        Eye::Right => {
+            let squeeze = Matrix4::new_translation(&Vector3::new(0.5, 0.0, 0.0))
+                * Matrix4::new_nonuniform_scaling(&Vector3::new(0.5, 1.0, 1.0));
+            Camera::from(Projection::CustomMatrix(CustomMatrix::new(squeeze * projection.as_matrix())))
+        },
+    }
+}
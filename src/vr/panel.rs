@@ -0,0 +1,93 @@
+use amethyst::{
+    core::math::{UnitQuaternion, Vector3},
+    renderer::{
+        rendy::{
+            hal::{
+                format::Format,
+                image::{Filter, Kind, SamplerInfo, ViewKind, WrapMode},
+            },
+            texture::TextureBuilder,
+        },
+        types::TextureData,
+    },
+};
+use rusttype::{point, Font, Scale};
+
+/* World-space UI panel helpers */
+
+// RGBA texture of `text` drawn in `colour` on a transparent background,
+// laid out roughly the way amethyst_ui centres label text
+pub fn rasterize_text(font: &Font<'static>, text: &str, font_size: f32, colour: [f32; 4], width: u32, height: u32) -> TextureData {
+    let (width, height) = (width.max(1), height.max(1));
+    let mut pixels = vec![0u8; (width * height * 4) as usize];
+
+    let scale = Scale::uniform(font_size);
+    let metrics = font.v_metrics(scale);
+    let glyphs: Vec<_> = font.layout(text, scale, point(0.0, metrics.ascent)).collect();
+    let text_width = glyphs.last()
+        .map(|g| g.position().x + g.unpositioned().h_metrics().advance_width)
+        .unwrap_or(0.0);
+    let offset_x = ((width as f32 - text_width) * 0.5).max(0.0);
+    let offset_y = ((height as f32 - (metrics.ascent - metrics.descent)) * 0.5).max(0.0);
+
+    for glyph in glyphs {
+        if let Some(bounds) = glyph.pixel_bounding_box() {
+            glyph.draw(|x, y, coverage| {
+                let px = x as i32 + bounds.min.x + offset_x as i32;
+                let py = y as i32 + bounds.min.y + offset_y as i32;
+                if px < 0 || py < 0 || px >= width as i32 || py >= height as i32 {
+                    return;
+                }
+
+                let i = ((py as u32 * width + px as u32) * 4) as usize;
+                pixels[i] = (colour[0] * 255.0) as u8;
+                pixels[i + 1] = (colour[1] * 255.0) as u8;
+                pixels[i + 2] = (colour[2] * 255.0) as u8;
+                pixels[i + 3] = pixels[i + 3].max((coverage * colour[3] * 255.0) as u8);
+            });
+        }
+    }
+
+    rgba_texture(pixels, width, height)
+}
+
+pub fn solid_texture(colour: [f32; 4]) -> TextureData {
+    let pixel = colour.iter().map(|c| (c * 255.0) as u8).collect();
+    rgba_texture(pixel, 1, 1)
+}
+
+fn rgba_texture(pixels: Vec<u8>, width: u32, height: u32) -> TextureData {
+    TextureBuilder::new()
+        .with_data_width(width)
+        .with_data_height(height)
+        .with_kind(Kind::D2(width, height, 1, 1))
+        .with_view_kind(ViewKind::D2)
+        .with_sampler_info(SamplerInfo::new(Filter::Linear, WrapMode::Clamp))
+        .with_raw_data(pixels, Format::Rgba8Srgb)
+        .into()
+}
+
+// Where a ray hits the panel, in UI pixels with the origin at the bottom
+// left of the screen like UiTransform's pixel coordinates
+pub fn pointer_position(
+    panel_position: Vector3<f32>,
+    panel_rotation: &UnitQuaternion<f32>,
+    origin: Vector3<f32>,
+    direction: Vector3<f32>,
+    scale: f32,
+    screen: (f32, f32),
+    ) -> Option<(f32, f32)> {
+    let normal = panel_rotation * Vector3::z();
+    let facing = direction.dot(&normal);
+    if facing.abs() < std::f32::EPSILON {
+        return None;
+    }
+
+    let t = (panel_position - origin).dot(&normal) / facing;
+    if t <= 0.0 {
+        return None;
+    }
+
+    let local = panel_rotation.inverse() * (origin + direction * t - panel_position);
+    Some((local.x / scale + screen.0 * 0.5, local.y / scale + screen.1 * 0.5))
+}
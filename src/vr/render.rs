@@ -0,0 +1,184 @@
+use amethyst::{
+    ecs::World,
+    renderer::{
+        bundle::{RenderOrder, RenderPlan, RenderPlugin, Target},
+        pass::{DrawPbr3DDesc, DrawPbr3DTransparentDesc},
+        rendy::{
+            command::{QueueId, RenderPassEncoder},
+            factory::Factory,
+            graph::{
+                render::{PrepareResult, RenderGroup, RenderGroupDesc},
+                GraphContext, NodeBuffer, NodeImage,
+            },
+            hal::{
+                command::{AttachmentClear, ClearColor},
+                pass::Subpass,
+                pso::{ClearRect, Rect},
+            },
+        },
+        types::Backend,
+        ActiveCamera,
+    },
+};
+use crate::{
+    sky::render::sky_colour,
+    vr::{Eye, VrRig},
+};
+
+/* Stereo render plugin */
+// Mock compositor: draws the scene once per eye into the two halves of
+// the window. Each eye pass only swaps the active camera to that eye, so
+// an OpenXR swapchain can later take the same eye passes as its targets
+#[derive(Debug)]
+pub struct RenderStereo {
+    clear: [f32; 4],
+}
+
+impl RenderStereo {
+    // `clear` must match the window's clear colour; the sky's colour replaces it once there is one
+    pub fn new(clear: [f32; 4]) -> Self {
+        RenderStereo { clear: clear }
+    }
+}
+
+impl<B: Backend> RenderPlugin<B> for RenderStereo {
+    fn on_plan(
+        &mut self,
+        plan: &mut RenderPlan<B>,
+        _factory: &mut Factory<B>,
+        _world: &World,
+        ) -> Result<(), amethyst::Error> {
+        let clear = self.clear;
+        plan.extend_target(Target::Main, move |ctx| {
+            // The right eye spills into the left half, which the left eye pass clears before drawing.
+            // Each eye draws its transparent objects, like the fog's sheets, before the next eye starts
+            ctx.add(RenderOrder::Opaque, EyeGroupDesc::new(Eye::Right, Some(clear), DrawPbr3DDesc::new()).builder())?;
+            ctx.add(RenderOrder::Opaque as i32 + 1, EyeGroupDesc::new(Eye::Right, None, DrawPbr3DTransparentDesc::new()).builder())?;
+            ctx.add(RenderOrder::Opaque as i32 + 2, EyeGroupDesc::new(Eye::Left, Some(clear), DrawPbr3DDesc::new()).builder())?;
+            ctx.add(RenderOrder::Opaque as i32 + 3, EyeGroupDesc::new(Eye::Left, None, DrawPbr3DTransparentDesc::new()).builder())?;
+            Ok(())
+        });
+
+        Ok(())
+    }
+}
+
+/* Per-eye render group */
+// Without a `clear` colour the group draws over what the eye's earlier groups drew
+#[derive(Debug)]
+pub struct EyeGroupDesc<D> {
+    eye: Eye,
+    clear: Option<[f32; 4]>,
+    inner: D,
+}
+
+impl<D> EyeGroupDesc<D> {
+    pub fn new(eye: Eye, clear: Option<[f32; 4]>, inner: D) -> Self {
+        EyeGroupDesc {
+            eye: eye,
+            clear: clear,
+            inner: inner,
+        }
+    }
+}
+
+impl<B: Backend, D: RenderGroupDesc<B, World>> RenderGroupDesc<B, World> for EyeGroupDesc<D> {
+    fn depth(&self) -> bool {
+        self.inner.depth()
+    }
+
+    fn colors(&self) -> usize {
+        self.inner.colors()
+    }
+
+    fn build(
+        self,
+        ctx: &GraphContext<B>,
+        factory: &mut Factory<B>,
+        queue: QueueId,
+        aux: &World,
+        framebuffer_width: u32,
+        framebuffer_height: u32,
+        subpass: Subpass<'_, B>,
+        buffers: Vec<NodeBuffer>,
+        images: Vec<NodeImage>,
+        ) -> Result<Box<dyn RenderGroup<B, World>>, failure::Error> {
+        // The left eye's viewport covers the left half; the right eye relies on its projection
+        let width = match self.eye {
+            Eye::Left => framebuffer_width / 2,
+            Eye::Right => framebuffer_width,
+        };
+        let inner = self.inner.build(ctx, factory, queue, aux, width, framebuffer_height, subpass, buffers, images)?;
+
+        Ok(Box::new(EyeGroup {
+            eye: self.eye,
+            clear: self.clear,
+            half: Rect {
+                x: 0,
+                y: 0,
+                w: (framebuffer_width / 2) as i16,
+                h: framebuffer_height as i16,
+            },
+            inner: inner,
+        }))
+    }
+}
+
+#[derive(Debug)]
+pub struct EyeGroup<B: Backend> {
+    eye: Eye,
+    clear: Option<[f32; 4]>,
+    half: Rect,
+    inner: Box<dyn RenderGroup<B, World>>,
+}
+
+impl<B: Backend> RenderGroup<B, World> for EyeGroup<B> {
+    fn prepare(
+        &mut self,
+        factory: &Factory<B>,
+        queue: QueueId,
+        index: usize,
+        subpass: Subpass<'_, B>,
+        aux: &World,
+        ) -> PrepareResult {
+        let eye = match aux.try_fetch::<VrRig>() {
+            Some(rig) => rig.eye(self.eye),
+            None => return self.inner.prepare(factory, queue, index, subpass, aux),
+        };
+
+        // Camera uniforms are gathered during prepare, so the swap only needs to last that long
+        let previous = aux.fetch_mut::<ActiveCamera>().entity.replace(eye);
+        let result = self.inner.prepare(factory, queue, index, subpass, aux);
+        aux.fetch_mut::<ActiveCamera>().entity = previous;
+
+        result
+    }
+
+    fn draw_inline(
+        &mut self,
+        encoder: RenderPassEncoder<'_, B>,
+        index: usize,
+        subpass: Subpass<'_, B>,
+        aux: &World,
+        ) {
+        let mut encoder = encoder;
+        if let (Eye::Left, Some(clear)) = (self.eye, self.clear) {
+            unsafe {
+                encoder.clear_attachments(
+                    vec![
+                        AttachmentClear::Color { index: 0, value: ClearColor::Sfloat(sky_colour(aux, clear)) },
+                        // Depth is reversed, so the far plane is 0
+                        AttachmentClear::DepthStencil { depth: Some(0.0), stencil: None },
+                    ],
+                    Some(ClearRect { rect: self.half, layers: 0..1 }),
+                );
+            }
+        }
+
+        self.inner.draw_inline(encoder, index, subpass, aux);
+    }
+
+    fn dispose(self: Box<Self>, factory: &mut Factory<B>, aux: &World) {
+        self.inner.dispose(factory, aux);
+    }
+}